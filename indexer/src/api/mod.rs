@@ -0,0 +1,540 @@
+mod models;
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use alloy::{
+    network::Ethereum,
+    primitives::Address,
+    providers::{Provider, RootProvider},
+};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{rejection::QueryRejection, FromRef, Path, Query, State},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Json, Router,
+};
+use futures::Stream;
+use indexer_database::{
+    last_index_block_helper, liquidation_opportunities_helper, reserves_table_helper,
+    user_debt_collateral_helper,
+    users_tables_helper::{self, AccountsSortColumn, UserCurrentLocation},
+};
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use tracing::{error, info, instrument};
+
+use crate::{
+    blockchain_manager::BlockchainManager,
+    config::LocalConfig,
+    metrics::METRICS,
+    user_events::{UserEventFilter, UserEvents},
+};
+pub use models::*;
+
+/// Number of accounts returned per page when no limit is given
+const DEFAULT_PAGE_SIZE: u64 = 100;
+/// Maximum number of accounts returned per page
+const MAX_PAGE_SIZE: u64 = 1000;
+/// Time a readiness check may take before it fails, so probes don't hang on a dead RPC
+const READINESS_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves the indexed data over HTTP
+///
+/// Every route is read only and versioned under `/v1`, the schemas of a version don't
+/// change once released. Decimal values are returned as strings so no precision is lost.
+///
+/// Routes:
+/// * `GET /v1/accounts` - Liquidatable or at-risk accounts, sorted and paginated
+/// * `GET /v1/accounts/{user_address}` - An account with its positions
+/// * `GET /v1/reserves` - The Aave reserves
+/// * `GET /v1/status` - Sync status of the users indexer
+/// * `GET /v1/events` - Server-sent events of the users' tier and health factor changes
+/// * `GET /metrics` - Prometheus metrics, unversioned
+/// * `GET /healthz` - Liveness, unversioned
+/// * `GET /readyz` - Readiness, unversioned
+pub struct ApiServer;
+
+/// State shared by the routes
+#[derive(Clone)]
+struct ApiState {
+    db: DatabaseConnection,
+    local_config: Arc<LocalConfig>,
+    user_events: Arc<UserEvents>,
+    /// Provider of the readiness probes, without retries so a dead RPC fails the probe
+    provider: RootProvider<Ethereum>,
+}
+
+impl FromRef<ApiState> for DatabaseConnection {
+    fn from_ref(state: &ApiState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<ApiState> for Arc<LocalConfig> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.local_config.clone()
+    }
+}
+
+impl FromRef<ApiState> for Arc<UserEvents> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.user_events.clone()
+    }
+}
+
+impl FromRef<ApiState> for RootProvider<Ethereum> {
+    fn from_ref(state: &ApiState) -> Self {
+        state.provider.clone()
+    }
+}
+
+impl ApiServer {
+    /// Starts serving the routes on `API_ADDRESS`
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Arc wrapped local configuration
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<JoinHandle<Result<()>>>` - A handle to the spawned server task, or an
+    ///   error if the address can't be bound
+    #[instrument("API_SERVER", skip(db, local_config, user_events))]
+    pub async fn start_api_server(
+        db: &DatabaseConnection,
+        local_config: &Arc<LocalConfig>,
+        user_events: &Arc<UserEvents>,
+    ) -> Result<JoinHandle<Result<()>>> {
+        let listener = tokio::net::TcpListener::bind(&local_config.api_address)
+            .await
+            .context(format!(
+                "Failed to bind API server to {}",
+                local_config.api_address
+            ))?;
+        let provider = BlockchainManager::get_probe_provider(local_config).await?;
+
+        let router = Router::new()
+            .route("/v1/accounts", get(Self::get_accounts))
+            .route("/v1/accounts/{user_address}", get(Self::get_account))
+            .route("/v1/reserves", get(Self::get_reserves))
+            .route("/v1/status", get(Self::get_status))
+            .route("/v1/events", get(Self::get_events))
+            .route("/metrics", get(Self::get_metrics))
+            .route("/healthz", get(Self::get_health))
+            .route("/readyz", get(Self::get_readiness))
+            .fallback(|| async { ApiError::NotFound("Route not found".to_string()) })
+            .with_state(ApiState {
+                db: db.clone(),
+                local_config: local_config.clone(),
+                user_events: user_events.clone(),
+                provider,
+            });
+
+        let handle = tokio::spawn(async move {
+            info!("Starting API server on {}", listener.local_addr()?);
+
+            axum::serve(listener, router)
+                .await
+                .context("API server stopped")?;
+
+            Ok(())
+        });
+
+        Ok(handle)
+    }
+
+    /// Lists the accounts of a tier
+    ///
+    /// Query parameters: `tier` (`liquidatable` or `at_risk`), `sort` (`health_factor`
+    /// or `debt`), `order` (`asc` or `desc`), `limit` and `offset`.
+    async fn get_accounts(
+        State(db): State<DatabaseConnection>,
+        query: Result<Query<AccountsQuery>, QueryRejection>,
+    ) -> Result<Json<ApiResponse<Page<AccountV1>>>, ApiError> {
+        let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if limit == 0 || limit > MAX_PAGE_SIZE {
+            return Err(ApiError::BadRequest(format!(
+                "limit must be between 1 and {}",
+                MAX_PAGE_SIZE
+            )));
+        }
+        let offset = query.offset.unwrap_or(0);
+
+        let location = match query.tier {
+            TierParam::Liquidatable => UserCurrentLocation::Liquidatable,
+            TierParam::AtRisk => UserCurrentLocation::AtRisk,
+        };
+        let sort_column = match query.sort {
+            SortParam::HealthFactor => AccountsSortColumn::HealthFactor,
+            SortParam::Debt => AccountsSortColumn::TotalDebt,
+        };
+
+        let (users, total) = users_tables_helper::get_users_in_tier_page(
+            &db,
+            location,
+            sort_column,
+            matches!(query.order, OrderParam::Desc),
+            limit,
+            offset,
+        )
+        .await
+        .context("Failed to get accounts")?;
+
+        Ok(Json(ApiResponse::new(Page {
+            items: users.into_iter().map(AccountV1::from).collect(),
+            limit,
+            offset,
+            total,
+        })))
+    }
+
+    /// Returns an account with its positions and its best liquidation
+    async fn get_account(
+        State(db): State<DatabaseConnection>,
+        Path(user_address): Path<String>,
+    ) -> Result<Json<ApiResponse<AccountDetailsV1>>, ApiError> {
+        // Addresses are stored checksummed, accept any casing
+        let user_address = Address::from_str(&user_address)
+            .map_err(|_| ApiError::BadRequest(format!("Invalid address {}", user_address)))?
+            .to_string();
+
+        let user = users_tables_helper::get_user(&db, &user_address)
+            .await
+            .context(format!("Failed to get account {}", user_address))?
+            .ok_or_else(|| ApiError::NotFound(format!("Account {} not found", user_address)))?;
+
+        let positions = user_debt_collateral_helper::get_users_positions(
+            &db,
+            std::slice::from_ref(&user_address),
+        )
+        .await
+        .context(format!("Failed to get positions of {}", user_address))?;
+
+        let liquidation =
+            liquidation_opportunities_helper::get_liquidation_opportunity(&db, &user_address)
+                .await
+                .context(format!("Failed to get liquidation of {}", user_address))?;
+
+        Ok(Json(ApiResponse::new(AccountDetailsV1 {
+            account: AccountV1::from(user),
+            positions: positions.into_iter().map(PositionV1::from).collect(),
+            liquidation: liquidation.map(LiquidationV1::from),
+        })))
+    }
+
+    /// Returns the Aave reserves
+    async fn get_reserves(
+        State(db): State<DatabaseConnection>,
+    ) -> Result<Json<ApiResponse<Vec<ReserveV1>>>, ApiError> {
+        let reserves = reserves_table_helper::get_reserves(&db)
+            .await
+            .context("Failed to get reserves")?;
+
+        Ok(Json(ApiResponse::new(
+            reserves.into_iter().map(ReserveV1::from).collect(),
+        )))
+    }
+
+    /// Returns the sync status of the users indexer
+    async fn get_status(
+        State(db): State<DatabaseConnection>,
+    ) -> Result<Json<ApiResponse<StatusV1>>, ApiError> {
+        let last_index_block = last_index_block_helper::get_last_index_block(&db)
+            .await
+            .context("Failed to get last indexed block")?;
+
+        Ok(Json(ApiResponse::new(StatusV1::from(last_index_block))))
+    }
+
+    /// Streams the users' tier transitions and health factor moves as server-sent events
+    ///
+    /// Query parameters: `tier` (comma separated `liquidatable`, `at_risk` or `healthy`),
+    /// `min_debt` (in USD) and `reserve` (address of a reserve the user holds). Events
+    /// are only sent from the subscription on, a `lagged` event reports the events a
+    /// slow subscriber missed.
+    async fn get_events(
+        State(user_events): State<Arc<UserEvents>>,
+        query: Result<Query<EventsQuery>, QueryRejection>,
+    ) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+        let Query(query) = query.map_err(|e| ApiError::BadRequest(e.body_text()))?;
+        let filter = Self::parse_events_filter(query)?;
+
+        let stream = futures::stream::unfold(
+            (user_events.subscribe(), filter),
+            |(mut receiver, filter)| async move {
+                loop {
+                    let event = match receiver.recv().await {
+                        Ok(event) if filter.matches(&event) => {
+                            let event = UserEventV1::from(event);
+                            Event::default()
+                                .event(event.kind)
+                                .json_data(ApiResponse::new(event))
+                        }
+                        Ok(_) => continue,
+                        Err(RecvError::Lagged(skipped)) => Event::default()
+                            .event("lagged")
+                            .json_data(ApiResponse::new(LaggedV1 { skipped })),
+                        Err(RecvError::Closed) => return None,
+                    };
+
+                    return Some((event, (receiver, filter)));
+                }
+            },
+        );
+
+        Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+    }
+
+    /// Returns the metrics in the Prometheus text format
+    ///
+    /// The account gauges are counted from the database on every scrape. When the count
+    /// fails, the gauges keep their last values and the rest of the registry is still served.
+    async fn get_metrics(State(db): State<DatabaseConnection>) -> Result<Response, ApiError> {
+        match users_tables_helper::count_users_per_tier(&db).await {
+            Ok(counts) => {
+                for (location, count) in counts {
+                    METRICS
+                        .accounts
+                        .with_label_values(&[location.name()])
+                        .set(count as i64);
+                }
+            }
+            Err(e) => error!("Failed to count accounts: {}", e),
+        }
+
+        let metrics = METRICS.render().context("Failed to render metrics")?;
+
+        Ok((
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            metrics,
+        )
+            .into_response())
+    }
+
+    /// Answers as long as the process serves requests, whatever the state of the services
+    async fn get_health() -> &'static str {
+        "ok"
+    }
+
+    /// Returns whether the indexer is ready to serve up to date data
+    ///
+    /// Responds `503 Service Unavailable` when a check fails: the database is
+    /// unreachable, the indexed block lags the chain head by more than `MAX_BLOCK_LAG`
+    /// times `READINESS_BLOCK_LAG_FACTOR`, or a liquidatable user wasn't refreshed for
+    /// `LIQUIDATABLE_USERS_UPDATE_FREQUENCY` times `READINESS_LIQUIDATABLE_STALENESS_FACTOR`
+    /// seconds. Checks that can't run, e.g. on a dead RPC, fail.
+    async fn get_readiness(
+        State(db): State<DatabaseConnection>,
+        State(local_config): State<Arc<LocalConfig>>,
+        State(provider): State<RootProvider<Ethereum>>,
+    ) -> Response {
+        let (database, block_lag, liquidatable_staleness) = tokio::join!(
+            Self::run_readiness_check("database", Self::check_database(&db)),
+            Self::run_readiness_check(
+                "block_lag",
+                Self::check_block_lag(&db, &provider, &local_config),
+            ),
+            Self::run_readiness_check(
+                "liquidatable_staleness",
+                Self::check_liquidatable_staleness(&db, &local_config),
+            ),
+        );
+
+        let checks = vec![database, block_lag, liquidatable_staleness];
+        let ready = checks.iter().all(|check| check.ok);
+        let status = if ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+
+        (
+            status,
+            Json(ApiResponse::new(ReadinessV1 { ready, checks })),
+        )
+            .into_response()
+    }
+
+    /// Runs a readiness check within `READINESS_CHECK_TIMEOUT`
+    ///
+    /// # Arguments
+    /// * `name` - Name of the check in the response
+    /// * `check` - The check, resolving to whether it passed and what was measured
+    ///
+    /// # Returns
+    /// * `ReadinessCheckV1` - Outcome of the check, failed if it errored or timed out
+    async fn run_readiness_check(
+        name: &'static str,
+        check: impl std::future::Future<Output = Result<(bool, String)>>,
+    ) -> ReadinessCheckV1 {
+        let (ok, detail) = match tokio::time::timeout(READINESS_CHECK_TIMEOUT, check).await {
+            Ok(Ok((ok, detail))) => (ok, detail),
+            Ok(Err(e)) => (false, format!("{:#}", e)),
+            Err(_) => (
+                false,
+                format!("Timed out after {:?}", READINESS_CHECK_TIMEOUT),
+            ),
+        };
+        if !ok {
+            error!("Readiness check {} failed: {}", name, detail);
+        }
+
+        ReadinessCheckV1 { name, ok, detail }
+    }
+
+    /// Checks that the database answers
+    async fn check_database(db: &DatabaseConnection) -> Result<(bool, String)> {
+        db.ping().await.context("Database unreachable")?;
+
+        Ok((true, "Database reachable".to_string()))
+    }
+
+    /// Checks that the last indexed block is close enough to the chain head
+    async fn check_block_lag(
+        db: &DatabaseConnection,
+        provider: &RootProvider<Ethereum>,
+        local_config: &LocalConfig,
+    ) -> Result<(bool, String)> {
+        let last_index_block = last_index_block_helper::get_last_index_block(db)
+            .await
+            .context("Failed to get last indexed block")?;
+        let chain_head = METRICS
+            .observe_rpc("eth_blockNumber", provider.get_block_number().await)
+            .context("Failed to get chain head")?;
+
+        let block_lag = chain_head.saturating_sub(last_index_block.block_number as u64);
+        let max_block_lag = local_config.max_block_lag * local_config.readiness_block_lag_factor;
+
+        Ok((
+            block_lag <= max_block_lag,
+            format!(
+                "Indexed block {} is {} blocks behind the chain head {} (max {})",
+                last_index_block.block_number, block_lag, chain_head, max_block_lag
+            ),
+        ))
+    }
+
+    /// Checks that the liquidatable users are refreshed on time
+    async fn check_liquidatable_staleness(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+    ) -> Result<(bool, String)> {
+        let oldest_refresh = users_tables_helper::get_oldest_liquidatable_user_timestamp(db)
+            .await
+            .context("Failed to get oldest liquidatable refresh")?;
+        let Some(oldest_refresh) = oldest_refresh else {
+            return Ok((true, "No liquidatable user".to_string()));
+        };
+
+        let staleness = (chrono::Utc::now() - oldest_refresh).num_seconds().max(0) as u64;
+        let max_staleness = local_config.liquidatable_users_update_frequency
+            * local_config.readiness_liquidatable_staleness_factor;
+
+        Ok((
+            staleness <= max_staleness,
+            format!(
+                "Oldest liquidatable user refreshed {}s ago (max {}s)",
+                staleness, max_staleness
+            ),
+        ))
+    }
+
+    /// Parses the query parameters of the events stream into a filter
+    ///
+    /// # Arguments
+    /// * `query` - Query parameters of the events stream
+    ///
+    /// # Returns
+    /// * `Result<UserEventFilter, ApiError>` - The filter, or a bad request error for an
+    ///   invalid parameter
+    fn parse_events_filter(query: EventsQuery) -> Result<UserEventFilter, ApiError> {
+        let locations = query
+            .tier
+            .iter()
+            .flat_map(|tiers| tiers.split(','))
+            .map(str::trim)
+            .filter(|tier| !tier.is_empty())
+            .map(|tier| match tier {
+                "liquidatable" => Ok(UserCurrentLocation::Liquidatable),
+                "at_risk" => Ok(UserCurrentLocation::AtRisk),
+                "healthy" => Ok(UserCurrentLocation::Healthy),
+                _ => Err(ApiError::BadRequest(format!("Invalid tier {}", tier))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let min_debt_in_usd = query
+            .min_debt
+            .map(|min_debt| {
+                Decimal::from_str(&min_debt)
+                    .map_err(|_| ApiError::BadRequest(format!("Invalid min_debt {}", min_debt)))
+            })
+            .transpose()?;
+
+        let reserve = query
+            .reserve
+            .map(|reserve| {
+                Address::from_str(&reserve)
+                    .map(|address| address.to_string())
+                    .map_err(|_| ApiError::BadRequest(format!("Invalid reserve {}", reserve)))
+            })
+            .transpose()?;
+
+        Ok(UserEventFilter {
+            locations,
+            min_debt_in_usd,
+            reserve,
+        })
+    }
+}
+
+/// Error returned by a route, rendered as a versioned JSON error
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::Internal(e) => {
+                let error_message = e
+                    .chain()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" -> ");
+                error!("API request failed with error: {}", error_message);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Internal server error".to_string(),
+                )
+            }
+        };
+
+        (
+            status,
+            Json(ApiErrorResponse {
+                version: API_VERSION,
+                error,
+            }),
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,348 @@
+use indexer_database::{
+    entities::{last_index_block, liquidation_opportunities, reserves, user_debt_collateral},
+    users_tables_helper::{UserCurrentLocation, UserDetails},
+};
+use sea_orm::ActiveEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::user_events::{UserEvent, UserEventKind};
+
+/// Version of the response schemas, bumped on any breaking change
+pub const API_VERSION: &str = "v1";
+
+/// Envelope of every successful response
+#[derive(Debug, Serialize)]
+pub struct ApiResponse<T> {
+    pub version: &'static str,
+    pub data: T,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            version: API_VERSION,
+            data,
+        }
+    }
+}
+
+/// Envelope of every error response
+#[derive(Debug, Serialize)]
+pub struct ApiErrorResponse {
+    pub version: &'static str,
+    pub error: String,
+}
+
+/// A page of items
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub limit: u64,
+    pub offset: u64,
+    /// Number of items across all pages
+    pub total: u64,
+}
+
+/// Tier of the accounts to list
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TierParam {
+    #[default]
+    Liquidatable,
+    AtRisk,
+}
+
+/// Value the accounts are sorted by
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortParam {
+    #[default]
+    HealthFactor,
+    Debt,
+}
+
+/// Sort order of the accounts
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderParam {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Query parameters of the accounts list
+#[derive(Debug, Default, Deserialize)]
+pub struct AccountsQuery {
+    #[serde(default)]
+    pub tier: TierParam,
+    #[serde(default)]
+    pub sort: SortParam,
+    #[serde(default)]
+    pub order: OrderParam,
+    pub limit: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+/// An account, decimal values are strings to keep their precision
+#[derive(Debug, Serialize)]
+pub struct AccountV1 {
+    pub user_address: String,
+    /// `liquidatable`, `at_risk` or `healthy`
+    pub tier: &'static str,
+    pub health_factor: String,
+    pub projected_health_factor: String,
+    pub total_collateral_value_in_usd: String,
+    pub total_debt_value_in_usd: String,
+    pub emode_category: i32,
+    pub is_in_isolation_mode: bool,
+    pub has_siloed_borrowing: bool,
+    pub leading_collateral_reserve: String,
+    pub leading_debt_reserve: String,
+    pub leading_collateral_reserve_value: String,
+    pub leading_debt_reserve_value: String,
+    pub last_updated_block_number: i32,
+    /// RFC 3339 time of the last refresh
+    pub updated_at: String,
+}
+
+impl From<UserDetails> for AccountV1 {
+    fn from(user: UserDetails) -> Self {
+        Self {
+            tier: user.current_location.name(),
+            user_address: user.user_address,
+            health_factor: user.health_factor.to_string(),
+            projected_health_factor: user.projected_health_factor.to_string(),
+            total_collateral_value_in_usd: user.total_collateral_value_in_usd.to_string(),
+            total_debt_value_in_usd: user.total_debt_value_in_usd.to_string(),
+            emode_category: user.emode_category,
+            is_in_isolation_mode: user.is_in_isolation_mode,
+            has_siloed_borrowing: user.has_siloed_borrowing,
+            leading_collateral_reserve: user.leading_collateral_reserve,
+            leading_debt_reserve: user.leading_debt_reserve,
+            leading_collateral_reserve_value: user.leading_collateral_reserve_value.to_string(),
+            leading_debt_reserve_value: user.leading_debt_reserve_value.to_string(),
+            last_updated_block_number: user.last_updated_block_number,
+            updated_at: user.timestamp.to_rfc3339(),
+        }
+    }
+}
+
+/// An account with its positions and best liquidation
+#[derive(Debug, Serialize)]
+pub struct AccountDetailsV1 {
+    #[serde(flatten)]
+    pub account: AccountV1,
+    pub positions: Vec<PositionV1>,
+    /// Most profitable liquidation, only set for liquidatable accounts
+    pub liquidation: Option<LiquidationV1>,
+}
+
+/// A collateral or debt position of an account
+#[derive(Debug, Serialize)]
+pub struct PositionV1 {
+    pub reserve_address: String,
+    pub is_collateral: bool,
+    /// `none` for collateral, `stable` or `variable` for debt
+    pub rate_mode: String,
+    pub amount: String,
+    pub amount_raw: Option<String>,
+    pub value_in_usd: Option<String>,
+    /// Whether the collateral counts as collateral for the user, `false` for debt
+    pub usage_as_collateral_enabled: bool,
+}
+
+impl From<user_debt_collateral::Model> for PositionV1 {
+    fn from(position: user_debt_collateral::Model) -> Self {
+        Self {
+            reserve_address: position.reserve_address,
+            is_collateral: position.is_collateral,
+            rate_mode: position.rate_mode.to_value(),
+            amount: position.amount.to_string(),
+            amount_raw: position.amount_raw,
+            value_in_usd: position.value_in_usd.map(|value| value.to_string()),
+            usage_as_collateral_enabled: position.usage_as_collateral_enabled,
+        }
+    }
+}
+
+/// The most profitable liquidation of an account
+#[derive(Debug, Serialize)]
+pub struct LiquidationV1 {
+    pub collateral_reserve: String,
+    pub debt_reserve: String,
+    pub debt_to_cover: String,
+    pub debt_to_cover_in_usd: String,
+    pub collateral_to_seize: String,
+    pub collateral_to_seize_in_usd: String,
+    pub profit_in_usd: String,
+    pub last_updated_block_number: i32,
+}
+
+impl From<liquidation_opportunities::Model> for LiquidationV1 {
+    fn from(opportunity: liquidation_opportunities::Model) -> Self {
+        Self {
+            collateral_reserve: opportunity.collateral_reserve,
+            debt_reserve: opportunity.debt_reserve,
+            debt_to_cover: opportunity.debt_to_cover.to_string(),
+            debt_to_cover_in_usd: opportunity.debt_to_cover_in_usd.to_string(),
+            collateral_to_seize: opportunity.collateral_to_seize.to_string(),
+            collateral_to_seize_in_usd: opportunity.collateral_to_seize_in_usd.to_string(),
+            profit_in_usd: opportunity.profit_in_usd.to_string(),
+            last_updated_block_number: opportunity.last_updated_block_number,
+        }
+    }
+}
+
+/// An Aave reserve with its configuration and last cached price
+#[derive(Debug, Serialize)]
+pub struct ReserveV1 {
+    pub reserve_address: String,
+    pub symbol: String,
+    pub decimals: i32,
+    pub a_token_address: String,
+    pub stable_debt_token_address: String,
+    pub variable_debt_token_address: String,
+    pub is_active: bool,
+    pub is_frozen: bool,
+    pub is_paused: bool,
+    pub borrowing_enabled: bool,
+    pub usage_as_collateral_enabled: bool,
+    pub ltv: String,
+    pub liquidation_threshold: String,
+    pub liquidation_bonus: String,
+    pub liquidation_protocol_fee: String,
+    pub emode_category: i32,
+    pub debt_ceiling: String,
+    pub isolation_mode_total_debt: String,
+    pub siloed_borrowing: bool,
+    pub borrow_cap: String,
+    pub supply_cap: String,
+    /// Last oracle price in USD, `0` until the price watcher reads it
+    pub price: String,
+}
+
+impl From<reserves::Model> for ReserveV1 {
+    fn from(reserve: reserves::Model) -> Self {
+        Self {
+            reserve_address: reserve.reserve_address,
+            symbol: reserve.symbol,
+            decimals: reserve.decimals,
+            a_token_address: reserve.a_token_address,
+            stable_debt_token_address: reserve.stable_debt_token_address,
+            variable_debt_token_address: reserve.variable_debt_token_address,
+            is_active: reserve.is_active,
+            is_frozen: reserve.is_frozen,
+            is_paused: reserve.is_paused,
+            borrowing_enabled: reserve.borrowing_enabled,
+            usage_as_collateral_enabled: reserve.usage_as_collateral_enabled,
+            ltv: reserve.ltv.to_string(),
+            liquidation_threshold: reserve.liquidation_threshold.to_string(),
+            liquidation_bonus: reserve.liquidation_bonus.to_string(),
+            liquidation_protocol_fee: reserve.liquidation_protocol_fee.to_string(),
+            emode_category: reserve.emode_category,
+            debt_ceiling: reserve.debt_ceiling.to_string(),
+            isolation_mode_total_debt: reserve.isolation_mode_total_debt.to_string(),
+            siloed_borrowing: reserve.siloed_borrowing,
+            borrow_cap: reserve.borrow_cap.to_string(),
+            supply_cap: reserve.supply_cap.to_string(),
+            price: reserve.price.to_string(),
+        }
+    }
+}
+
+/// Sync status of the users indexer
+#[derive(Debug, Serialize)]
+pub struct StatusV1 {
+    pub last_indexed_block: i32,
+    /// RFC 3339 time the last indexed block was recorded
+    pub last_indexed_at: String,
+}
+
+impl From<last_index_block::Model> for StatusV1 {
+    fn from(last_index_block: last_index_block::Model) -> Self {
+        Self {
+            last_indexed_block: last_index_block.block_number,
+            last_indexed_at: last_index_block.timestamp.and_utc().to_rfc3339(),
+        }
+    }
+}
+
+/// Query parameters of the user events stream
+#[derive(Debug, Default, Deserialize)]
+pub struct EventsQuery {
+    /// Comma separated tiers the user must be in after the event, e.g. `liquidatable,at_risk`
+    pub tier: Option<String>,
+    /// Minimum debt of the user in USD
+    pub min_debt: Option<String>,
+    /// Reserve the user must hold as collateral or debt
+    pub reserve: Option<String>,
+}
+
+/// A tier transition or health factor move of a user, sent as a `tier_changed` or
+/// `health_factor_changed` server-sent event
+#[derive(Debug, Serialize)]
+pub struct UserEventV1 {
+    /// `tier_changed` or `health_factor_changed`
+    pub kind: &'static str,
+    pub user_address: String,
+    /// Tier before the refresh, `null` for a new user
+    pub old_tier: Option<&'static str>,
+    pub new_tier: &'static str,
+    /// Health factor before the refresh, `null` for a new user
+    pub old_health_factor: Option<String>,
+    pub health_factor: String,
+    pub total_debt_value_in_usd: String,
+    /// Reserves the user holds as collateral or debt
+    pub reserves: Vec<String>,
+    pub block_number: u64,
+    /// RFC 3339 time the change was written
+    pub timestamp: String,
+}
+
+impl From<UserEvent> for UserEventV1 {
+    fn from(event: UserEvent) -> Self {
+        Self {
+            kind: match event.kind {
+                UserEventKind::TierChanged => "tier_changed",
+                UserEventKind::HealthFactorChanged => "health_factor_changed",
+            },
+            user_address: event.user_address,
+            old_tier: (event.old_location != UserCurrentLocation::NotFound)
+                .then(|| event.old_location.name()),
+            new_tier: event.new_location.name(),
+            old_health_factor: event
+                .old_health_factor
+                .map(|health_factor| health_factor.to_string()),
+            health_factor: event.health_factor.to_string(),
+            total_debt_value_in_usd: event.total_debt_value_in_usd.to_string(),
+            reserves: event.reserves,
+            block_number: event.block_number,
+            timestamp: event.timestamp.to_rfc3339(),
+        }
+    }
+}
+
+/// Sent as a `lagged` server-sent event when the subscriber was too slow and missed events
+#[derive(Debug, Serialize)]
+pub struct LaggedV1 {
+    /// Number of events missed
+    pub skipped: u64,
+}
+
+/// Readiness of the indexer, returned by `/readyz`
+#[derive(Debug, Serialize)]
+pub struct ReadinessV1 {
+    /// True if every check passed
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheckV1>,
+}
+
+/// Outcome of a readiness check
+#[derive(Debug, Serialize)]
+pub struct ReadinessCheckV1 {
+    /// `database`, `block_lag` or `liquidatable_staleness`
+    pub name: &'static str,
+    pub ok: bool,
+    /// What was measured, or why the check couldn't run
+    pub detail: String,
+}
@@ -3,15 +3,18 @@ pub mod multicall;
 use alloy::{
     network::Ethereum,
     primitives::Address,
-    providers::{Provider, ProviderBuilder},
+    providers::{Provider, ProviderBuilder, RootProvider},
     rpc::client::RpcClient,
     transports::{http::reqwest::Url, layers::RetryBackoffLayer},
 };
-use anyhow::{Ok, Result};
+use anyhow::{Context, Ok, Result};
 
 use crate::{
     config::LocalConfig,
-    utils::contracts::{AavePoolContract, AavePoolDataProviderContract},
+    utils::contracts::{
+        AaveOracleContract, AavePoolAddressesProviderContract, AavePoolContract,
+        AavePoolDataProviderContract,
+    },
 };
 
 /// BlockchainManager handles blockchain-related operations and connections.
@@ -22,6 +25,7 @@ pub struct AaveHelperContract<'a, P: Provider<Ethereum>> {
     pub pool_contract: AavePoolContract::AavePoolContractInstance<(), &'a P>,
     pub pool_data_provider_contract:
         AavePoolDataProviderContract::AavePoolDataProviderContractInstance<(), &'a P>,
+    pub oracle_contract: AaveOracleContract::AaveOracleContractInstance<(), &'a P>,
 }
 
 impl BlockchainManager {
@@ -47,6 +51,20 @@ impl BlockchainManager {
         Ok(provider)
     }
 
+    /// Creates a provider that doesn't retry failed requests, for health probes that
+    /// must report a dead RPC quickly
+    ///
+    /// # Arguments
+    /// * `local_config` - Local configuration containing the RPC URL
+    ///
+    /// # Returns
+    /// * `Result<RootProvider<Ethereum>>` - A Result containing either the provider instance or an error
+    pub async fn get_probe_provider(local_config: &LocalConfig) -> Result<RootProvider<Ethereum>> {
+        let client = RpcClient::builder().http(Url::parse(&local_config.rpc_url)?);
+
+        Ok(RootProvider::new(client))
+    }
+
     pub async fn get_aave_helper_contracts<'a, P: Provider<Ethereum>>(
         provider: &'a P,
         local_config: &LocalConfig,
@@ -62,23 +80,66 @@ impl BlockchainManager {
                 local_config.pool_data_provider.parse()?,
             )
             .await?,
+            oracle_contract: Self::get_aave_oracle_contract(
+                provider,
+                local_config.price_oracle.parse()?,
+            )
+            .await?,
         };
         Ok(contract)
     }
 
-    pub async fn get_aave_pool_contract<'a, P: Provider<Ethereum>>(
-        provider: &'a P,
+    /// Resolves the pool configurator of a pool from its addresses provider
+    ///
+    /// # Arguments
+    /// * `provider` - Blockchain provider
+    /// * `pool_address` - Address of the Aave pool
+    ///
+    /// # Returns
+    /// * `Result<Address>` - Address of the pool configurator
+    pub async fn get_pool_configurator_address<P: Provider<Ethereum>>(
+        provider: &P,
+        pool_address: Address,
+    ) -> Result<Address> {
+        let addresses_provider = AavePoolContract::new(pool_address, provider)
+            .ADDRESSES_PROVIDER()
+            .call()
+            .await
+            .context("Failed to get the pool addresses provider")?
+            ._0;
+
+        let pool_configurator =
+            AavePoolAddressesProviderContract::new(addresses_provider, provider)
+                .getPoolConfigurator()
+                .call()
+                .await
+                .context("Failed to get the pool configurator")?
+                ._0;
+
+        Ok(pool_configurator)
+    }
+
+    pub async fn get_aave_pool_contract<P: Provider<Ethereum>>(
+        provider: &P,
         address: Address,
-    ) -> Result<AavePoolContract::AavePoolContractInstance<(), &'a P>> {
+    ) -> Result<AavePoolContract::AavePoolContractInstance<(), &P>> {
         let contract = AavePoolContract::new(address, provider);
         Ok(contract)
     }
 
-    pub async fn get_aave_pool_data_provider_contract<'a, P: Provider<Ethereum>>(
-        provider: &'a P,
+    pub async fn get_aave_pool_data_provider_contract<P: Provider<Ethereum>>(
+        provider: &P,
         address: Address,
-    ) -> Result<AavePoolDataProviderContract::AavePoolDataProviderContractInstance<(), &'a P>> {
+    ) -> Result<AavePoolDataProviderContract::AavePoolDataProviderContractInstance<(), &P>> {
         let contract = AavePoolDataProviderContract::new(address, provider);
         Ok(contract)
     }
+
+    pub async fn get_aave_oracle_contract<P: Provider<Ethereum>>(
+        provider: &P,
+        address: Address,
+    ) -> Result<AaveOracleContract::AaveOracleContractInstance<(), &P>> {
+        let contract = AaveOracleContract::new(address, provider);
+        Ok(contract)
+    }
 }
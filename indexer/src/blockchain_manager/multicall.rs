@@ -5,9 +5,12 @@ use alloy::{
 };
 use anyhow::Result;
 
-use crate::utils::contracts::{
-    Multicall3::Call3,
-    MulticallContract::{self, MulticallContractInstance},
+use crate::{
+    metrics::METRICS,
+    utils::contracts::{
+        Multicall3::Call3,
+        MulticallContract::{self, MulticallContractInstance},
+    },
 };
 
 const MULTICALL_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
@@ -19,8 +22,7 @@ pub struct MulticallManager<P: Provider<Ethereum>> {
 
 impl<P: Provider<Ethereum>> MulticallManager<P> {
     pub async fn new(provider: P) -> Result<Self> {
-        let multicall =
-            MulticallContract::new(MULTICALL_ADDRESS.parse::<Address>()?, provider);
+        let multicall = MulticallContract::new(MULTICALL_ADDRESS.parse::<Address>()?, provider);
 
         Ok(Self {
             multicall_contract: multicall,
@@ -57,12 +59,19 @@ impl<P: Provider<Ethereum>> MulticallManager<P> {
     }
 
     pub async fn execute_calls(&self, block_number: u64) -> Result<Vec<Bytes>> {
+        let timer = METRICS.multicall_duration.start_timer();
         let multicall_result = self
             .multicall_contract
             .aggregate3(self.calls.clone())
             .block(block_number.into())
             .call()
-            .await?;
+            .await;
+        timer.observe_duration();
+
+        if multicall_result.is_err() {
+            METRICS.multicall_failures.inc();
+        }
+        let multicall_result = METRICS.observe_rpc("eth_call", multicall_result)?;
         let mut results = vec![];
 
         for i in 0..multicall_result.returnData.len() {
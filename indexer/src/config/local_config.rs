@@ -1,6 +1,12 @@
-use anyhow::Result;
+use std::str::FromStr;
+
+use alloy::primitives::Address;
+use anyhow::{Context, Result};
+use rust_decimal::Decimal;
+use tracing::info;
 
 use super::env_helper::load_env_var;
+use crate::blockchain_manager::BlockchainManager;
 
 #[derive(Debug, Clone)]
 pub struct LocalConfig {
@@ -8,26 +14,50 @@ pub struct LocalConfig {
     pub start_block: u64,
     pub pool_address: String,
     pub pool_data_provider: String,
+    /// Empty until resolved from the pool's addresses provider when not configured
+    pub pool_configurator: String,
     pub price_oracle: String,
     pub log_per_request: u64,
+    pub backfill_workers: u64,
+    pub multicall_max_calls: u64,
+    pub updater_concurrency: u64,
+    pub updater_cycle_budget: u64,
     pub max_block_lag: u64,
+    pub reorg_confirmation_window: u64,
     pub max_cap_on_health_factor: u64,
-    pub at_risk_health_factor: f64,
+    pub at_risk_health_factor: Decimal,
     pub liquidatable_users_update_frequency: u64,
     pub at_risk_users_update_frequency: u64,
     pub healthy_users_update_frequency: u64,
+    pub reserves_refresh_frequency: u64,
+    pub price_poll_interval: u64,
+    pub price_change_threshold: Decimal,
+    pub health_factor_change_threshold: Decimal,
+    pub api_address: String,
+    pub readiness_block_lag_factor: u64,
+    pub readiness_liquidatable_staleness_factor: u64,
 }
 
 impl LocalConfig {
+    /// Loads the configuration from the environment
+    ///
+    /// # Returns
+    /// * `Result<Self>` - The configuration, or an error if a variable is missing or invalid
     pub fn load_from_env() -> Result<Self> {
-        Ok(Self {
+        let local_config = Self {
             rpc_url: load_env_var("RPC_URL")?,
             start_block: load_env_var("START_BLOCK")?,
             pool_address: load_env_var("POOL_ADDRESS")?,
             pool_data_provider: load_env_var("POOL_DATA_PROVIDER")?,
+            pool_configurator: std::env::var("POOL_CONFIGURATOR").unwrap_or_default(),
             price_oracle: load_env_var("PRICE_ORACLE")?,
             log_per_request: load_env_var("LOG_PER_REQUEST")?,
+            backfill_workers: load_env_var("BACKFILL_WORKERS")?,
+            multicall_max_calls: load_env_var("MULTICALL_MAX_CALLS")?,
+            updater_concurrency: load_env_var("UPDATER_CONCURRENCY")?,
+            updater_cycle_budget: load_env_var("UPDATER_CYCLE_BUDGET")?,
             max_block_lag: load_env_var("MAX_BLOCK_LAG")?,
+            reorg_confirmation_window: load_env_var("REORG_CONFIRMATION_WINDOW")?,
             max_cap_on_health_factor: load_env_var("MAX_CAP_ON_HEALTH_FACTOR")?,
             at_risk_health_factor: load_env_var("AT_RISK_HEALTH_FACTOR")?,
             liquidatable_users_update_frequency: load_env_var(
@@ -35,6 +65,54 @@ impl LocalConfig {
             )?,
             at_risk_users_update_frequency: load_env_var("AT_RISK_USERS_UPDATE_FREQUENCY")?,
             healthy_users_update_frequency: load_env_var("HEALTHY_USERS_UPDATE_FREQUENCY")?,
-        })
+            reserves_refresh_frequency: load_env_var("RESERVES_REFRESH_FREQUENCY")?,
+            price_poll_interval: load_env_var("PRICE_POLL_INTERVAL")?,
+            price_change_threshold: load_env_var("PRICE_CHANGE_THRESHOLD")?,
+            health_factor_change_threshold: load_env_var("HEALTH_FACTOR_CHANGE_THRESHOLD")?,
+            api_address: load_env_var("API_ADDRESS")?,
+            readiness_block_lag_factor: load_env_var("READINESS_BLOCK_LAG_FACTOR")?,
+            readiness_liquidatable_staleness_factor: load_env_var(
+                "READINESS_LIQUIDATABLE_STALENESS_FACTOR",
+            )?,
+        };
+
+        // Invalid contract addresses would only fail once a service uses them, the pool
+        // configurator is resolved later when empty
+        let mut addresses = vec![
+            ("POOL_ADDRESS", &local_config.pool_address),
+            ("POOL_DATA_PROVIDER", &local_config.pool_data_provider),
+            ("PRICE_ORACLE", &local_config.price_oracle),
+        ];
+        if !local_config.pool_configurator.is_empty() {
+            addresses.push(("POOL_CONFIGURATOR", &local_config.pool_configurator));
+        }
+        for (var_name, address) in addresses {
+            Address::from_str(address)
+                .context(format!("{} is not a valid address: {}", var_name, address))?;
+        }
+
+        Ok(local_config)
+    }
+
+    /// Resolves the pool configurator from the pool's addresses provider when
+    /// `POOL_CONFIGURATOR` is empty
+    ///
+    /// # Returns
+    /// * `Result<()>` - Error if the pool configurator can't be resolved
+    pub async fn resolve_pool_configurator(&mut self) -> Result<()> {
+        if !self.pool_configurator.is_empty() {
+            return Ok(());
+        }
+
+        let provider = BlockchainManager::get_provider(self).await?;
+        let pool_configurator =
+            BlockchainManager::get_pool_configurator_address(&provider, self.pool_address.parse()?)
+                .await
+                .context("POOL_CONFIGURATOR is empty and can't be resolved from the pool")?;
+
+        info!("Resolved pool configurator {}", pool_configurator);
+        self.pool_configurator = pool_configurator.to_string();
+
+        Ok(())
     }
 }
@@ -1,5 +1,12 @@
+pub mod api;
 mod blockchain_manager;
 pub mod config;
+pub mod liquidation;
+pub mod metrics;
+pub mod price_watcher;
+pub mod reserves_helper;
+pub mod stress_test;
+pub mod user_events;
 pub mod users_helper;
 pub mod users_indexer;
 pub mod users_updater_service;
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+
+use indexer_database::{
+    liquidation_opportunities_helper::LiquidationOpportunity,
+    user_debt_collateral_helper::UserPosition,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    reserves_helper::AaveReserve,
+    utils::constants::{
+        CLOSE_FACTOR_HF_THRESHOLD, DEFAULT_LIQUIDATION_CLOSE_FACTOR, LIQUIDATION_THRESHOLD,
+        MAX_LIQUIDATION_CLOSE_FACTOR,
+    },
+};
+
+/// Helper struct for evaluating liquidations of Aave positions
+pub struct LiquidationHelper;
+
+impl LiquidationHelper {
+    /// Finds the most profitable (collateral, debt) pair to liquidate for a user
+    ///
+    /// Every pair of a collateral usable in liquidations and a debt reserve is evaluated
+    /// the way the Aave pool does it: the liquidator repays up to the close factor of the
+    /// debt (50%, or 100% at a health factor of 0.95 or below) and receives the equivalent
+    /// collateral plus the liquidation bonus, minus the protocol's share of the bonus.
+    /// When the collateral can't cover the bonus, the debt to cover is reduced accordingly.
+    /// Collateral in the user's e-mode category gets the e-mode liquidation bonus.
+    /// Collateral the user disabled is skipped, the pool reverts liquidations seizing it.
+    ///
+    /// # Arguments
+    /// * `health_factor` - User's current health factor
+    /// * `emode_category` - E-mode category of the user, 0 when not in e-mode
+    /// * `collateral_assets` - Collateral positions of the user, valued in USD
+    /// * `debt_assets` - Debt positions of the user, valued in USD
+    /// * `aave_reserves` - List of Aave reserves with their liquidation parameters
+    ///
+    /// # Returns
+    /// * `Option<LiquidationOpportunity>` - The most profitable liquidation, `None` if the
+    ///   user can't be liquidated
+    pub fn find_best_liquidation(
+        health_factor: Decimal,
+        emode_category: u8,
+        collateral_assets: &[UserPosition],
+        debt_assets: &[UserPosition],
+        aave_reserves: &[AaveReserve],
+    ) -> Option<LiquidationOpportunity> {
+        if health_factor >= LIQUIDATION_THRESHOLD {
+            return None;
+        }
+
+        let close_factor = if health_factor <= CLOSE_FACTOR_HF_THRESHOLD {
+            MAX_LIQUIDATION_CLOSE_FACTOR
+        } else {
+            DEFAULT_LIQUIDATION_CLOSE_FACTOR
+        };
+
+        let reserves = aave_reserves
+            .iter()
+            .map(|reserve| (reserve.address.to_string(), reserve))
+            .collect::<HashMap<String, &AaveReserve>>();
+
+        // Stable and variable debt in the same reserve are repaid by a single liquidation
+        let mut debt_per_reserve: Vec<(&String, Decimal, Decimal)> = Vec::new();
+        for position in debt_assets {
+            match debt_per_reserve
+                .iter_mut()
+                .find(|(reserve_address, _, _)| **reserve_address == position.reserve_address)
+            {
+                Some((_, amount, value)) => {
+                    *amount = amount.saturating_add(position.amount);
+                    *value = value.saturating_add(position.value_in_usd);
+                }
+                None => debt_per_reserve.push((
+                    &position.reserve_address,
+                    position.amount,
+                    position.value_in_usd,
+                )),
+            }
+        }
+
+        let mut best_liquidation: Option<LiquidationOpportunity> = None;
+
+        for collateral in collateral_assets {
+            if !collateral.usage_as_collateral_enabled {
+                continue;
+            }
+            let Some(collateral_reserve) = reserves.get(&collateral.reserve_address) else {
+                continue;
+            };
+            // Reserves that don't count as collateral can't be seized, whatever the e-mode
+            let (_, liquidation_bonus) = collateral_reserve.liquidation_parameters(emode_category);
+            if collateral_reserve.liquidation_threshold.is_zero()
+                || liquidation_bonus.is_zero()
+                || collateral.value_in_usd.is_zero()
+            {
+                continue;
+            }
+
+            for (debt_reserve, debt_amount, debt_value) in debt_per_reserve.iter() {
+                if debt_value.is_zero() {
+                    continue;
+                }
+
+                let Some(liquidation) = Self::evaluate_liquidation(
+                    collateral,
+                    liquidation_bonus,
+                    collateral_reserve.liquidation_protocol_fee,
+                    debt_reserve,
+                    *debt_amount,
+                    *debt_value,
+                    close_factor,
+                ) else {
+                    continue;
+                };
+
+                if best_liquidation
+                    .as_ref()
+                    .is_none_or(|best| liquidation.profit_in_usd > best.profit_in_usd)
+                {
+                    best_liquidation = Some(liquidation);
+                }
+            }
+        }
+
+        best_liquidation
+    }
+
+    /// Evaluates the liquidation of a single (collateral, debt) pair
+    ///
+    /// # Arguments
+    /// * `collateral` - Collateral position to seize
+    /// * `liquidation_bonus` - Liquidation bonus of the collateral for the user
+    /// * `liquidation_protocol_fee` - Share of the bonus kept by the protocol
+    /// * `debt_reserve` - Address of the debt reserve to repay
+    /// * `debt_amount` - Total debt of the user in the debt reserve
+    /// * `debt_value` - USD value of `debt_amount`
+    /// * `close_factor` - Share of the debt that can be repaid
+    ///
+    /// # Returns
+    /// * `Option<LiquidationOpportunity>` - The liquidation, `None` if a value overflows
+    fn evaluate_liquidation(
+        collateral: &UserPosition,
+        liquidation_bonus: Decimal,
+        liquidation_protocol_fee: Decimal,
+        debt_reserve: &str,
+        debt_amount: Decimal,
+        debt_value: Decimal,
+        close_factor: Decimal,
+    ) -> Option<LiquidationOpportunity> {
+        let mut debt_to_cover_in_usd = debt_value.checked_mul(close_factor)?;
+        let mut seized_in_usd = debt_to_cover_in_usd.checked_mul(liquidation_bonus)?;
+
+        // The user doesn't have enough collateral, only the debt it can pay for is covered
+        if seized_in_usd > collateral.value_in_usd {
+            seized_in_usd = collateral.value_in_usd;
+            debt_to_cover_in_usd = seized_in_usd.checked_div(liquidation_bonus)?;
+        }
+
+        let bonus_in_usd = seized_in_usd - seized_in_usd.checked_div(liquidation_bonus)?;
+        let protocol_fee_in_usd = bonus_in_usd.checked_mul(liquidation_protocol_fee)?;
+        let collateral_to_seize_in_usd = seized_in_usd - protocol_fee_in_usd;
+
+        Some(LiquidationOpportunity {
+            collateral_reserve: collateral.reserve_address.clone(),
+            debt_reserve: debt_reserve.to_string(),
+            debt_to_cover: debt_amount
+                .checked_mul(debt_to_cover_in_usd.checked_div(debt_value)?)?,
+            debt_to_cover_in_usd,
+            collateral_to_seize: collateral
+                .amount
+                .checked_mul(collateral_to_seize_in_usd.checked_div(collateral.value_in_usd)?)?,
+            collateral_to_seize_in_usd,
+            profit_in_usd: collateral_to_seize_in_usd - debt_to_cover_in_usd,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloy::primitives::Address;
+    use indexer_database::entities::sea_orm_active_enums::RateMode;
+
+    use super::*;
+    use crate::reserves_helper::{EModeCategory, ReserveConfiguration};
+
+    const COLLATERAL: Address = Address::repeat_byte(1);
+    const OTHER_COLLATERAL: Address = Address::repeat_byte(2);
+    const DEBT: Address = Address::repeat_byte(3);
+
+    fn reserve(
+        address: Address,
+        liquidation_bonus: Decimal,
+        liquidation_protocol_fee: Decimal,
+        emode_category: Option<EModeCategory>,
+    ) -> AaveReserve {
+        AaveReserve {
+            address,
+            symbol: address.to_string(),
+            decimals: 18,
+            a_token_address: Address::ZERO,
+            stable_debt_token_address: Address::ZERO,
+            variable_debt_token_address: Address::ZERO,
+            is_active: true,
+            is_frozen: false,
+            is_paused: false,
+            borrowing_enabled: true,
+            usage_as_collateral_enabled: true,
+            ltv: Decimal::new(75, 2),
+            liquidation_threshold: Decimal::new(8, 1),
+            liquidation_bonus,
+            liquidation_protocol_fee,
+            emode_category,
+            configuration: ReserveConfiguration::default(),
+        }
+    }
+
+    fn position(
+        reserve: Address,
+        amount: Decimal,
+        value_in_usd: Decimal,
+        rate_mode: RateMode,
+        usage_as_collateral_enabled: bool,
+    ) -> UserPosition {
+        UserPosition {
+            reserve_address: reserve.to_string(),
+            amount,
+            amount_raw: String::new(),
+            rate_mode,
+            value_in_usd,
+            usage_as_collateral_enabled,
+        }
+    }
+
+    fn collateral(reserve: Address, amount: Decimal, value_in_usd: Decimal) -> UserPosition {
+        position(reserve, amount, value_in_usd, RateMode::None, true)
+    }
+
+    fn debt(amount: Decimal, value_in_usd: Decimal) -> UserPosition {
+        position(DEBT, amount, value_in_usd, RateMode::Variable, false)
+    }
+
+    /// Reserves with a 5% liquidation bonus and no protocol fee
+    fn reserves() -> Vec<AaveReserve> {
+        vec![
+            reserve(COLLATERAL, Decimal::new(105, 2), Decimal::ZERO, None),
+            reserve(DEBT, Decimal::new(105, 2), Decimal::ZERO, None),
+        ]
+    }
+
+    #[test]
+    fn healthy_users_cant_be_liquidated() {
+        let liquidation = LiquidationHelper::find_best_liquidation(
+            Decimal::ONE,
+            0,
+            &[collateral(
+                COLLATERAL,
+                Decimal::from(100),
+                Decimal::from(10_000),
+            )],
+            &[debt(Decimal::from(1000), Decimal::from(1000))],
+            &reserves(),
+        );
+
+        assert!(liquidation.is_none());
+    }
+
+    #[test]
+    fn half_of_the_debt_is_covered_above_a_health_factor_of_0_95() {
+        let liquidation = LiquidationHelper::find_best_liquidation(
+            Decimal::new(9501, 4),
+            0,
+            &[collateral(
+                COLLATERAL,
+                Decimal::from(100),
+                Decimal::from(10_000),
+            )],
+            &[debt(Decimal::from(1000), Decimal::from(1000))],
+            &reserves(),
+        )
+        .unwrap();
+
+        assert_eq!(liquidation.collateral_reserve, COLLATERAL.to_string());
+        assert_eq!(liquidation.debt_reserve, DEBT.to_string());
+        assert_eq!(liquidation.debt_to_cover, Decimal::from(500));
+        assert_eq!(liquidation.debt_to_cover_in_usd, Decimal::from(500));
+        assert_eq!(liquidation.collateral_to_seize, Decimal::new(525, 2));
+        assert_eq!(liquidation.collateral_to_seize_in_usd, Decimal::from(525));
+        assert_eq!(liquidation.profit_in_usd, Decimal::from(25));
+    }
+
+    #[test]
+    fn the_whole_debt_is_covered_at_a_health_factor_of_0_95() {
+        let liquidation = LiquidationHelper::find_best_liquidation(
+            Decimal::new(95, 2),
+            0,
+            &[collateral(
+                COLLATERAL,
+                Decimal::from(100),
+                Decimal::from(10_000),
+            )],
+            &[debt(Decimal::from(1000), Decimal::from(1000))],
+            &reserves(),
+        )
+        .unwrap();
+
+        assert_eq!(liquidation.debt_to_cover, Decimal::from(1000));
+        assert_eq!(liquidation.collateral_to_seize_in_usd, Decimal::from(1050));
+        assert_eq!(liquidation.profit_in_usd, Decimal::from(50));
+    }
+
+    #[test]
+    fn the_protocol_fee_is_taken_from_the_bonus() {
+        let liquidation = LiquidationHelper::evaluate_liquidation(
+            &collateral(COLLATERAL, Decimal::from(100), Decimal::from(10_000)),
+            Decimal::new(105, 2),
+            Decimal::new(1, 1),
+            &DEBT.to_string(),
+            Decimal::from(1000),
+            Decimal::from(1000),
+            DEFAULT_LIQUIDATION_CLOSE_FACTOR,
+        )
+        .unwrap();
+
+        // 525 USD seized, 10% of the 25 USD bonus goes to the protocol
+        assert_eq!(liquidation.debt_to_cover_in_usd, Decimal::from(500));
+        assert_eq!(
+            liquidation.collateral_to_seize_in_usd,
+            Decimal::new(5225, 1)
+        );
+        assert_eq!(liquidation.collateral_to_seize, Decimal::new(5225, 3));
+        assert_eq!(liquidation.profit_in_usd, Decimal::new(225, 1));
+    }
+
+    #[test]
+    fn the_debt_to_cover_is_reduced_to_the_collateral_available() {
+        let liquidation = LiquidationHelper::evaluate_liquidation(
+            &collateral(COLLATERAL, Decimal::new(21, 1), Decimal::from(210)),
+            Decimal::new(105, 2),
+            Decimal::ZERO,
+            &DEBT.to_string(),
+            Decimal::from(1000),
+            Decimal::from(1000),
+            MAX_LIQUIDATION_CLOSE_FACTOR,
+        )
+        .unwrap();
+
+        assert_eq!(liquidation.debt_to_cover, Decimal::from(200));
+        assert_eq!(liquidation.debt_to_cover_in_usd, Decimal::from(200));
+        assert_eq!(liquidation.collateral_to_seize, Decimal::new(21, 1));
+        assert_eq!(liquidation.collateral_to_seize_in_usd, Decimal::from(210));
+        assert_eq!(liquidation.profit_in_usd, Decimal::from(10));
+    }
+
+    #[test]
+    fn the_emode_bonus_applies_to_users_in_the_category() {
+        let emode_category = EModeCategory {
+            id: 1,
+            label: "Stablecoins".to_string(),
+            liquidation_threshold: Decimal::new(97, 2),
+            liquidation_bonus: Decimal::new(102, 2),
+        };
+        let reserves = vec![
+            reserve(
+                COLLATERAL,
+                Decimal::new(105, 2),
+                Decimal::ZERO,
+                Some(emode_category),
+            ),
+            reserve(DEBT, Decimal::new(105, 2), Decimal::ZERO, None),
+        ];
+        let collateral_assets = [collateral(
+            COLLATERAL,
+            Decimal::from(100),
+            Decimal::from(10_000),
+        )];
+        let debt_assets = [debt(Decimal::from(1000), Decimal::from(1000))];
+
+        let in_emode = LiquidationHelper::find_best_liquidation(
+            Decimal::new(97, 2),
+            1,
+            &collateral_assets,
+            &debt_assets,
+            &reserves,
+        )
+        .unwrap();
+        let outside_emode = LiquidationHelper::find_best_liquidation(
+            Decimal::new(97, 2),
+            0,
+            &collateral_assets,
+            &debt_assets,
+            &reserves,
+        )
+        .unwrap();
+
+        assert_eq!(in_emode.profit_in_usd, Decimal::from(10));
+        assert_eq!(outside_emode.profit_in_usd, Decimal::from(25));
+    }
+
+    #[test]
+    fn disabled_collateral_is_never_seized() {
+        let mut reserves = reserves();
+        reserves.push(reserve(
+            OTHER_COLLATERAL,
+            Decimal::new(110, 2),
+            Decimal::ZERO,
+            None,
+        ));
+        let disabled_collateral = position(
+            OTHER_COLLATERAL,
+            Decimal::from(100),
+            Decimal::from(10_000),
+            RateMode::None,
+            false,
+        );
+        let debt_assets = [debt(Decimal::from(1000), Decimal::from(1000))];
+
+        // The disabled collateral has the higher bonus but can't be picked
+        let liquidation = LiquidationHelper::find_best_liquidation(
+            Decimal::new(9, 1),
+            0,
+            &[
+                disabled_collateral.clone(),
+                collateral(COLLATERAL, Decimal::from(100), Decimal::from(10_000)),
+            ],
+            &debt_assets,
+            &reserves,
+        )
+        .unwrap();
+        assert_eq!(liquidation.collateral_reserve, COLLATERAL.to_string());
+
+        let liquidation = LiquidationHelper::find_best_liquidation(
+            Decimal::new(9, 1),
+            0,
+            &[disabled_collateral],
+            &debt_assets,
+            &reserves,
+        );
+        assert!(liquidation.is_none());
+    }
+}
@@ -3,10 +3,18 @@ use std::sync::Arc;
 use anyhow::{Context, Result};
 use futures::try_join;
 use indexer::{
-    config::LocalConfig, users_indexer::UsersIndexer, users_updater_service::UsersUpdaterService,
+    api::ApiServer,
+    config::LocalConfig,
+    price_watcher::PriceWatcher,
+    reserves_helper::ReserveRegistry,
+    stress_test::StressTest,
+    user_events::UserEvents,
+    users_indexer::UsersIndexer,
+    users_updater_service::{UpdateScheduler, UsersUpdaterService},
     utils,
 };
 use indexer_database::IndexerDatabase;
+use sea_orm::DatabaseConnection;
 use tokio::task::JoinHandle;
 use tracing::{error, info};
 
@@ -14,42 +22,101 @@ use tracing::{error, info};
 ///
 /// This function performs the following steps:
 /// 1. Initializes the pre-run environment
-/// 2. Starts the users indexer service
-/// 3. Starts the users updater service
-/// 4. Handles if any of the services panics
+///    (runs the stress test instead of the services when the `stress` command is given)
+/// 2. Loads the reserve registry and starts refreshing it
+/// 3. Starts the users indexer service
+/// 4. Starts the users updater service
+/// 5. Starts the price watcher
+/// 6. Starts the HTTP API
+/// 7. Handles if any of the services panics
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> Result<()> {
     init_pre_run().await?;
 
     info!("Starting the Liquidation Bot Indexer");
 
-    let local_config = Arc::new(LocalConfig::load_from_env()?);
+    let mut local_config = LocalConfig::load_from_env()?;
 
     let database_connection = Arc::new(IndexerDatabase::get_postgres_connection().await?);
 
-    let users_indexer: JoinHandle<Result<()>> =
-        UsersIndexer::start_users_indexer(&database_connection, &local_config).await?;
+    // The stress test only reads the database, it must not need a reachable RPC
+    let args = std::env::args().collect::<Vec<String>>();
+    if args.len() > 1 && args[1] == "stress" {
+        return run_stress_test(&database_connection, &local_config, &args[2..]).await;
+    }
+
+    local_config.resolve_pool_configurator().await?;
+    let local_config = Arc::new(local_config);
+
+    let (reserve_registry, reserve_registry_service) =
+        ReserveRegistry::start_reserve_registry(&database_connection, &local_config).await?;
+
+    let update_scheduler = Arc::new(UpdateScheduler::new());
+    let user_events = Arc::new(UserEvents::new());
+
+    let users_indexer: JoinHandle<Result<()>> = UsersIndexer::start_users_indexer(
+        &database_connection,
+        &local_config,
+        &reserve_registry,
+        &update_scheduler,
+        &user_events,
+    )
+    .await?;
+
+    let users_updater_service = UsersUpdaterService::start_users_updater_service(
+        &database_connection,
+        &local_config,
+        &reserve_registry,
+        &update_scheduler,
+        &user_events,
+    )
+    .await?;
 
-    let users_updater_service =
-        UsersUpdaterService::start_users_updater_service(&database_connection, &local_config)
-            .await?;
+    let price_watcher = PriceWatcher::start_price_watcher(
+        &database_connection,
+        &local_config,
+        &reserve_registry,
+        &user_events,
+    )
+    .await?;
+
+    let api_server =
+        ApiServer::start_api_server(&database_connection, &local_config, &user_events).await?;
 
     tokio::select! {
         result = async {
-            match try_join!(users_indexer, users_updater_service) {
-                Ok((users_indexer_result, users_updater_service_result)) => {
+            match try_join!(users_indexer, users_updater_service, reserve_registry_service, price_watcher, api_server) {
+                Ok((users_indexer_result, users_updater_service_result, reserve_registry_result, price_watcher_result, api_server_result)) => {
                     if let Err(e) = users_indexer_result {
-                        let error_message = e.chain().into_iter().map(|e| e.to_string()).collect::<Vec<String>>().join(" -> ");
+                        let error_message = e.chain().map(|e| e.to_string()).collect::<Vec<String>>().join(" -> ");
                         error!("Users indexer failed with error: {}", error_message);
                         return Err(anyhow::anyhow!("Users indexer failed: {}", error_message));
                     }
 
                     if let Err(e) = users_updater_service_result {
-                        let error_message = e.chain().into_iter().map(|e| e.to_string()).collect::<Vec<String>>().join(" -> ");
+                        let error_message = e.chain().map(|e| e.to_string()).collect::<Vec<String>>().join(" -> ");
                         error!("Users updater service failed with error: {}", error_message);
                         return Err(anyhow::anyhow!("Users updater service failed: {}", error_message));
                     }
 
+                    if let Err(e) = reserve_registry_result {
+                        let error_message = e.chain().map(|e| e.to_string()).collect::<Vec<String>>().join(" -> ");
+                        error!("Reserve registry failed with error: {}", error_message);
+                        return Err(anyhow::anyhow!("Reserve registry failed: {}", error_message));
+                    }
+
+                    if let Err(e) = price_watcher_result {
+                        let error_message = e.chain().map(|e| e.to_string()).collect::<Vec<String>>().join(" -> ");
+                        error!("Price watcher failed with error: {}", error_message);
+                        return Err(anyhow::anyhow!("Price watcher failed: {}", error_message));
+                    }
+
+                    if let Err(e) = api_server_result {
+                        let error_message = e.chain().map(|e| e.to_string()).collect::<Vec<String>>().join(" -> ");
+                        error!("API server failed with error: {}", error_message);
+                        return Err(anyhow::anyhow!("API server failed: {}", error_message));
+                    }
+
                     info!("All indexers stopped");
                     Ok(())
                 }
@@ -99,3 +166,50 @@ async fn init_pre_run() -> Result<()> {
 
     Ok(())
 }
+
+/// Runs the price shock stress test and prints or exports the accounts that would
+/// become liquidatable
+///
+/// Usage: `indexer stress WETH=-15%,WBTC=-10% [--output report.csv]`
+///
+/// # Arguments
+/// * `db` - Database connection handle
+/// * `local_config` - Local configuration settings
+/// * `args` - Arguments following the `stress` command
+///
+/// # Returns
+/// * `Result<()>` - Success or error if the arguments are invalid or the test fails
+async fn run_stress_test(
+    db: &DatabaseConnection,
+    local_config: &LocalConfig,
+    args: &[String],
+) -> Result<()> {
+    let shocks = args.first().context(
+        "Missing price shocks, usage: indexer stress WETH=-15%,WBTC=-10% [--output report.csv]",
+    )?;
+    let shocks = StressTest::parse_shocks(shocks)?;
+
+    let output = match args.get(1).map(String::as_str) {
+        Some("--output") => Some(args.get(2).context("Missing path after --output")?),
+        Some(arg) => return Err(anyhow::anyhow!("Unknown argument {}", arg)),
+        None => None,
+    };
+
+    info!("Running stress test with shocks {:?}", shocks);
+    let report = StressTest::run(db, local_config, &shocks).await?;
+
+    match output {
+        Some(path) => {
+            StressTest::export_report(&report, path)?;
+            info!(
+                "Exported {} accounts ({} USD of debt at risk) to {}",
+                report.accounts.len(),
+                report.total_debt_at_risk.round_dp(2),
+                path
+            );
+        }
+        None => StressTest::print_report(&report),
+    }
+
+    Ok(())
+}
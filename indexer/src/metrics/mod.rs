@@ -0,0 +1,153 @@
+use std::sync::LazyLock;
+
+use anyhow::Result;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+/// Metrics of the whole process, shared by every service
+pub static METRICS: LazyLock<Metrics> =
+    LazyLock::new(|| Metrics::new().expect("failed to register the metrics"));
+
+/// Prometheus gauges, counters and histograms of the indexer, served on `/metrics`
+pub struct Metrics {
+    registry: Registry,
+    /// Last block indexed by the users indexer
+    pub indexed_block: IntGauge,
+    /// Chain head seen by the users indexer
+    pub chain_head: IntGauge,
+    /// Blocks between the chain head and the last indexed block
+    pub block_lag: IntGauge,
+    /// Number of accounts per tier, updated when scraped
+    pub accounts: IntGaugeVec,
+    /// Users written after a refresh from the chain
+    pub users_refreshed: IntCounter,
+    /// Duration of the multicalls
+    pub multicall_duration: Histogram,
+    /// Multicalls that failed as a whole
+    pub multicall_failures: IntCounter,
+    /// Failed RPC requests per method
+    pub rpc_errors: IntCounterVec,
+    /// Duration of the database writes per operation, commit included
+    pub db_write_duration: HistogramVec,
+    /// Time spent refreshing each tier's due users per updater cycle
+    pub updater_cycle_duration: HistogramVec,
+}
+
+impl Metrics {
+    /// Creates and registers the metrics
+    ///
+    /// # Returns
+    /// * `Result<Self>` - The metrics, or an error if a metric is invalid
+    fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some("indexer".to_string()), None)?;
+
+        let indexed_block =
+            IntGauge::new("indexed_block", "Last block indexed by the users indexer")?;
+        let chain_head = IntGauge::new("chain_head", "Chain head seen by the users indexer")?;
+        let block_lag = IntGauge::new(
+            "block_lag",
+            "Blocks between the chain head and the last indexed block",
+        )?;
+        let accounts = IntGaugeVec::new(
+            Opts::new("accounts", "Number of accounts per tier"),
+            &["tier"],
+        )?;
+        let users_refreshed = IntCounter::new(
+            "users_refreshed_total",
+            "Users written after a refresh from the chain",
+        )?;
+        let multicall_duration = Histogram::with_opts(HistogramOpts::new(
+            "multicall_duration_seconds",
+            "Duration of the multicalls",
+        ))?;
+        let multicall_failures = IntCounter::new(
+            "multicall_failures_total",
+            "Multicalls that failed as a whole",
+        )?;
+        let rpc_errors = IntCounterVec::new(
+            Opts::new("rpc_errors_total", "Failed RPC requests per method"),
+            &["method"],
+        )?;
+        let db_write_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "db_write_duration_seconds",
+                "Duration of the database writes per operation, commit included",
+            ),
+            &["operation"],
+        )?;
+        let updater_cycle_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "updater_cycle_duration_seconds",
+                "Time spent refreshing each tier's due users per updater cycle",
+            )
+            .buckets(vec![0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0]),
+            &["tier"],
+        )?;
+
+        registry.register(Box::new(indexed_block.clone()))?;
+        registry.register(Box::new(chain_head.clone()))?;
+        registry.register(Box::new(block_lag.clone()))?;
+        registry.register(Box::new(accounts.clone()))?;
+        registry.register(Box::new(users_refreshed.clone()))?;
+        registry.register(Box::new(multicall_duration.clone()))?;
+        registry.register(Box::new(multicall_failures.clone()))?;
+        registry.register(Box::new(rpc_errors.clone()))?;
+        registry.register(Box::new(db_write_duration.clone()))?;
+        registry.register(Box::new(updater_cycle_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            indexed_block,
+            chain_head,
+            block_lag,
+            accounts,
+            users_refreshed,
+            multicall_duration,
+            multicall_failures,
+            rpc_errors,
+            db_write_duration,
+            updater_cycle_duration,
+        })
+    }
+
+    /// Records the sync state of the users indexer
+    ///
+    /// # Arguments
+    /// * `indexed_block` - Last block indexed
+    /// * `chain_head` - Current chain head
+    pub fn record_sync(&self, indexed_block: u64, chain_head: u64) {
+        self.indexed_block.set(indexed_block as i64);
+        self.chain_head.set(chain_head as i64);
+        self.block_lag
+            .set(chain_head.saturating_sub(indexed_block) as i64);
+    }
+
+    /// Counts the error of an RPC request, if it failed
+    ///
+    /// # Arguments
+    /// * `method` - RPC method of the request
+    /// * `result` - Result of the request
+    ///
+    /// # Returns
+    /// * `Result<T, E>` - The result, unchanged
+    pub fn observe_rpc<T, E>(&self, method: &str, result: Result<T, E>) -> Result<T, E> {
+        if result.is_err() {
+            self.rpc_errors.with_label_values(&[method]).inc();
+        }
+        result
+    }
+
+    /// Renders the metrics in the Prometheus text format
+    ///
+    /// # Returns
+    /// * `Result<String>` - The metrics, or an error if they can't be encoded
+    pub fn render(&self) -> Result<String> {
+        let encoder = TextEncoder::new();
+        let mut buffer = Vec::new();
+        encoder.encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8(buffer)?)
+    }
+}
@@ -0,0 +1,291 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alloy::{network::Ethereum, primitives::Address, providers::Provider};
+use anyhow::{Context, Result};
+use indexer_database::{reserves_table_helper, user_debt_collateral_helper};
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+use tokio::task::JoinHandle;
+use tracing::{error, info, instrument};
+
+use crate::{
+    blockchain_manager::{AaveHelperContract, BlockchainManager},
+    config::LocalConfig,
+    metrics::METRICS,
+    reserves_helper::{AaveReserve, ReserveRegistry},
+    user_events::UserEvents,
+    users_helper::UserHelper,
+    users_updater_service::UsersUpdaterService,
+    utils::{constants::USD_VALUE_DECIMALS, math_helper},
+};
+
+/// Watches the oracle prices of the reserves and re-evaluates the users exposed to
+/// the reserves whose price moved
+///
+/// Health factors mostly change because prices move, so waiting for a user's tier
+/// timer can leave a liquidatable position unnoticed for up to an hour. The watcher
+/// reads the prices once per new block and, when a reserve moved by more than
+/// `PRICE_CHANGE_THRESHOLD` percent since the last price that triggered a refresh,
+/// refreshes every user holding it as collateral or debt, whatever their tier.
+///
+/// Smaller moves don't justify refreshing every exposed user, so the health factor of
+/// those users is projected from their stored positions instead, and only the users
+/// whose projection crosses a tier boundary are refreshed.
+pub struct PriceWatcher;
+
+impl PriceWatcher {
+    #[instrument("PRICE_WATCHER", skip(db, local_config, reserve_registry, user_events))]
+    pub async fn start_price_watcher(
+        db: &DatabaseConnection,
+        local_config: &Arc<LocalConfig>,
+        reserve_registry: &Arc<ReserveRegistry>,
+        user_events: &Arc<UserEvents>,
+    ) -> Result<JoinHandle<Result<()>>> {
+        let db = db.clone();
+        let local_config = local_config.clone();
+        let reserve_registry = reserve_registry.clone();
+        let user_events = user_events.clone();
+
+        let handle = tokio::spawn(async move {
+            info!("Starting price watcher");
+
+            let provider = BlockchainManager::get_provider(&local_config).await?;
+
+            let aave_helper_contracts = Arc::new(
+                BlockchainManager::get_aave_helper_contracts(&provider, &local_config).await?,
+            );
+
+            // Last price of each reserve that triggered a refresh
+            let mut reference_prices: HashMap<Address, Decimal> = HashMap::new();
+            // Last price read of each reserve
+            let mut last_prices: HashMap<Address, Decimal> = HashMap::new();
+            let mut last_block_number = 0;
+
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    local_config.price_poll_interval,
+                ))
+                .await;
+
+                let block_number = match METRICS
+                    .observe_rpc("eth_blockNumber", provider.get_block_number().await)
+                {
+                    Ok(block_number) => block_number,
+                    Err(e) => {
+                        error!("Error fetching block number: {}", e);
+                        continue;
+                    }
+                };
+                if block_number <= last_block_number {
+                    continue;
+                }
+
+                match Self::check_prices(
+                    &db,
+                    &local_config,
+                    &aave_helper_contracts,
+                    &reserve_registry.reserves(),
+                    &provider,
+                    block_number,
+                    &mut reference_prices,
+                    &mut last_prices,
+                    &user_events,
+                )
+                .await
+                {
+                    Ok(_) => last_block_number = block_number,
+                    Err(e) => error!("Error checking prices at block {}: {}", block_number, e),
+                }
+            }
+        });
+        Ok(handle)
+    }
+
+    /// Reads the reserve prices at the given block, caches them in the reserves table and
+    /// refreshes the users exposed to the reserves whose price moved beyond the threshold
+    ///
+    /// The first price seen for a reserve becomes its reference. The reference is only
+    /// moved when a refresh is triggered, so slow drifts are caught once they add up.
+    /// The last and reference prices are only updated once every exposed user is
+    /// refreshed, so a check where a user failed is retried against the same prices.
+    /// The other users exposed to a reserve whose price changed get their health factor
+    /// projected, and are refreshed too if the projection moves them to another tier.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `provider` - Blockchain provider
+    /// * `block_number` - Block number to read the prices at
+    /// * `reference_prices` - Last price of each reserve that triggered a refresh
+    /// * `last_prices` - Last price read of each reserve
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error result of the check
+    #[allow(clippy::too_many_arguments)]
+    async fn check_prices<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        provider: &'a P,
+        block_number: u64,
+        reference_prices: &mut HashMap<Address, Decimal>,
+        last_prices: &mut HashMap<Address, Decimal>,
+        user_events: &UserEvents,
+    ) -> Result<()> {
+        let prices = aave_helper_contracts
+            .oracle_contract
+            .getAssetsPrices(
+                aave_reserves
+                    .iter()
+                    .map(|reserve| reserve.address)
+                    .collect(),
+            )
+            .block(block_number.into())
+            .call()
+            .await;
+        let prices = METRICS
+            .observe_rpc("eth_call", prices)
+            .context("Failed to fetch reserve prices")?
+            ._0;
+
+        let prices = aave_reserves
+            .iter()
+            .zip(prices)
+            .map(|(reserve, price)| {
+                (
+                    reserve,
+                    math_helper::divide_by_precision(price, USD_VALUE_DECIMALS),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let changed_prices = prices
+            .iter()
+            .filter(|(reserve, price)| last_prices.get(&reserve.address) != Some(price))
+            .map(|(reserve, price)| (reserve.address.to_string(), *price))
+            .collect::<Vec<_>>();
+        if changed_prices.is_empty() {
+            return Ok(());
+        }
+        let timer = METRICS
+            .db_write_duration
+            .with_label_values(&["reserve_prices"])
+            .start_timer();
+        reserves_table_helper::update_reserve_prices(db, changed_prices.clone()).await?;
+        timer.observe_duration();
+
+        let mut moved_reserves = Vec::new();
+        // References moved by this check, only applied once the exposed users are refreshed
+        let mut new_reference_prices = Vec::new();
+
+        for (reserve, price) in prices.iter() {
+            let price = *price;
+
+            let Some(reference_price) = reference_prices.get(&reserve.address).copied() else {
+                new_reference_prices.push((reserve.address, price));
+                continue;
+            };
+
+            if !Self::has_moved(reference_price, price, local_config.price_change_threshold) {
+                continue;
+            }
+
+            info!(
+                "Price of {} moved from {} to {} at block {}",
+                reserve.symbol, reference_price, price, block_number
+            );
+            new_reference_prices.push((reserve.address, price));
+            moved_reserves.push(reserve.address.to_string());
+        }
+
+        let mut users =
+            user_debt_collateral_helper::get_users_with_positions_in_reserves(db, &moved_reserves)
+                .await?;
+
+        // Users only exposed to smaller moves are refreshed if their projection changes tier
+        let changed_reserves = changed_prices
+            .iter()
+            .map(|(reserve_address, _)| reserve_address.clone())
+            .filter(|reserve_address| !moved_reserves.contains(reserve_address))
+            .collect::<Vec<_>>();
+        let projected_users = user_debt_collateral_helper::get_users_with_positions_in_reserves(
+            db,
+            &changed_reserves,
+        )
+        .await?
+        .into_iter()
+        .filter(|user_address| users.binary_search(user_address).is_err())
+        .collect::<Vec<_>>();
+        let escalated_users = UserHelper::project_health_factors(
+            db,
+            local_config,
+            aave_reserves,
+            &prices
+                .iter()
+                .map(|(reserve, price)| (reserve.address.to_string(), *price))
+                .collect(),
+            &projected_users,
+        )
+        .await?;
+        users.extend(escalated_users);
+
+        if !users.is_empty() {
+            info!(
+                "Refreshing {} users exposed to {} reserves with moved prices",
+                users.len(),
+                changed_prices.len()
+            );
+
+            let failures = UsersUpdaterService::update_users_concurrently(
+                db,
+                local_config,
+                &users,
+                aave_helper_contracts,
+                aave_reserves,
+                provider,
+                block_number,
+                user_events,
+                true,
+            )
+            .await?;
+            if !failures.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Failed to refresh {} of {} exposed users",
+                    failures.len(),
+                    users.len()
+                ));
+            }
+        }
+
+        // The moves are handled, an error above retries the block against the same prices
+        reference_prices.extend(new_reference_prices);
+        last_prices.extend(
+            prices
+                .iter()
+                .map(|(reserve, price)| (reserve.address, *price)),
+        );
+
+        Ok(())
+    }
+
+    /// Checks whether a price moved by more than the threshold from its reference
+    ///
+    /// # Arguments
+    /// * `reference_price` - Price the move is measured from
+    /// * `price` - Current price
+    /// * `threshold` - Price move in percent
+    ///
+    /// # Returns
+    /// * `bool` - True if the price moved by more than the threshold
+    fn has_moved(reference_price: Decimal, price: Decimal, threshold: Decimal) -> bool {
+        if reference_price.is_zero() {
+            return !price.is_zero();
+        }
+
+        (price - reference_price).abs() / reference_price * Decimal::ONE_HUNDRED > threshold
+    }
+}
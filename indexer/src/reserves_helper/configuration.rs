@@ -0,0 +1,249 @@
+use alloy::{
+    network::Ethereum,
+    primitives::{Address, B256, U256},
+    providers::Provider,
+    sol_types::{SolEvent, SolEventInterface},
+};
+use anyhow::{Context, Result};
+use indexer_database::reserves_table_helper::{self, ReserveDetails};
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+use tracing::info;
+
+use super::{AaveReserve, ReserveConfiguration, ReservesHelper};
+use crate::{
+    blockchain_manager::AaveHelperContract,
+    utils::{
+        constants::DEBT_CEILING_DECIMALS,
+        contracts::{
+            AavePoolConfiguratorContract::{
+                self, AavePoolConfiguratorContractEvents as ConfiguratorEvent,
+            },
+            AavePoolContract::{self, AavePoolContractEvents as PoolEvent},
+        },
+        math_helper,
+    },
+};
+
+/// Returns the topic0 of every event that changes the isolation mode, siloed borrowing
+/// or caps of a reserve
+///
+/// # Returns
+/// * `Vec<B256>` - Event signature hashes used to filter the pool and pool configurator logs
+pub fn configuration_event_signatures() -> Vec<B256> {
+    vec![
+        AavePoolContract::IsolationModeTotalDebtUpdated::SIGNATURE_HASH,
+        AavePoolConfiguratorContract::DebtCeilingChanged::SIGNATURE_HASH,
+        AavePoolConfiguratorContract::SiloedBorrowingChanged::SIGNATURE_HASH,
+        AavePoolConfiguratorContract::BorrowCapChanged::SIGNATURE_HASH,
+        AavePoolConfiguratorContract::SupplyCapChanged::SIGNATURE_HASH,
+    ]
+}
+
+impl ReservesHelper {
+    /// Loads the isolation mode, siloed borrowing and caps of a reserve
+    ///
+    /// # Arguments
+    /// * `aave_helper_contracts` - Aave protocol contract helpers
+    /// * `address` - Address of the reserve
+    ///
+    /// # Returns
+    /// * `Result<ReserveConfiguration>` - The reserve's configuration
+    pub(super) async fn load_reserve_configuration<'a, P: Provider<Ethereum>>(
+        aave_helper_contracts: &AaveHelperContract<'a, P>,
+        address: Address,
+    ) -> Result<ReserveConfiguration> {
+        let pool_data_provider = &aave_helper_contracts.pool_data_provider_contract;
+
+        let debt_ceiling = pool_data_provider
+            .getDebtCeiling(address)
+            .call()
+            .await
+            .context(format!(
+                "Failed to fetch debt ceiling of reserve {}",
+                address
+            ))?
+            ._0;
+        let siloed_borrowing = pool_data_provider
+            .getSiloedBorrowing(address)
+            .call()
+            .await
+            .context(format!(
+                "Failed to fetch siloed borrowing of reserve {}",
+                address
+            ))?
+            ._0;
+        let caps = pool_data_provider
+            .getReserveCaps(address)
+            .call()
+            .await
+            .context(format!("Failed to fetch caps of reserve {}", address))?;
+        let isolation_mode_total_debt = aave_helper_contracts
+            .pool_contract
+            .getReserveData(address)
+            .call()
+            .await
+            .context(format!("Failed to fetch data of reserve {}", address))?
+            ._0
+            .isolationModeTotalDebt;
+
+        Ok(ReserveConfiguration {
+            debt_ceiling: math_helper::divide_by_precision(debt_ceiling, DEBT_CEILING_DECIMALS),
+            isolation_mode_total_debt: math_helper::divide_by_precision(
+                U256::from(isolation_mode_total_debt),
+                DEBT_CEILING_DECIMALS,
+            ),
+            siloed_borrowing,
+            // Caps are whole tokens
+            borrow_cap: math_helper::divide_by_precision(caps.borrowCap, 0),
+            supply_cap: math_helper::divide_by_precision(caps.supplyCap, 0),
+        })
+    }
+
+    /// Applies the reserve configuration events found in the given logs to the reserves
+    /// and stores the changes
+    ///
+    /// `IsolationModeTotalDebtUpdated` carries the new total debt, which is applied as is.
+    /// The pool configurator events trigger a reload of the reserve's configuration.
+    /// Events of reserves that aren't in the list are ignored.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `aave_helper_contracts` - Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `logs` - Pool and pool configurator logs, filtered with `configuration_event_signatures`
+    ///
+    /// # Returns
+    /// * `Result<Vec<AaveReserve>>` - The reserves whose configuration changed, updated
+    pub async fn apply_configuration_logs<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        aave_helper_contracts: &AaveHelperContract<'a, P>,
+        aave_reserves: &[AaveReserve],
+        logs: &[alloy::rpc::types::Log],
+    ) -> Result<Vec<AaveReserve>> {
+        let mut isolation_mode_total_debts: Vec<(Address, Decimal)> = Vec::new();
+        let mut reserves_to_reload: Vec<Address> = Vec::new();
+
+        for log in logs {
+            if log.inner.address == *aave_helper_contracts.pool_contract.address() {
+                let event = PoolEvent::decode_log(&log.inner, false)?;
+                if let PoolEvent::IsolationModeTotalDebtUpdated(event) = event.data {
+                    let total_debt =
+                        math_helper::divide_by_precision(event.totalDebt, DEBT_CEILING_DECIMALS);
+                    // Only the last total debt of each reserve matters
+                    match isolation_mode_total_debts
+                        .iter_mut()
+                        .find(|(asset, _)| *asset == event.asset)
+                    {
+                        Some((_, value)) => *value = total_debt,
+                        None => isolation_mode_total_debts.push((event.asset, total_debt)),
+                    }
+                }
+                continue;
+            }
+
+            let event = ConfiguratorEvent::decode_log(&log.inner, false)?;
+            let asset = match event.data {
+                ConfiguratorEvent::DebtCeilingChanged(event) => event.asset,
+                ConfiguratorEvent::SiloedBorrowingChanged(event) => event.asset,
+                ConfiguratorEvent::BorrowCapChanged(event) => event.asset,
+                ConfiguratorEvent::SupplyCapChanged(event) => event.asset,
+            };
+            if !reserves_to_reload.contains(&asset) {
+                reserves_to_reload.push(asset);
+            }
+        }
+
+        let mut changed_reserves: Vec<AaveReserve> = Vec::new();
+
+        for (asset, total_debt) in isolation_mode_total_debts {
+            let Some(reserve) = aave_reserves
+                .iter()
+                .find(|reserve| reserve.address == asset)
+            else {
+                continue;
+            };
+            let mut reserve = reserve.clone();
+            reserve.configuration.isolation_mode_total_debt = total_debt;
+            changed_reserves.push(reserve);
+        }
+
+        for asset in reserves_to_reload {
+            let Some(reserve) = aave_reserves
+                .iter()
+                .find(|reserve| reserve.address == asset)
+            else {
+                continue;
+            };
+            // The reload also picks up the latest isolation mode total debt
+            let configuration =
+                Self::load_reserve_configuration(aave_helper_contracts, asset).await?;
+
+            info!(
+                "Reserve {} configuration changed: {:?}",
+                asset, configuration
+            );
+            match changed_reserves
+                .iter_mut()
+                .find(|reserve| reserve.address == asset)
+            {
+                Some(changed_reserve) => changed_reserve.configuration = configuration,
+                None => {
+                    let mut reserve = reserve.clone();
+                    reserve.configuration = configuration;
+                    changed_reserves.push(reserve);
+                }
+            }
+        }
+
+        reserves_table_helper::add_or_update_reserves(
+            db,
+            changed_reserves.iter().map(Self::reserve_details).collect(),
+        )
+        .await?;
+
+        Ok(changed_reserves)
+    }
+
+    /// Builds the row stored in the reserves table for a reserve
+    ///
+    /// # Arguments
+    /// * `reserve` - The reserve to store
+    ///
+    /// # Returns
+    /// * `ReserveDetails` - The reserve's stored configuration
+    pub(super) fn reserve_details(reserve: &AaveReserve) -> ReserveDetails {
+        ReserveDetails {
+            reserve_address: reserve.address.to_string(),
+            symbol: reserve.symbol.clone(),
+            decimals: reserve.decimals,
+            a_token_address: reserve.a_token_address.to_string(),
+            stable_debt_token_address: reserve.stable_debt_token_address.to_string(),
+            variable_debt_token_address: reserve.variable_debt_token_address.to_string(),
+            is_active: reserve.is_active,
+            is_frozen: reserve.is_frozen,
+            is_paused: reserve.is_paused,
+            borrowing_enabled: reserve.borrowing_enabled,
+            usage_as_collateral_enabled: reserve.usage_as_collateral_enabled,
+            ltv: reserve.ltv,
+            liquidation_threshold: reserve.liquidation_threshold,
+            liquidation_bonus: reserve.liquidation_bonus,
+            liquidation_protocol_fee: reserve.liquidation_protocol_fee,
+            emode_category: reserve
+                .emode_category
+                .as_ref()
+                .map(|category| category.id)
+                .unwrap_or_default(),
+            emode_liquidation_threshold: reserve
+                .emode_category
+                .as_ref()
+                .map(|category| category.liquidation_threshold)
+                .unwrap_or(reserve.liquidation_threshold),
+            debt_ceiling: reserve.configuration.debt_ceiling,
+            isolation_mode_total_debt: reserve.configuration.isolation_mode_total_debt,
+            siloed_borrowing: reserve.configuration.siloed_borrowing,
+            borrow_cap: reserve.configuration.borrow_cap,
+            supply_cap: reserve.configuration.supply_cap,
+        }
+    }
+}
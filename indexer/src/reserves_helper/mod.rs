@@ -0,0 +1,226 @@
+mod configuration;
+mod models;
+mod registry;
+
+pub use configuration::configuration_event_signatures;
+pub use models::{AaveReserve, EModeCategory, ReserveConfiguration};
+pub use registry::{ReserveChange, ReserveRegistry};
+
+use alloy::{network::Ethereum, primitives::U256, providers::Provider};
+use anyhow::{Context, Result};
+use futures::future::try_join_all;
+use indexer_database::reserves_table_helper;
+use sea_orm::DatabaseConnection;
+use tracing::info;
+
+use crate::{
+    blockchain_manager::AaveHelperContract,
+    utils::{constants::PERCENTAGE_DECIMALS, math_helper},
+};
+
+pub struct ReservesHelper;
+
+impl ReservesHelper {
+    /// Loads the Aave reserve list together with each reserve's token decimals,
+    /// token addresses, state, liquidation parameters, e-mode category and borrowing
+    /// constraints
+    ///
+    /// The symbols are read from `getAllReservesTokens`, the decimals, state, LTV,
+    /// liquidation threshold and bonus from `getReserveConfigurationData`, the token
+    /// addresses from `getReserveTokensAddresses`, the pause state from `getPaused`, the
+    /// protocol fee from `getLiquidationProtocolFee` and the e-mode category from
+    /// `getReserveEModeCategory` and `getEModeCategoryData`. The debt ceiling, siloed
+    /// borrowing and caps are read with `load_reserve_configuration`. Everything is
+    /// persisted in the reserves table, so balances can be normalized without further calls.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `aave_helper_contracts` - Aave protocol contract helpers
+    ///
+    /// # Returns
+    /// * `Result<Vec<AaveReserve>>` - The reserves, in the order of the pool's reserve list
+    pub async fn load_reserves<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        aave_helper_contracts: &AaveHelperContract<'a, P>,
+    ) -> Result<Vec<AaveReserve>> {
+        let reserve_addresses = aave_helper_contracts
+            .pool_contract
+            .getReservesList()
+            .call()
+            .await?
+            ._0;
+
+        let reserve_tokens = aave_helper_contracts
+            .pool_data_provider_contract
+            .getAllReservesTokens()
+            .call()
+            .await
+            .context("Failed to fetch reserve symbols")?
+            ._0;
+        let reserve_tokens = &reserve_tokens;
+
+        let reserves = try_join_all(reserve_addresses.into_iter().map(|address| async move {
+            let pool_data_provider = &aave_helper_contracts.pool_data_provider_contract;
+            let symbol = reserve_tokens
+                .iter()
+                .find(|token| token.tokenAddress == address)
+                .map(|token| token.symbol.clone())
+                .unwrap_or_default();
+            let configuration = pool_data_provider
+                .getReserveConfigurationData(address)
+                .call()
+                .await
+                .context(format!(
+                    "Failed to fetch configuration of reserve {}",
+                    address
+                ))?;
+            let token_addresses = pool_data_provider
+                .getReserveTokensAddresses(address)
+                .call()
+                .await
+                .context(format!(
+                    "Failed to fetch token addresses of reserve {}",
+                    address
+                ))?;
+            let is_paused = pool_data_provider
+                .getPaused(address)
+                .call()
+                .await
+                .context(format!(
+                    "Failed to fetch pause state of reserve {}",
+                    address
+                ))?
+                .isPaused;
+            let liquidation_protocol_fee = pool_data_provider
+                .getLiquidationProtocolFee(address)
+                .call()
+                .await
+                .context(format!(
+                    "Failed to fetch liquidation protocol fee of reserve {}",
+                    address
+                ))?
+                ._0;
+            let emode_category_id = pool_data_provider
+                .getReserveEModeCategory(address)
+                .call()
+                .await
+                .context(format!(
+                    "Failed to fetch e-mode category of reserve {}",
+                    address
+                ))?
+                ._0;
+            let emode_category_id = u8::try_from(emode_category_id)
+                .context(format!("Invalid e-mode category for reserve {}", address))?;
+            let reserve_configuration =
+                Self::load_reserve_configuration(aave_helper_contracts, address).await?;
+
+            let reserve = AaveReserve {
+                address,
+                symbol,
+                decimals: u8::try_from(configuration.decimals)
+                    .context(format!("Invalid decimals for reserve {}", address))?,
+                a_token_address: token_addresses.aTokenAddress,
+                stable_debt_token_address: token_addresses.stableDebtTokenAddress,
+                variable_debt_token_address: token_addresses.variableDebtTokenAddress,
+                is_active: configuration.isActive,
+                is_frozen: configuration.isFrozen,
+                is_paused,
+                borrowing_enabled: configuration.borrowingEnabled,
+                usage_as_collateral_enabled: configuration.usageAsCollateralEnabled,
+                ltv: math_helper::divide_by_precision(configuration.ltv, PERCENTAGE_DECIMALS),
+                liquidation_threshold: math_helper::divide_by_precision(
+                    configuration.liquidationThreshold,
+                    PERCENTAGE_DECIMALS,
+                ),
+                liquidation_bonus: math_helper::divide_by_precision(
+                    configuration.liquidationBonus,
+                    PERCENTAGE_DECIMALS,
+                ),
+                liquidation_protocol_fee: math_helper::divide_by_precision(
+                    liquidation_protocol_fee,
+                    PERCENTAGE_DECIMALS,
+                ),
+                emode_category: None,
+                configuration: reserve_configuration,
+            };
+
+            Ok::<_, anyhow::Error>((reserve, emode_category_id))
+        }))
+        .await?;
+
+        // Category 0 means the reserve isn't part of any e-mode category
+        let mut emode_category_ids = reserves
+            .iter()
+            .map(|(_, emode_category_id)| *emode_category_id)
+            .filter(|emode_category_id| *emode_category_id != 0)
+            .collect::<Vec<_>>();
+        emode_category_ids.sort_unstable();
+        emode_category_ids.dedup();
+
+        let emode_categories = try_join_all(
+            emode_category_ids
+                .into_iter()
+                .map(|id| Self::load_emode_category(aave_helper_contracts, id)),
+        )
+        .await?;
+
+        let reserves = reserves
+            .into_iter()
+            .map(|(mut reserve, emode_category_id)| {
+                reserve.emode_category = emode_categories
+                    .iter()
+                    .find(|category| category.id == emode_category_id)
+                    .cloned();
+                reserve
+            })
+            .collect::<Vec<_>>();
+
+        reserves_table_helper::add_or_update_reserves(
+            db,
+            reserves.iter().map(Self::reserve_details).collect(),
+        )
+        .await?;
+
+        info!(
+            "Loaded {} reserves and {} e-mode categories",
+            reserves.len(),
+            emode_categories.len()
+        );
+
+        Ok(reserves)
+    }
+
+    /// Loads the liquidation parameters of an e-mode category
+    ///
+    /// # Arguments
+    /// * `aave_helper_contracts` - Aave protocol contract helpers
+    /// * `id` - Id of the e-mode category
+    ///
+    /// # Returns
+    /// * `Result<EModeCategory>` - The e-mode category
+    async fn load_emode_category<'a, P: Provider<Ethereum>>(
+        aave_helper_contracts: &AaveHelperContract<'a, P>,
+        id: u8,
+    ) -> Result<EModeCategory> {
+        let category = aave_helper_contracts
+            .pool_contract
+            .getEModeCategoryData(id)
+            .call()
+            .await
+            .context(format!("Failed to fetch e-mode category {}", id))?
+            ._0;
+
+        Ok(EModeCategory {
+            id,
+            label: category.label,
+            liquidation_threshold: math_helper::divide_by_precision(
+                U256::from(category.liquidationThreshold),
+                PERCENTAGE_DECIMALS,
+            ),
+            liquidation_bonus: math_helper::divide_by_precision(
+                U256::from(category.liquidationBonus),
+                PERCENTAGE_DECIMALS,
+            ),
+        })
+    }
+}
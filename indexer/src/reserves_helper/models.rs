@@ -0,0 +1,86 @@
+use alloy::primitives::Address;
+use rust_decimal::Decimal;
+
+/// An Aave reserve with the configuration needed to normalize its balances and
+/// evaluate liquidations
+#[derive(Debug, Clone)]
+pub struct AaveReserve {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    pub a_token_address: Address,
+    pub stable_debt_token_address: Address,
+    pub variable_debt_token_address: Address,
+    pub is_active: bool,
+    pub is_frozen: bool,
+    pub is_paused: bool,
+    pub borrowing_enabled: bool,
+    pub usage_as_collateral_enabled: bool,
+    /// Share of the collateral value that can be borrowed against
+    pub ltv: Decimal,
+    /// Share of the collateral value counted towards the health factor, zero if the
+    /// reserve can't be used as collateral
+    pub liquidation_threshold: Decimal,
+    /// Collateral received per unit of debt repaid by a liquidator, e.g. 1.05
+    pub liquidation_bonus: Decimal,
+    /// Share of the liquidation bonus kept by the protocol
+    pub liquidation_protocol_fee: Decimal,
+    /// E-mode category the reserve belongs to, if any
+    pub emode_category: Option<EModeCategory>,
+    /// Isolation mode, siloed borrowing and caps of the reserve
+    pub configuration: ReserveConfiguration,
+}
+
+impl AaveReserve {
+    /// Returns the liquidation threshold and bonus of the reserve for a user, the
+    /// e-mode ones if the user is in the reserve's e-mode category
+    ///
+    /// # Arguments
+    /// * `user_emode_category` - E-mode category of the user, 0 when not in e-mode
+    ///
+    /// # Returns
+    /// * `(Decimal, Decimal)` - Tuple containing (liquidation_threshold, liquidation_bonus)
+    pub fn liquidation_parameters(&self, user_emode_category: u8) -> (Decimal, Decimal) {
+        match &self.emode_category {
+            Some(category) if user_emode_category != 0 && category.id == user_emode_category => {
+                (category.liquidation_threshold, category.liquidation_bonus)
+            }
+            _ => (self.liquidation_threshold, self.liquidation_bonus),
+        }
+    }
+}
+
+/// An Aave e-mode category, whose parameters replace the reserve defaults for users
+/// that opted into it
+#[derive(Debug, Clone)]
+pub struct EModeCategory {
+    pub id: u8,
+    pub label: String,
+    pub liquidation_threshold: Decimal,
+    pub liquidation_bonus: Decimal,
+}
+
+/// Borrowing constraints of an Aave reserve, kept up to date from the configuration
+/// events of the pool and the pool configurator
+#[derive(Debug, Clone, Default)]
+pub struct ReserveConfiguration {
+    /// Maximum debt in USD that can be borrowed against the reserve in isolation mode,
+    /// zero if the reserve isn't isolated
+    pub debt_ceiling: Decimal,
+    /// Debt in USD currently borrowed against the reserve in isolation mode
+    pub isolation_mode_total_debt: Decimal,
+    /// Whether borrowing the reserve prevents borrowing any other reserve
+    pub siloed_borrowing: bool,
+    /// Maximum amount of tokens that can be borrowed, zero if uncapped
+    pub borrow_cap: Decimal,
+    /// Maximum amount of tokens that can be supplied, zero if uncapped
+    pub supply_cap: Decimal,
+}
+
+impl ReserveConfiguration {
+    /// Returns whether the reserve is an isolated asset, users whose only collateral
+    /// it is are in isolation mode
+    pub fn is_isolated(&self) -> bool {
+        !self.debt_ceiling.is_zero()
+    }
+}
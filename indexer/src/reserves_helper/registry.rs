@@ -0,0 +1,227 @@
+use std::sync::{Arc, RwLock};
+
+use alloy::{network::Ethereum, primitives::Address, providers::Provider};
+use anyhow::Result;
+use indexer_database::reserves_table_helper;
+use sea_orm::DatabaseConnection;
+use tokio::{sync::broadcast, task::JoinHandle};
+use tracing::{error, info, instrument};
+
+use super::{AaveReserve, ReservesHelper};
+use crate::{
+    blockchain_manager::{AaveHelperContract, BlockchainManager},
+    config::LocalConfig,
+};
+
+/// Number of reserve changes kept for slow subscribers, older changes are dropped
+const RESERVE_CHANGES_CAPACITY: usize = 64;
+
+/// A change of the pool's reserve list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReserveChange {
+    /// The reserve was listed on the pool
+    Added(Address),
+    /// The reserve was dropped from the pool
+    Dropped(Address),
+}
+
+/// Aave reserves shared by the users indexer and the users updater service
+///
+/// The reserve list is reloaded every `RESERVES_REFRESH_FREQUENCY` seconds, so reserves
+/// listed or dropped while running are picked up without a restart. Readers take a
+/// snapshot with `reserves`, which stays consistent for as long as they hold it.
+pub struct ReserveRegistry {
+    reserves: RwLock<Arc<Vec<AaveReserve>>>,
+    changes: broadcast::Sender<ReserveChange>,
+}
+
+impl ReserveRegistry {
+    /// Loads the reserves and starts refreshing them periodically
+    ///
+    /// # Arguments
+    /// * `db` - Arc wrapped database connection
+    /// * `local_config` - Arc wrapped local configuration
+    ///
+    /// # Returns
+    /// * `Result<(Arc<ReserveRegistry>, JoinHandle<Result<()>>)>` - The loaded registry and
+    ///   a handle to the spawned refresh task
+    #[instrument("RESERVE_REGISTRY", skip(db, local_config))]
+    pub async fn start_reserve_registry(
+        db: &Arc<DatabaseConnection>,
+        local_config: &Arc<LocalConfig>,
+    ) -> Result<(Arc<Self>, JoinHandle<Result<()>>)> {
+        let reserves = {
+            let provider = BlockchainManager::get_provider(local_config).await?;
+            let aave_helper_contracts =
+                BlockchainManager::get_aave_helper_contracts(&provider, local_config).await?;
+            ReservesHelper::load_reserves(db, &aave_helper_contracts).await?
+        };
+        Self::delete_dropped_reserves(db, &reserves).await?;
+
+        let (changes, _) = broadcast::channel(RESERVE_CHANGES_CAPACITY);
+        let registry = Arc::new(Self {
+            reserves: RwLock::new(Arc::new(reserves)),
+            changes,
+        });
+
+        let db = db.clone();
+        let local_config = local_config.clone();
+        let reserve_registry = registry.clone();
+
+        let handle = tokio::spawn(async move {
+            info!("Starting reserve registry");
+
+            let provider = BlockchainManager::get_provider(&local_config).await?;
+            let aave_helper_contracts =
+                BlockchainManager::get_aave_helper_contracts(&provider, &local_config).await?;
+
+            loop {
+                tokio::time::sleep(std::time::Duration::from_secs(
+                    local_config.reserves_refresh_frequency,
+                ))
+                .await;
+
+                if let Err(e) = reserve_registry.refresh(&db, &aave_helper_contracts).await {
+                    error!("Error refreshing reserves: {}", e);
+                }
+            }
+        });
+
+        Ok((registry, handle))
+    }
+
+    /// Returns a snapshot of the current reserves, in the order of the pool's reserve list
+    ///
+    /// # Returns
+    /// * `Arc<Vec<AaveReserve>>` - The reserves
+    pub fn reserves(&self) -> Arc<Vec<AaveReserve>> {
+        self.reserves
+            .read()
+            .expect("reserve registry poisoned")
+            .clone()
+    }
+
+    /// Subscribes to the reserves added to or dropped from the pool, the users updater
+    /// service refreshes the users of the dropped reserves
+    ///
+    /// # Returns
+    /// * `broadcast::Receiver<ReserveChange>` - Receiver of the changes found by later refreshes
+    pub fn subscribe(&self) -> broadcast::Receiver<ReserveChange> {
+        self.changes.subscribe()
+    }
+
+    /// Applies the reserve configuration events found in the given logs
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `aave_helper_contracts` - Aave protocol contract helpers
+    /// * `logs` - Pool and pool configurator logs, filtered with `configuration_event_signatures`
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error result of the update
+    pub async fn apply_configuration_logs<'a, P: Provider<Ethereum>>(
+        &self,
+        db: &DatabaseConnection,
+        aave_helper_contracts: &AaveHelperContract<'a, P>,
+        logs: &[alloy::rpc::types::Log],
+    ) -> Result<()> {
+        if logs.is_empty() {
+            return Ok(());
+        }
+
+        let changed_reserves = ReservesHelper::apply_configuration_logs(
+            db,
+            aave_helper_contracts,
+            &self.reserves(),
+            logs,
+        )
+        .await?;
+
+        let mut reserves = self.reserves.write().expect("reserve registry poisoned");
+        let mut updated_reserves = reserves.as_ref().clone();
+        for changed_reserve in changed_reserves {
+            // The reserve may have been dropped by a refresh in the meantime
+            if let Some(reserve) = updated_reserves
+                .iter_mut()
+                .find(|reserve| reserve.address == changed_reserve.address)
+            {
+                *reserve = changed_reserve;
+            }
+        }
+        *reserves = Arc::new(updated_reserves);
+
+        Ok(())
+    }
+
+    /// Reloads the reserves and publishes the ones added or dropped since the last load
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `aave_helper_contracts` - Aave protocol contract helpers
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error result of the refresh
+    async fn refresh<'a, P: Provider<Ethereum>>(
+        &self,
+        db: &DatabaseConnection,
+        aave_helper_contracts: &AaveHelperContract<'a, P>,
+    ) -> Result<()> {
+        let reserves = ReservesHelper::load_reserves(db, aave_helper_contracts).await?;
+        Self::delete_dropped_reserves(db, &reserves).await?;
+
+        let previous_reserves = self.reserves();
+        let mut changes = Vec::new();
+        for reserve in reserves.iter() {
+            if !previous_reserves
+                .iter()
+                .any(|previous_reserve| previous_reserve.address == reserve.address)
+            {
+                changes.push(ReserveChange::Added(reserve.address));
+            }
+        }
+        for previous_reserve in previous_reserves.iter() {
+            if !reserves
+                .iter()
+                .any(|reserve| reserve.address == previous_reserve.address)
+            {
+                changes.push(ReserveChange::Dropped(previous_reserve.address));
+            }
+        }
+
+        *self.reserves.write().expect("reserve registry poisoned") = Arc::new(reserves);
+
+        for change in changes {
+            info!("Reserve list changed: {:?}", change);
+            // Nobody may be listening, the change is still applied to the registry
+            let _ = self.changes.send(change);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the stored reserves that are not part of the pool's reserve list anymore
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `reserves` - The pool's current reserves
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error result of the database operation
+    async fn delete_dropped_reserves(
+        db: &DatabaseConnection,
+        reserves: &[AaveReserve],
+    ) -> Result<()> {
+        let dropped_reserves = reserves_table_helper::get_reserves(db)
+            .await?
+            .into_iter()
+            .map(|stored_reserve| stored_reserve.reserve_address)
+            .filter(|reserve_address| {
+                !reserves
+                    .iter()
+                    .any(|reserve| reserve.address.to_string() == *reserve_address)
+            })
+            .collect::<Vec<_>>();
+
+        reserves_table_helper::delete_reserves(db, dropped_reserves).await
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use indexer_database::{
+    entities::{reserves, user_debt_collateral},
+    reserves_table_helper, user_debt_collateral_helper,
+    users_tables_helper::{self, UserCurrentLocation},
+};
+use rust_decimal::Decimal;
+use sea_orm::DatabaseConnection;
+use tracing::info;
+
+use crate::{
+    config::LocalConfig, users_helper::UserHelper, utils::constants::LIQUIDATION_THRESHOLD,
+};
+
+/// Number of accounts evaluated at once, bounds the positions held in memory
+const ACCOUNTS_PER_CHUNK: usize = 1000;
+
+/// A price shock applied to a reserve, e.g. `WETH=-15%`
+#[derive(Debug, Clone)]
+pub struct PriceShock {
+    /// Symbol of the reserve, matched case-insensitively
+    pub symbol: String,
+    /// Price change in percent, -15 for a 15% drop
+    pub change: Decimal,
+}
+
+/// An account that becomes liquidatable under the price shocks
+#[derive(Debug, Clone)]
+pub struct StressedAccount {
+    pub user_address: String,
+    pub current_location: UserCurrentLocation,
+    pub health_factor: Decimal,
+    pub stressed_health_factor: Decimal,
+    /// Debt of the account in USD at the shocked prices
+    pub stressed_debt_in_usd: Decimal,
+}
+
+/// Result of a stress test
+#[derive(Debug, Clone, Default)]
+pub struct StressReport {
+    /// Accounts crossing below a health factor of 1.0, the lowest health factor first
+    pub accounts: Vec<StressedAccount>,
+    /// Sum of the debt of those accounts in USD at the shocked prices
+    pub total_debt_at_risk: Decimal,
+    /// Number of accounts whose health factor couldn't be recomputed
+    pub skipped_accounts: usize,
+}
+
+/// Recomputes the health factor of every account under hypothetical price shocks
+///
+/// Everything is read from the database: the stored positions, the reserve liquidation
+/// thresholds and the prices cached by the price watcher. The chain isn't queried and
+/// nothing is written. The health factors are projected the same way as the price
+/// watcher does, with the e-mode liquidation threshold for the reserves in an account's
+/// e-mode category.
+pub struct StressTest;
+
+impl StressTest {
+    /// Parses a comma separated list of price shocks, e.g. `WETH=-15%,WBTC=-10%`
+    ///
+    /// # Arguments
+    /// * `shocks` - The price shocks, the percent sign is optional
+    ///
+    /// # Returns
+    /// * `Result<Vec<PriceShock>>` - The parsed shocks, or an error for a malformed shock
+    pub fn parse_shocks(shocks: &str) -> Result<Vec<PriceShock>> {
+        shocks
+            .split(',')
+            .map(str::trim)
+            .filter(|shock| !shock.is_empty())
+            .map(|shock| {
+                let (symbol, change) = shock.split_once('=').context(format!(
+                    "Invalid price shock {}, expected SYMBOL=-15%",
+                    shock
+                ))?;
+                let change = change
+                    .trim()
+                    .trim_end_matches('%')
+                    .parse::<Decimal>()
+                    .context(format!("Invalid price change in shock {}", shock))?;
+                if change <= -Decimal::ONE_HUNDRED {
+                    return Err(anyhow::anyhow!(
+                        "Invalid price shock {}, a price can't drop by 100% or more",
+                        shock
+                    ));
+                }
+
+                Ok(PriceShock {
+                    symbol: symbol.trim().to_string(),
+                    change,
+                })
+            })
+            .collect()
+    }
+
+    /// Finds the accounts that become liquidatable under the given price shocks
+    ///
+    /// Accounts already liquidatable are left out, as are accounts with a reserve whose
+    /// price is unknown, which are counted as skipped.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `shocks` - Price shocks to apply, reserves without a shock keep their price
+    ///
+    /// # Returns
+    /// * `Result<StressReport>` - The accounts crossing below a health factor of 1.0
+    pub async fn run(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        shocks: &[PriceShock],
+    ) -> Result<StressReport> {
+        let reserves = reserves_table_helper::get_reserves(db).await?;
+
+        let mut shock_factors: HashMap<String, Decimal> = HashMap::new();
+        for shock in shocks {
+            let reserve = reserves
+                .iter()
+                .find(|reserve| reserve.symbol.eq_ignore_ascii_case(&shock.symbol))
+                .context(format!("Unknown reserve {}", shock.symbol))?;
+            shock_factors.insert(
+                reserve.reserve_address.clone(),
+                Decimal::ONE + shock.change / Decimal::ONE_HUNDRED,
+            );
+        }
+
+        let reserves_per_address = reserves
+            .iter()
+            .map(|reserve| (reserve.reserve_address.clone(), reserve))
+            .collect::<HashMap<String, &reserves::Model>>();
+        let mut prices = reserves
+            .iter()
+            .filter(|reserve| !reserve.price.is_zero())
+            .map(|reserve| (reserve.reserve_address.clone(), reserve.price))
+            .collect::<HashMap<String, Decimal>>();
+
+        let mut user_addresses = users_tables_helper::get_all_at_risk_users(db).await?;
+        user_addresses.extend(users_tables_helper::get_all_healthy_users(db).await?);
+        info!("Stress testing {} accounts", user_addresses.len());
+
+        let max_health_factor = Decimal::from(local_config.max_cap_on_health_factor);
+        let mut report = StressReport::default();
+
+        for user_addresses in user_addresses.chunks(ACCOUNTS_PER_CHUNK) {
+            let users = users_tables_helper::get_users(db, user_addresses).await?;
+
+            let mut positions_per_user: HashMap<String, Vec<user_debt_collateral::Model>> =
+                HashMap::new();
+            for position in
+                user_debt_collateral_helper::get_users_positions(db, user_addresses).await?
+            {
+                // Reserves whose price wasn't cached yet are priced from the stored values
+                if !prices.contains_key(&position.reserve_address) && !position.amount.is_zero() {
+                    if let Some(value_in_usd) = position.value_in_usd {
+                        prices.insert(
+                            position.reserve_address.clone(),
+                            value_in_usd / position.amount,
+                        );
+                    }
+                }
+
+                positions_per_user
+                    .entry(position.user_address.clone())
+                    .or_default()
+                    .push(position);
+            }
+
+            let stressed_prices = prices
+                .iter()
+                .map(|(reserve_address, price)| {
+                    let shock_factor = shock_factors
+                        .get(reserve_address)
+                        .copied()
+                        .unwrap_or(Decimal::ONE);
+                    (reserve_address.clone(), price.saturating_mul(shock_factor))
+                })
+                .collect::<HashMap<String, Decimal>>();
+
+            for user in users {
+                let Some(positions) = positions_per_user.get(&user.user_address) else {
+                    continue;
+                };
+
+                let Some(stressed_health_factor) = UserHelper::project_health_factor(
+                    user.health_factor,
+                    positions,
+                    &stressed_prices,
+                    |reserve_address| {
+                        reserves_per_address.get(reserve_address).map(|reserve| {
+                            Self::liquidation_threshold(reserve, user.emode_category)
+                        })
+                    },
+                    max_health_factor,
+                ) else {
+                    report.skipped_accounts += 1;
+                    continue;
+                };
+
+                if stressed_health_factor >= LIQUIDATION_THRESHOLD {
+                    continue;
+                }
+
+                let stressed_debt_in_usd = positions
+                    .iter()
+                    .filter(|position| !position.is_collateral)
+                    .filter_map(|position| {
+                        stressed_prices
+                            .get(&position.reserve_address)
+                            .map(|price| position.amount.saturating_mul(*price))
+                    })
+                    .fold(Decimal::ZERO, Decimal::saturating_add);
+
+                report.total_debt_at_risk = report
+                    .total_debt_at_risk
+                    .saturating_add(stressed_debt_in_usd);
+                report.accounts.push(StressedAccount {
+                    user_address: user.user_address,
+                    current_location: user.current_location,
+                    health_factor: user.health_factor,
+                    stressed_health_factor,
+                    stressed_debt_in_usd,
+                });
+            }
+        }
+
+        report
+            .accounts
+            .sort_by_key(|account| account.stressed_health_factor);
+
+        Ok(report)
+    }
+
+    /// Returns the liquidation threshold of a stored reserve for a user, the e-mode one
+    /// if the user is in the reserve's e-mode category
+    ///
+    /// # Arguments
+    /// * `reserve` - The stored reserve
+    /// * `user_emode_category` - E-mode category of the user, 0 when not in e-mode
+    ///
+    /// # Returns
+    /// * `Decimal` - The liquidation threshold that applies to the user
+    fn liquidation_threshold(reserve: &reserves::Model, user_emode_category: i32) -> Decimal {
+        if user_emode_category != 0 && reserve.emode_category == user_emode_category {
+            reserve.emode_liquidation_threshold
+        } else {
+            reserve.liquidation_threshold
+        }
+    }
+
+    /// Prints the report to the standard output
+    ///
+    /// # Arguments
+    /// * `report` - The stress test report
+    pub fn print_report(report: &StressReport) {
+        println!(
+            "{:<44} {:<14} {:>24} {:>24} {:>24}",
+            "user_address", "tier", "health_factor", "stressed_health_factor", "debt_in_usd"
+        );
+        for account in report.accounts.iter() {
+            println!(
+                "{:<44} {:<14} {:>24} {:>24} {:>24}",
+                account.user_address,
+                format!("{:?}", account.current_location),
+                account.health_factor.round_dp(4),
+                account.stressed_health_factor.round_dp(4),
+                account.stressed_debt_in_usd.round_dp(2)
+            );
+        }
+        println!();
+        println!(
+            "{} accounts would become liquidatable, total debt at risk: {} USD",
+            report.accounts.len(),
+            report.total_debt_at_risk.round_dp(2)
+        );
+        if report.skipped_accounts > 0 {
+            println!(
+                "{} accounts were skipped, a reserve of theirs has no known price",
+                report.skipped_accounts
+            );
+        }
+    }
+
+    /// Exports the report as CSV
+    ///
+    /// # Arguments
+    /// * `report` - The stress test report
+    /// * `path` - Path of the CSV file to write
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error if the file can't be written
+    pub fn export_report(report: &StressReport, path: &str) -> Result<()> {
+        let mut csv =
+            String::from("user_address,tier,health_factor,stressed_health_factor,debt_in_usd\n");
+        for account in report.accounts.iter() {
+            csv.push_str(&format!(
+                "{},{:?},{},{},{}\n",
+                account.user_address,
+                account.current_location,
+                account.health_factor,
+                account.stressed_health_factor,
+                account.stressed_debt_in_usd
+            ));
+        }
+
+        std::fs::write(path, csv).context(format!("Failed to write stress report to {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(shocks: &str) -> Vec<(String, Decimal)> {
+        StressTest::parse_shocks(shocks)
+            .unwrap()
+            .into_iter()
+            .map(|shock| (shock.symbol, shock.change))
+            .collect()
+    }
+
+    #[test]
+    fn parses_a_list_of_shocks() {
+        assert_eq!(
+            parse("WETH=-15%, WBTC = -10.5% ,USDC=2"),
+            vec![
+                ("WETH".to_string(), Decimal::from(-15)),
+                ("WBTC".to_string(), Decimal::new(-105, 1)),
+                ("USDC".to_string(), Decimal::from(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_shocks_are_ignored() {
+        assert!(parse("").is_empty());
+        assert_eq!(
+            parse("WETH=-15%,,"),
+            vec![("WETH".to_string(), Decimal::from(-15))]
+        );
+    }
+
+    #[test]
+    fn a_price_can_drop_by_almost_100_percent() {
+        assert_eq!(
+            parse("WETH=-99.99%"),
+            vec![("WETH".to_string(), Decimal::new(-9999, 2))]
+        );
+    }
+
+    #[test]
+    fn a_price_cant_drop_by_100_percent_or_more() {
+        assert!(StressTest::parse_shocks("WETH=-100%").is_err());
+        assert!(StressTest::parse_shocks("WETH=-150%").is_err());
+    }
+
+    fn reserve(emode_category: i32) -> reserves::Model {
+        reserves::Model {
+            id: 0,
+            reserve_address: String::new(),
+            decimals: 18,
+            debt_ceiling: Decimal::ZERO,
+            isolation_mode_total_debt: Decimal::ZERO,
+            siloed_borrowing: false,
+            borrow_cap: Decimal::ZERO,
+            supply_cap: Decimal::ZERO,
+            symbol: String::new(),
+            a_token_address: String::new(),
+            stable_debt_token_address: String::new(),
+            variable_debt_token_address: String::new(),
+            is_active: true,
+            is_frozen: false,
+            is_paused: false,
+            borrowing_enabled: true,
+            usage_as_collateral_enabled: true,
+            ltv: Decimal::new(75, 2),
+            liquidation_threshold: Decimal::new(8, 1),
+            liquidation_bonus: Decimal::new(105, 2),
+            liquidation_protocol_fee: Decimal::ZERO,
+            emode_category,
+            price: Decimal::ONE,
+            timestamp: Default::default(),
+            emode_liquidation_threshold: Decimal::new(95, 2),
+        }
+    }
+
+    #[test]
+    fn e_mode_accounts_get_the_e_mode_threshold_of_their_category() {
+        assert_eq!(
+            StressTest::liquidation_threshold(&reserve(1), 1),
+            Decimal::new(95, 2)
+        );
+        assert_eq!(
+            StressTest::liquidation_threshold(&reserve(1), 2),
+            Decimal::new(8, 1)
+        );
+        assert_eq!(
+            StressTest::liquidation_threshold(&reserve(1), 0),
+            Decimal::new(8, 1)
+        );
+        assert_eq!(
+            StressTest::liquidation_threshold(&reserve(0), 0),
+            Decimal::new(8, 1)
+        );
+    }
+
+    #[test]
+    fn malformed_shocks_are_rejected() {
+        assert!(StressTest::parse_shocks("WETH-15%").is_err());
+        assert!(StressTest::parse_shocks("WETH=fifteen").is_err());
+        assert!(StressTest::parse_shocks("WETH=-15%,WBTC").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use indexer_database::users_tables_helper::UserCurrentLocation;
+use rust_decimal::Decimal;
+use tokio::sync::broadcast;
+
+/// Number of user events kept for slow subscribers, older events are dropped
+const USER_EVENTS_CAPACITY: usize = 4096;
+
+/// Why a user event was published
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserEventKind {
+    /// The user moved to another tier, or was added to a tier
+    TierChanged,
+    /// The user stayed in its tier but its health factor moved by more than
+    /// `HEALTH_FACTOR_CHANGE_THRESHOLD` percent
+    HealthFactorChanged,
+}
+
+/// A change of a user's state written to the database
+#[derive(Debug, Clone)]
+pub struct UserEvent {
+    pub kind: UserEventKind,
+    pub user_address: String,
+    /// Tier before the refresh, `NotFound` for a new user
+    pub old_location: UserCurrentLocation,
+    pub new_location: UserCurrentLocation,
+    /// Health factor before the refresh, `None` for a new user
+    pub old_health_factor: Option<Decimal>,
+    pub health_factor: Decimal,
+    pub total_debt_value_in_usd: Decimal,
+    /// Reserves the user holds as collateral or debt
+    pub reserves: Vec<String>,
+    pub block_number: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl UserEvent {
+    /// Returns the kind of event to publish for a refreshed user, if any
+    ///
+    /// # Arguments
+    /// * `health_factor_change_threshold` - Relative health factor move, in percent, that
+    ///   is published for users staying in their tier
+    /// * `old_location` - Tier of the user before the refresh
+    /// * `old_health_factor` - Health factor of the user before the refresh
+    /// * `new_location` - Tier of the user after the refresh
+    /// * `health_factor` - Health factor of the user after the refresh
+    ///
+    /// # Returns
+    /// * `Option<UserEventKind>` - The kind of event to publish, `None` if nothing worth
+    ///   publishing changed
+    pub fn kind_of_change(
+        health_factor_change_threshold: Decimal,
+        old_location: &UserCurrentLocation,
+        old_health_factor: Option<Decimal>,
+        new_location: &UserCurrentLocation,
+        health_factor: Decimal,
+    ) -> Option<UserEventKind> {
+        if old_location != new_location {
+            return Some(UserEventKind::TierChanged);
+        }
+
+        let old_health_factor = old_health_factor?;
+        if old_health_factor == health_factor {
+            return None;
+        }
+        let change = (health_factor - old_health_factor)
+            .abs()
+            .checked_div(old_health_factor)
+            .map(|change| change * Decimal::ONE_HUNDRED)
+            .unwrap_or(Decimal::MAX);
+
+        (change >= health_factor_change_threshold).then_some(UserEventKind::HealthFactorChanged)
+    }
+}
+
+/// Subscriber side filter of the user events
+#[derive(Debug, Clone, Default)]
+pub struct UserEventFilter {
+    /// Tiers the user must be in after the event, any tier if empty
+    pub locations: Vec<UserCurrentLocation>,
+    /// Minimum debt of the user in USD
+    pub min_debt_in_usd: Option<Decimal>,
+    /// Reserve the user must hold as collateral or debt
+    pub reserve: Option<String>,
+}
+
+impl UserEventFilter {
+    /// Returns whether the event passes the filter
+    ///
+    /// # Arguments
+    /// * `event` - The user event
+    ///
+    /// # Returns
+    /// * `bool` - True if the event should be sent to the subscriber
+    pub fn matches(&self, event: &UserEvent) -> bool {
+        (self.locations.is_empty() || self.locations.contains(&event.new_location))
+            && self
+                .min_debt_in_usd
+                .is_none_or(|min_debt| event.total_debt_value_in_usd >= min_debt)
+            && self.reserve.as_ref().is_none_or(|reserve| {
+                event
+                    .reserves
+                    .iter()
+                    .any(|event_reserve| event_reserve.eq_ignore_ascii_case(reserve))
+            })
+    }
+}
+
+/// Publishes the tier transitions and health factor moves of the users as they are
+/// written, so consumers don't have to poll the accounts table
+///
+/// Events are only published once the transaction writing them is committed. Slow
+/// subscribers miss the oldest events once `USER_EVENTS_CAPACITY` events are pending.
+pub struct UserEvents {
+    events: broadcast::Sender<UserEvent>,
+}
+
+impl Default for UserEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UserEvents {
+    /// Creates a publisher without subscribers
+    ///
+    /// # Returns
+    /// * `Self` - A new UserEvents instance
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(USER_EVENTS_CAPACITY);
+        Self { events }
+    }
+
+    /// Publishes events to the current subscribers
+    ///
+    /// # Arguments
+    /// * `events` - The events, in the order they were written
+    pub fn publish(&self, events: Vec<UserEvent>) {
+        for event in events {
+            // Sending only fails when nobody is subscribed
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Subscribes to the events published from now on
+    ///
+    /// # Returns
+    /// * `broadcast::Receiver<UserEvent>` - Receiver of the events
+    pub fn subscribe(&self) -> broadcast::Receiver<UserEvent> {
+        self.events.subscribe()
+    }
+}
@@ -0,0 +1,379 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alloy::{network::Ethereum, providers::Provider};
+use anyhow::{Context, Result};
+use indexer_database::{
+    liquidation_opportunities_helper, user_debt_collateral_helper,
+    users_tables_helper::{self, UserCurrentLocation, UserDetails},
+};
+use sea_orm::{ConnectionTrait, DatabaseConnection, TransactionTrait};
+use tracing::{error, info};
+
+use super::{models, UserHelper, UserUpdateFailure};
+use crate::{
+    blockchain_manager::{multicall::MulticallManager, AaveHelperContract},
+    config::LocalConfig,
+    metrics::METRICS,
+    reserves_helper::AaveReserve,
+    user_events::{UserEvent, UserEvents},
+};
+
+impl UserHelper {
+    /// Returns how many users fit in a single multicall under `MULTICALL_MAX_CALLS`,
+    /// leaving room for the reserve prices call
+    ///
+    /// The multicall is only ever run through `eth_call`, so it's bounded by the node's
+    /// RPC gas cap rather than the block gas limit. Every queued call is a view over a
+    /// single user and reserve with a roughly constant cost, so capping the number of
+    /// calls caps the gas too; `MULTICALL_MAX_CALLS` has to be sized for the node's cap.
+    ///
+    /// # Arguments
+    /// * `local_config` - Local configuration settings
+    /// * `reserves_count` - Number of Aave reserves queried per user
+    ///
+    /// # Returns
+    /// * `usize` - Number of users per multicall, at least one
+    pub fn users_per_multicall(local_config: &LocalConfig, reserves_count: usize) -> usize {
+        (local_config.multicall_max_calls.saturating_sub(1) as usize / (2 + reserves_count)).max(1)
+    }
+
+    /// Updates many users' data in the database with the given block number.
+    /// Users are packed into as few multicalls as `MULTICALL_MAX_CALLS` allows, together
+    /// with the reserve prices, the results are decoded back per user and all database
+    /// writes are done in bulk.
+    /// Users whose data is recent enough are skipped, as in `update_user`, and users
+    /// whose results can't be decoded are reported back without failing the batch.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `user_addresses` - Ethereum addresses of the users
+    /// * `block_number` - Current block number being processed
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<Vec<UserUpdateFailure>>` - Users that could not be refreshed, or an error if the whole batch failed
+    #[allow(clippy::too_many_arguments)]
+    pub async fn update_users_batch<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        user_addresses: &[String],
+        block_number: u64,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        multicall_manager: &mut MulticallManager<&'a P>,
+        user_events: &UserEvents,
+    ) -> Result<Vec<UserUpdateFailure>> {
+        Self::update_users_batch_in_db(
+            db,
+            local_config,
+            user_addresses,
+            block_number,
+            aave_helper_contracts,
+            aave_reserves,
+            multicall_manager,
+            user_events,
+            false,
+        )
+        .await
+    }
+
+    /// Updates many users' data in the database with the given block number, even if
+    /// the stored data is recent. Used to re-evaluate users whose stored state was
+    /// computed with prices that have moved since.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `user_addresses` - Ethereum addresses of the users
+    /// * `block_number` - Current block number being processed
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<Vec<UserUpdateFailure>>` - Users that could not be refreshed, or an error if the whole batch failed
+    #[allow(clippy::too_many_arguments)]
+    pub async fn force_update_users_batch<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        user_addresses: &[String],
+        block_number: u64,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        multicall_manager: &mut MulticallManager<&'a P>,
+        user_events: &UserEvents,
+    ) -> Result<Vec<UserUpdateFailure>> {
+        Self::update_users_batch_in_db(
+            db,
+            local_config,
+            user_addresses,
+            block_number,
+            aave_helper_contracts,
+            aave_reserves,
+            multicall_manager,
+            user_events,
+            true,
+        )
+        .await
+    }
+
+    /// Fetches many users' data from the blockchain and writes it to the database
+    /// This is an internal function called by update_users_batch and force_update_users_batch
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `user_addresses` - Ethereum addresses of the users
+    /// * `block_number` - Current block number being processed
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `multicall_manager` - Multicall manager used to fetch the users
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    /// * `force` - Whether users whose data is recent enough are refreshed too
+    ///
+    /// # Returns
+    /// * `Result<Vec<UserUpdateFailure>>` - Users that could not be refreshed, or an error if the whole batch failed
+    #[allow(clippy::too_many_arguments)]
+    async fn update_users_batch_in_db<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        user_addresses: &[String],
+        block_number: u64,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        multicall_manager: &mut MulticallManager<&'a P>,
+        user_events: &UserEvents,
+        force: bool,
+    ) -> Result<Vec<UserUpdateFailure>> {
+        let mut existing_users = users_tables_helper::get_users(db, user_addresses)
+            .await?
+            .into_iter()
+            .map(|user| (user.user_address.clone(), user))
+            .collect::<HashMap<String, UserDetails>>();
+
+        // Skip users whose data is recent enough, unless forced
+        let users_to_update = user_addresses
+            .iter()
+            .filter(|user_address| match existing_users.get(*user_address) {
+                Some(details) => {
+                    force
+                        || block_number as i64 - details.last_updated_block_number as i64
+                            >= local_config.max_block_lag as i64
+                }
+                None => true,
+            })
+            .collect::<Vec<_>>();
+
+        if users_to_update.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let calls_per_user = 2 + aave_reserves.len();
+        let users_per_multicall = Self::users_per_multicall(local_config, aave_reserves.len());
+
+        let mut account_states = Vec::with_capacity(users_to_update.len());
+        let mut failures = Vec::new();
+
+        for users_chunk in users_to_update.chunks(users_per_multicall) {
+            Self::add_prices_call(multicall_manager, aave_helper_contracts, aave_reserves);
+            let results = match users_chunk.iter().try_for_each(|user_address| {
+                Self::add_user_calls(
+                    multicall_manager,
+                    aave_helper_contracts,
+                    aave_reserves,
+                    user_address,
+                )
+            }) {
+                Ok(()) => multicall_manager.execute_calls(block_number).await,
+                Err(e) => Err(e),
+            };
+            // The multicall manager is shared, so the queued calls are dropped on every path
+            multicall_manager.clear_calls();
+            let results = results?;
+
+            // Results that can't be matched to the users fail the whole chunk
+            let expected_results = 1 + users_chunk.len() * calls_per_user;
+            if results.len() != expected_results {
+                let error = format!(
+                    "Multicall returned {} results, expected {}",
+                    results.len(),
+                    expected_results
+                );
+                error!("{} for {} users", error, users_chunk.len());
+                failures.extend(users_chunk.iter().map(|user_address| UserUpdateFailure {
+                    user_address: user_address.to_string(),
+                    error: error.clone(),
+                }));
+                continue;
+            }
+
+            // Every user in the multicall is valued with the same prices, so prices that
+            // can't be decoded fail the whole chunk
+            let prices = match Self::decode_prices(&results[0], aave_reserves) {
+                Ok(prices) => prices,
+                Err(e) => {
+                    error!(
+                        "Failed to decode prices for {} users: {}",
+                        users_chunk.len(),
+                        e
+                    );
+                    failures.extend(users_chunk.iter().map(|user_address| UserUpdateFailure {
+                        user_address: user_address.to_string(),
+                        error: e.to_string(),
+                    }));
+                    continue;
+                }
+            };
+
+            for (user_address, user_results) in
+                users_chunk.iter().zip(results[1..].chunks(calls_per_user))
+            {
+                match Self::decode_user_account_state(
+                    user_address,
+                    user_results,
+                    aave_reserves,
+                    &prices,
+                    local_config,
+                )
+                .await
+                {
+                    Ok(account_state) => account_states.push(account_state),
+                    Err(e) => {
+                        error!("Failed to decode data for user {}: {}", user_address, e);
+                        failures.push(UserUpdateFailure {
+                            user_address: user_address.to_string(),
+                            error: e.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        info!(
+            "Fetched {} users in {} multicalls",
+            account_states.len(),
+            users_to_update.len().div_ceil(users_per_multicall)
+        );
+
+        let refreshed_users = account_states.len() as u64;
+
+        // Write the whole batch at once so a failure leaves every user as it was
+        let timer = METRICS
+            .db_write_duration
+            .with_label_values(&["users_batch"])
+            .start_timer();
+        let txn = db.begin().await?;
+        let events = Self::add_or_update_users_to_db(
+            &txn,
+            local_config,
+            block_number,
+            account_states,
+            &mut existing_users,
+        )
+        .await?;
+        txn.commit().await?;
+        timer.observe_duration();
+
+        METRICS.users_refreshed.inc_by(refreshed_users);
+        user_events.publish(events);
+
+        Ok(failures)
+    }
+
+    /// Adds or updates many users' basic information, positions and liquidation
+    /// opportunities in the database using bulk statements grouped by tier.
+    /// Users changing tier are updated in place.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle or transaction
+    /// * `local_config` - Local configuration settings
+    /// * `block_number` - Current block number
+    /// * `account_states` - Users' account states decoded from the blockchain
+    /// * `existing_users` - Existing user details from database, keyed by user address
+    ///
+    /// # Returns
+    /// * `Result<Vec<UserEvent>>` - The changes to publish once the writes are committed
+    async fn add_or_update_users_to_db(
+        db: &impl ConnectionTrait,
+        local_config: &LocalConfig,
+        block_number: u64,
+        account_states: Vec<models::UserAccountState>,
+        existing_users: &mut HashMap<String, UserDetails>,
+    ) -> Result<Vec<UserEvent>> {
+        let mut users_to_upsert: HashMap<UserCurrentLocation, Vec<UserDetails>> = HashMap::new();
+        let mut events = Vec::new();
+        let mut users_positions = Vec::with_capacity(account_states.len());
+        let mut users_liquidations = Vec::with_capacity(account_states.len());
+
+        for account_state in account_states {
+            let existing_user = existing_users.remove(&account_state.user_address);
+            let old_health_factor = existing_user.as_ref().map(|user| user.health_factor);
+            let user_details =
+                Self::build_user_details(block_number, &account_state, existing_user);
+            let user_old_location = user_details.current_location.clone();
+
+            let new_location = Self::get_user_new_location(
+                account_state.health_factor,
+                local_config.at_risk_health_factor,
+            );
+
+            if user_old_location != new_location {
+                info!(
+                    "Moved user [HF: {}] {} from {:?} to {:?}",
+                    account_state.health_factor,
+                    account_state.user_address,
+                    user_old_location,
+                    new_location
+                );
+            }
+
+            events.extend(Self::build_user_event(
+                local_config,
+                block_number,
+                &account_state,
+                &user_old_location,
+                old_health_factor,
+                &new_location,
+            ));
+
+            users_to_upsert
+                .entry(new_location)
+                .or_default()
+                .push(user_details);
+
+            users_liquidations.push((
+                account_state.user_address.clone(),
+                account_state.liquidation,
+            ));
+
+            users_positions.push((
+                account_state.user_address,
+                account_state.user_reserve_data.collateral_assets,
+                account_state.user_reserve_data.debt_assets,
+            ));
+        }
+
+        for (location, users) in users_to_upsert {
+            users_tables_helper::upsert_users(db, users, location)
+                .await
+                .context("Failed to update users in the database")?;
+        }
+
+        user_debt_collateral_helper::add_or_update_users_debt_collateral(db, users_positions)
+            .await
+            .context("Failed to update users positions")?;
+
+        liquidation_opportunities_helper::update_liquidation_opportunities(
+            db,
+            block_number,
+            users_liquidations,
+        )
+        .await
+        .context("Failed to update users liquidation opportunities")?;
+
+        Ok(events)
+    }
+}
@@ -1,31 +1,39 @@
+mod batch;
 mod models;
+mod projection;
+
+pub use models::UserUpdateFailure;
 
 use std::sync::Arc;
 
 use alloy::{
     network::Ethereum,
-    primitives::{Address, Bytes},
+    primitives::{Address, Bytes, U256},
     providers::Provider,
     sol_types::SolCall,
 };
 use anyhow::{Context, Result};
 use chrono::Utc;
 use indexer_database::{
-    user_debt_collateral_helper,
+    entities::sea_orm_active_enums::RateMode,
+    liquidation_opportunities_helper,
+    user_debt_collateral_helper::{self, UserPosition},
     users_tables_helper::{self, UserCurrentLocation, UserDetails},
 };
-use sea_orm::DatabaseConnection;
+use rust_decimal::Decimal;
+use sea_orm::{ConnectionTrait, DatabaseConnection, TransactionTrait};
 use tracing::info;
 
 use crate::{
     blockchain_manager::{multicall::MulticallManager, AaveHelperContract},
     config::LocalConfig,
+    liquidation::LiquidationHelper,
+    metrics::METRICS,
+    reserves_helper::AaveReserve,
+    user_events::{UserEvent, UserEvents},
     utils::{
-        constants::{
-            HEALTH_FACTOR_DECIMALS, LIQUIDATION_THRESHOLD, TOKEN_BALANCE_DECIMALS,
-            USD_VALUE_DECIMALS,
-        },
-        contracts::{AavePoolContract, AavePoolDataProviderContract},
+        constants::{HEALTH_FACTOR_DECIMALS, LIQUIDATION_THRESHOLD, USD_VALUE_DECIMALS},
+        contracts::{AaveOracleContract, AavePoolContract, AavePoolDataProviderContract},
         math_helper,
     },
 };
@@ -45,21 +53,37 @@ impl UserHelper {
     /// * `user_address` - Ethereum address of the user
     /// * `block_number` - Current block number being processed
     /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
-    /// * `aave_reserves` - List of Aave reserve token addresses
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `user_events` - Publisher of the user's tier and health factor changes
     ///
     /// # Returns
-    /// * `Result<()>` - Success or error result of the update operation
+    /// * `Result<bool>` - True if the user was refreshed, false if its data was recent enough
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_user<'a, P: Provider<Ethereum>>(
         db: &DatabaseConnection,
         local_config: &LocalConfig,
         user_address: &str,
         block_number: u64,
         aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
-        aave_reserves: &[Address],
+        aave_reserves: &[AaveReserve],
         multicall_manager: &mut MulticallManager<&'a P>,
-    ) -> Result<()> {
+        user_events: &UserEvents,
+    ) -> Result<bool> {
         // Get user details
-        let user_details = users_tables_helper::get_user(db, &user_address).await?;
+        let user_details = users_tables_helper::get_user(db, user_address).await?;
+
+        // Skip update if user data is recent enough
+        if let Some(details) = user_details.as_ref() {
+            let blocks_since_last_update: i64 =
+                block_number as i64 - details.last_updated_block_number as i64;
+            if blocks_since_last_update < local_config.max_block_lag as i64 {
+                info!(
+                    "User {} data is recent (last updated: block {}, current: block {})",
+                    user_address, details.last_updated_block_number, block_number
+                );
+                return Ok(false);
+            }
+        }
 
         Self::update_user_in_db(
             db,
@@ -70,69 +94,290 @@ impl UserHelper {
             user_details,
             local_config,
             multicall_manager,
+            user_events,
+        )
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Updates the user's data in the database at the given block number, even if
+    /// the stored data is recent. Used to re-evaluate users whose stored state may
+    /// have been computed from orphaned blocks.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `user_address` - Ethereum address of the user
+    /// * `block_number` - Current block number being processed
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `user_events` - Publisher of the user's tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error result of the update operation
+    #[allow(clippy::too_many_arguments)]
+    pub async fn force_update_user<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        user_address: &str,
+        block_number: u64,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        multicall_manager: &mut MulticallManager<&'a P>,
+        user_events: &UserEvents,
+    ) -> Result<()> {
+        let user_details = users_tables_helper::get_user(db, user_address).await?;
+
+        Self::update_user_in_db(
+            db,
+            user_address,
+            block_number,
+            aave_helper_contracts,
+            aave_reserves,
+            user_details,
+            local_config,
+            multicall_manager,
+            user_events,
         )
         .await?;
 
         Ok(())
     }
 
-    /// Updates a user's data in the database if necessary based on block lag settings
-    /// This is an internal function called by update_user that handles the actual database operations
+    /// Fetches a user's data from the blockchain and writes it to the database
+    /// This is an internal function called by update_user and force_update_user that handles the actual database operations
     ///
     /// # Arguments
     /// * `db` - Database connection handle
     /// * `user_address` - Ethereum address of the user
     /// * `block_number` - Current block number being processed
     /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
-    /// * `aave_reserves` - List of Aave reserve token addresses
+    /// * `aave_reserves` - List of Aave reserves
     /// * `user_details` - Optional existing user details from database
     /// * `local_config` - Local configuration settings
+    /// * `user_events` - Publisher of the user's tier and health factor changes
     ///
     /// # Returns
     /// * `Result<()>` - Success or error result of the database update operation
+    #[allow(clippy::too_many_arguments)]
     async fn update_user_in_db<'a, P: Provider<Ethereum>>(
         db: &DatabaseConnection,
         user_address: &str,
         block_number: u64,
         aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
-        aave_reserves: &[Address],
+        aave_reserves: &[AaveReserve],
         user_details: Option<users_tables_helper::UserDetails>,
         local_config: &LocalConfig,
         multicall_manager: &mut MulticallManager<&'a P>,
+        user_events: &UserEvents,
     ) -> Result<()> {
-        // Skip update if user data is recent enough
-        if let Some(details) = user_details.as_ref() {
-            let blocks_since_last_update: i64 =
-                block_number as i64 - details.last_updated_block_number as i64;
-            if blocks_since_last_update < local_config.max_block_lag as i64 {
-                info!(
-                    "User {} data is recent (last updated: block {}, current: block {})",
-                    user_address, details.last_updated_block_number, block_number
-                );
-                return Ok(());
-            }
+        Self::add_prices_call(multicall_manager, aave_helper_contracts, aave_reserves);
+        let results = match Self::add_user_calls(
+            multicall_manager,
+            aave_helper_contracts,
+            aave_reserves,
+            user_address,
+        ) {
+            Ok(()) => multicall_manager.execute_calls(block_number).await,
+            Err(e) => Err(e),
+        };
+        // The multicall manager is shared, so the queued calls are dropped on every path
+        multicall_manager.clear_calls();
+        let results = results?;
+
+        let expected_results = 3 + aave_reserves.len();
+        if results.len() != expected_results {
+            return Err(anyhow::anyhow!(
+                "Multicall returned {} results, expected {}",
+                results.len(),
+                expected_results
+            ));
         }
 
+        let prices = Self::decode_prices(&results[0], aave_reserves)?;
+        let account_state = Self::decode_user_account_state(
+            user_address,
+            &results[1..],
+            aave_reserves,
+            &prices,
+            local_config,
+        )
+        .await?;
+
+        // Write the user and its positions together so they can't get out of sync
+        let timer = METRICS
+            .db_write_duration
+            .with_label_values(&["user"])
+            .start_timer();
+        let txn = db.begin().await?;
+
+        // Update user's risk category and basic info
+        let user_event = Self::add_or_update_user_to_db(
+            &txn,
+            local_config,
+            block_number,
+            &account_state,
+            user_details,
+        )
+        .await
+        .context("Failed to update user basic information")?;
+
+        // Update user's detailed position data
+        Self::add_or_update_user_debt_collateral(
+            &txn,
+            user_address,
+            account_state.user_reserve_data.collateral_assets,
+            account_state.user_reserve_data.debt_assets,
+        )
+        .await
+        .context("Failed to update user positions")?;
+
+        liquidation_opportunities_helper::update_liquidation_opportunities(
+            &txn,
+            block_number,
+            vec![(user_address.to_string(), account_state.liquidation)],
+        )
+        .await
+        .context("Failed to update user liquidation opportunity")?;
+
+        txn.commit().await?;
+        timer.observe_duration();
+
+        METRICS.users_refreshed.inc();
+        user_events.publish(user_event.into_iter().collect());
+
+        Ok(())
+    }
+
+    /// Queues a `getAssetsPrices` call for all reserves, so positions are valued with
+    /// prices from the same block as the balances
+    ///
+    /// # Arguments
+    /// * `multicall_manager` - Multicall manager to add the call to
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    fn add_prices_call<'a, P: Provider<Ethereum>>(
+        multicall_manager: &mut MulticallManager<&'a P>,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+    ) {
         multicall_manager.add_call(
-            &aave_helper_contracts.pool_contract.address(),
-            &aave_helper_contracts
+            aave_helper_contracts.oracle_contract.address(),
+            aave_helper_contracts
+                .oracle_contract
+                .getAssetsPrices(
+                    aave_reserves
+                        .iter()
+                        .map(|reserve| reserve.address)
+                        .collect(),
+                )
+                .calldata(),
+        );
+    }
+
+    /// Decodes the result of the call queued by `add_prices_call`
+    ///
+    /// # Arguments
+    /// * `result` - Multicall result of the `getAssetsPrices` call
+    /// * `aave_reserves` - List of Aave reserves
+    ///
+    /// # Returns
+    /// * `Result<Vec<Decimal>>` - Price of each reserve in the oracle's base currency (USD)
+    fn decode_prices(result: &Bytes, aave_reserves: &[AaveReserve]) -> Result<Vec<Decimal>> {
+        let prices =
+            AaveOracleContract::getAssetsPricesCall::abi_decode_returns(result.as_ref(), false)
+                .context("Failed to decode reserve prices")?
+                ._0;
+
+        if prices.len() != aave_reserves.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} reserve prices, got {}",
+                aave_reserves.len(),
+                prices.len()
+            ));
+        }
+
+        Ok(prices
+            .into_iter()
+            .map(|price| math_helper::divide_by_precision(price, USD_VALUE_DECIMALS))
+            .collect())
+    }
+
+    /// Queues the calls needed to refresh a user: one `getUserAccountData` call and one
+    /// `getUserEMode` call, followed by one `getUserReserveData` call per reserve
+    ///
+    /// # Arguments
+    /// * `multicall_manager` - Multicall manager to add the calls to
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `user_address` - Ethereum address of the user
+    ///
+    /// # Returns
+    /// * `Result<()>` - Error if the user address is invalid
+    fn add_user_calls<'a, P: Provider<Ethereum>>(
+        multicall_manager: &mut MulticallManager<&'a P>,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        user_address: &str,
+    ) -> Result<()> {
+        let user: Address = user_address.parse()?;
+
+        multicall_manager.add_call(
+            aave_helper_contracts.pool_contract.address(),
+            aave_helper_contracts
+                .pool_contract
+                .getUserAccountData(user)
+                .calldata(),
+        );
+
+        multicall_manager.add_call(
+            aave_helper_contracts.pool_contract.address(),
+            aave_helper_contracts
                 .pool_contract
-                .getUserAccountData(user_address.parse()?)
+                .getUserEMode(user)
                 .calldata(),
         );
 
         for reserve in aave_reserves {
             multicall_manager.add_call(
-                &aave_helper_contracts.pool_data_provider_contract.address(),
-                &aave_helper_contracts
+                aave_helper_contracts.pool_data_provider_contract.address(),
+                aave_helper_contracts
                     .pool_data_provider_contract
-                    .getUserReserveData(reserve.clone(), user_address.parse()?)
+                    .getUserReserveData(reserve.address, user)
                     .calldata(),
             );
         }
 
-        let results = multicall_manager.execute_calls(block_number).await?;
-        multicall_manager.clear_calls();
+        Ok(())
+    }
+
+    /// Decodes the results of the calls queued by `add_user_calls` into the user's account state
+    ///
+    /// # Arguments
+    /// * `user_address` - Ethereum address of the user
+    /// * `results` - Multicall results for this user, in the order they were queued
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `prices` - Price of each reserve, as decoded by `decode_prices`
+    /// * `local_config` - Local configuration settings
+    ///
+    /// # Returns
+    /// * `Result<models::UserAccountState>` - The user's decoded account state
+    async fn decode_user_account_state(
+        user_address: &str,
+        results: &[Bytes],
+        aave_reserves: &[AaveReserve],
+        prices: &[Decimal],
+        local_config: &LocalConfig,
+    ) -> Result<models::UserAccountState> {
+        if results.len() != 2 + aave_reserves.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} results for user {}, got {}",
+                2 + aave_reserves.len(),
+                user_address,
+                results.len()
+            ));
+        }
 
         let user_account_data = AavePoolContract::getUserAccountDataCall::abi_decode_returns(
             results[0].as_ref(),
@@ -140,42 +385,39 @@ impl UserHelper {
         )?;
 
         // Fetch user's current state from blockchain
-        let (health_factor, total_collateral_usd, total_debt_usd) =
+        let (health_factor, total_collateral_value_in_usd, total_debt_value_in_usd) =
             Self::get_user_data(&user_account_data, local_config.max_cap_on_health_factor)
                 .await
                 .context("Failed to fetch user data from blockchain")?;
 
+        let emode_category =
+            AavePoolContract::getUserEModeCall::abi_decode_returns(results[1].as_ref(), false)?._0;
+        let emode_category =
+            u8::try_from(emode_category).context("Invalid user e-mode category")?;
+
         // Get detailed reserve data for user's positions
-        let user_positions = Self::get_user_reserve_data(&results[1..], aave_reserves)
-            .await
-            .context("Failed to fetch user reserve data")?;
+        let user_reserve_data =
+            Self::get_user_reserve_data(&results[2..], aave_reserves, prices, emode_category)
+                .await
+                .context("Failed to fetch user reserve data")?;
 
-        // Update user's risk category and basic info
-        Self::add_or_update_user_to_db(
-            db,
-            local_config,
-            user_address,
-            block_number,
+        let liquidation = LiquidationHelper::find_best_liquidation(
             health_factor,
-            total_collateral_usd,
-            total_debt_usd,
-            user_positions.clone(),
-            user_details,
-        )
-        .await
-        .context("Failed to update user basic information")?;
-
-        // Update user's detailed position data
-        Self::add_or_update_user_debt_collateral(
-            db,
-            user_address,
-            user_positions.collateral_assets,
-            user_positions.debt_assets,
-        )
-        .await
-        .context("Failed to update user positions")?;
+            emode_category,
+            &user_reserve_data.collateral_assets,
+            &user_reserve_data.debt_assets,
+            aave_reserves,
+        );
 
-        Ok(())
+        Ok(models::UserAccountState {
+            user_address: user_address.to_string(),
+            health_factor,
+            total_collateral_value_in_usd,
+            total_debt_value_in_usd,
+            emode_category,
+            user_reserve_data,
+            liquidation,
+        })
     }
 
     /// Fetches user's health factor and collateral/debt values from the Aave pool contract
@@ -186,25 +428,24 @@ impl UserHelper {
     /// * `max_health_factor` - Maximum allowed health factor value
     ///
     /// # Returns
-    /// * `Result<(f64, f64, f64)>` - Tuple containing (health_factor, total_collateral_value_in_usd, total_debt_value_in_usd)
+    /// * `Result<(Decimal, Decimal, Decimal)>` - Tuple containing (health_factor, total_collateral_value_in_usd, total_debt_value_in_usd)
     async fn get_user_data(
         account_data: &AavePoolContract::getUserAccountDataReturn,
         max_health_factor: u64,
-    ) -> Result<(f64, f64, f64)> {
-        // Calculate and cap health factor
-
-        // Calculate and cap health factor
-        let mut health_factor =
-            math_helper::divide_by_precision_f64(account_data.healthFactor, HEALTH_FACTOR_DECIMALS);
-        health_factor = health_factor.min(max_health_factor as f64);
+    ) -> Result<(Decimal, Decimal, Decimal)> {
+        // Cap the health factor before converting it, users without debt report U256::MAX
+        let max_health_factor =
+            U256::from(max_health_factor) * U256::from(10).pow(U256::from(HEALTH_FACTOR_DECIMALS));
+        let health_factor = math_helper::divide_by_precision(
+            account_data.healthFactor.min(max_health_factor),
+            HEALTH_FACTOR_DECIMALS,
+        );
 
         // Calculate USD values
-        let collateral_usd = math_helper::divide_by_precision_f64(
-            account_data.totalCollateralBase,
-            USD_VALUE_DECIMALS,
-        );
+        let collateral_usd =
+            math_helper::divide_by_precision(account_data.totalCollateralBase, USD_VALUE_DECIMALS);
         let debt_usd =
-            math_helper::divide_by_precision_f64(account_data.totalDebtBase, USD_VALUE_DECIMALS);
+            math_helper::divide_by_precision(account_data.totalDebtBase, USD_VALUE_DECIMALS);
 
         Ok((health_factor, collateral_usd, debt_usd))
     }
@@ -215,17 +456,31 @@ impl UserHelper {
     /// * `pool_data_provider` - Reference to the Aave pool data provider contract
     /// * `reserves` - List of reserve token addresses to check
     /// * `user_address` - Ethereum address of the user
+    /// * `emode_category` - E-mode category of the user, 0 when not in e-mode
     ///
     /// # Returns
     /// * `Result<models::UserReserveData>` - Structured data containing user's collateral and debt positions
     async fn get_user_reserve_data(
         results: &[Bytes],
-        reserves: &[Address],
+        reserves: &[AaveReserve],
+        prices: &[Decimal],
+        emode_category: u8,
     ) -> Result<models::UserReserveData> {
         let mut collateral_positions = Vec::new();
         let mut debt_positions = Vec::new();
+        let mut enabled_collateral_reserves = Vec::new();
+        let mut has_siloed_borrowing = false;
+
+        if results.len() != reserves.len() || prices.len() != reserves.len() {
+            return Err(anyhow::anyhow!(
+                "Expected {} reserve results and prices, got {} results and {} prices",
+                reserves.len(),
+                results.len(),
+                prices.len()
+            ));
+        }
 
-        for (reserve, result) in reserves.iter().zip(results.iter()) {
+        for ((reserve, price), result) in reserves.iter().zip(prices).zip(results.iter()) {
             let position =
                 AavePoolDataProviderContract::getUserReserveDataCall::abi_decode_returns(
                     result.as_ref(),
@@ -234,26 +489,57 @@ impl UserHelper {
 
             // Process collateral position
             if !position.currentATokenBalance.is_zero() {
-                let balance = math_helper::divide_by_precision_f64(
+                let amount = math_helper::divide_by_precision(
                     position.currentATokenBalance,
-                    TOKEN_BALANCE_DECIMALS,
+                    reserve.decimals,
                 );
-                collateral_positions.push((reserve.to_string(), balance as f32));
+                collateral_positions.push(UserPosition {
+                    reserve_address: reserve.address.to_string(),
+                    amount,
+                    amount_raw: position.currentATokenBalance.to_string(),
+                    rate_mode: RateMode::None,
+                    value_in_usd: amount.saturating_mul(*price),
+                    usage_as_collateral_enabled: position.usageAsCollateralEnabled,
+                });
+
+                if position.usageAsCollateralEnabled {
+                    enabled_collateral_reserves.push(reserve);
+                }
             }
 
-            // Process debt position
-            if !position.currentVariableDebt.is_zero() {
-                let balance = math_helper::divide_by_precision_f64(
-                    position.currentVariableDebt,
-                    TOKEN_BALANCE_DECIMALS,
-                );
-                debt_positions.push((reserve.to_string(), balance as f32));
+            // Process debt positions, stable and variable debt are tracked separately
+            for (debt, rate_mode) in [
+                (position.currentStableDebt, RateMode::Stable),
+                (position.currentVariableDebt, RateMode::Variable),
+            ] {
+                if !debt.is_zero() {
+                    has_siloed_borrowing |= reserve.configuration.siloed_borrowing;
+                    let amount = math_helper::divide_by_precision(debt, reserve.decimals);
+                    debt_positions.push(UserPosition {
+                        reserve_address: reserve.address.to_string(),
+                        amount,
+                        amount_raw: debt.to_string(),
+                        rate_mode,
+                        value_in_usd: amount.saturating_mul(*price),
+                        usage_as_collateral_enabled: false,
+                    });
+                }
             }
         }
 
+        // A user is in isolation mode when its only enabled collateral is an isolated reserve
+        let is_in_isolation_mode = matches!(
+            enabled_collateral_reserves.as_slice(),
+            [reserve] if reserve.configuration.is_isolated()
+        );
+
         Ok(models::UserReserveData::new(
             collateral_positions,
             debt_positions,
+            reserves,
+            emode_category,
+            is_in_isolation_mode,
+            has_siloed_borrowing,
         ))
     }
 
@@ -265,7 +551,10 @@ impl UserHelper {
     ///
     /// # Returns
     /// * `UserCurrentLocation` - User's risk category (Healthy, AtRisk, or Liquidatable)
-    fn get_user_new_location(health_factor: f64, at_risk_threshold: f64) -> UserCurrentLocation {
+    fn get_user_new_location(
+        health_factor: Decimal,
+        at_risk_threshold: Decimal,
+    ) -> UserCurrentLocation {
         if health_factor < LIQUIDATION_THRESHOLD {
             UserCurrentLocation::Liquidatable
         } else if health_factor <= at_risk_threshold {
@@ -275,104 +564,94 @@ impl UserHelper {
         }
     }
 
+    /// Builds the user details to store from the user's fresh account state
+    ///
+    /// # Arguments
+    /// * `block_number` - Current block number
+    /// * `account_state` - User's account state decoded from the blockchain
+    /// * `user_details` - Optional existing user details from database
+    ///
+    /// # Returns
+    /// * `UserDetails` - Updated user details, keeping the id and location of the existing row
+    fn build_user_details(
+        block_number: u64,
+        account_state: &models::UserAccountState,
+        user_details: Option<UserDetails>,
+    ) -> UserDetails {
+        let (id, current_location) = match user_details {
+            Some(user) => (user.id, user.current_location),
+            None => (0, UserCurrentLocation::NotFound),
+        };
+        let user_reserve_data = &account_state.user_reserve_data;
+
+        UserDetails {
+            id,
+            user_address: account_state.user_address.clone(),
+            last_updated_block_number: block_number as i32,
+            health_factor: account_state.health_factor,
+            total_collateral_value_in_usd: account_state.total_collateral_value_in_usd,
+            total_debt_value_in_usd: account_state.total_debt_value_in_usd,
+            emode_category: account_state.emode_category as i32,
+            is_in_isolation_mode: user_reserve_data.is_in_isolation_mode,
+            has_siloed_borrowing: user_reserve_data.has_siloed_borrowing,
+            leading_collateral_reserve: user_reserve_data.leading_collateral_reserve.clone(),
+            leading_debt_reserve: user_reserve_data.leading_debt_reserve.clone(),
+            leading_collateral_reserve_value: user_reserve_data.leading_collateral_reserve_value,
+            leading_debt_reserve_value: user_reserve_data.leading_debt_reserve_value,
+            projected_health_factor: account_state.health_factor,
+            timestamp: Utc::now(),
+            current_location,
+        }
+    }
+
     /// Adds or updates a user's basic information in the database
     ///
     /// # Arguments
-    /// * `db` - Database connection handle
+    /// * `db` - Database connection handle or transaction
     /// * `local_config` - Local configuration settings
-    /// * `user_address` - Ethereum address of the user
     /// * `block_number` - Current block number
-    /// * `health_factor` - User's current health factor
-    /// * `total_collateral_value_in_usd` - Total USD value of user's collateral
-    /// * `total_debt_value_in_usd` - Total USD value of user's debt
-    /// * `user_reserve_data` - Detailed data about user's positions in different reserves
+    /// * `account_state` - User's account state decoded from the blockchain
     /// * `user_details` - Optional existing user details from database
     ///
     /// # Returns
-    /// * `Result<()>` - Success or error result of the database operation
+    /// * `Result<Option<UserEvent>>` - The change to publish once the write is committed, if any
     async fn add_or_update_user_to_db(
-        db: &DatabaseConnection,
+        db: &impl ConnectionTrait,
         local_config: &LocalConfig,
-        user_address: &str,
         block_number: u64,
-        health_factor: f64,
-        total_collateral_value_in_usd: f64,
-        total_debt_value_in_usd: f64,
-        user_reserve_data: models::UserReserveData,
+        account_state: &models::UserAccountState,
         user_details: Option<users_tables_helper::UserDetails>,
-    ) -> Result<()> {
-        let user_old_location = match user_details.as_ref() {
-            Some(user_details) => user_details.current_location.clone(),
-            None => users_tables_helper::UserCurrentLocation::NotFound,
-        };
+    ) -> Result<Option<UserEvent>> {
+        let user_address = &account_state.user_address;
+        let health_factor = account_state.health_factor;
+        let old_health_factor = user_details.as_ref().map(|user| user.health_factor);
+
+        let user_details = Self::build_user_details(block_number, account_state, user_details);
+        let user_old_location = user_details.current_location.clone();
 
         let new_location =
             Self::get_user_new_location(health_factor, local_config.at_risk_health_factor);
 
-        // If user location has changed, update the user location or in case of not found, add the user to the database
-        if user_old_location != new_location {
-            let need_deletion = match user_old_location {
-                users_tables_helper::UserCurrentLocation::NotFound => false,
-                _ => true,
-            };
-
-            let user_details = match user_details {
-                Some(user) => {
-                    let mut user = user;
-                    user.health_factor = health_factor as f32;
-                    user.last_updated_block_number = block_number as i32;
-                    user.total_collateral_value_in_usd = total_collateral_value_in_usd as f32;
-                    user.total_debt_value_in_usd = total_debt_value_in_usd as f32;
-                    user.leading_collateral_reserve = user_reserve_data.leading_collateral_reserve;
-                    user.leading_debt_reserve = user_reserve_data.leading_debt_reserve;
-                    user.leading_collateral_reserve_value =
-                        user_reserve_data.leading_collateral_reserve_token_value;
-                    user.leading_debt_reserve_value =
-                        user_reserve_data.leading_debt_reserve_token_value;
-                    user.timestamp = Utc::now();
-                    user
-                }
-                None => UserDetails {
-                    id: 0,
-                    user_address: user_address.to_string(),
-                    last_updated_block_number: block_number as i32,
-                    health_factor: health_factor as f32,
-                    total_collateral_value_in_usd: total_collateral_value_in_usd as f32,
-                    total_debt_value_in_usd: total_debt_value_in_usd as f32,
-                    leading_collateral_reserve: user_reserve_data.leading_collateral_reserve,
-                    leading_debt_reserve: user_reserve_data.leading_debt_reserve,
-                    leading_collateral_reserve_value: user_reserve_data
-                        .leading_collateral_reserve_token_value,
-                    leading_debt_reserve_value: user_reserve_data.leading_debt_reserve_token_value,
-                    timestamp: Utc::now(),
-                    current_location: user_old_location.clone(),
-                },
-            };
-
-            if need_deletion {
-                // Delete the user from the database
-                users_tables_helper::delete_user(db, user_details.id, user_old_location.clone())
-                    .await
-                    .context("Failed to delete user from the database")?;
-            }
+        let user_event = Self::build_user_event(
+            local_config,
+            block_number,
+            account_state,
+            &user_old_location,
+            old_health_factor,
+            &new_location,
+        );
 
-            // Add the user to the database
+        // A new user is added, an existing one is updated in place even when its tier changes
+        if user_old_location == UserCurrentLocation::NotFound {
             users_tables_helper::add_user(db, user_details, new_location.clone())
                 .await
                 .context("Failed to add user to the database")?;
 
             info!(
-                "Moved user [HF: {}] {} from {:?} to {:?}",
-                health_factor, user_address, user_old_location, new_location
+                "Added user [HF: {}] {} to {:?}",
+                health_factor, user_address, new_location
             );
-        }
-        // Update the user at the same location
-        else {
-            info!(
-                "User [HF: {}] {} is at {:?}, updating user",
-                health_factor, user_address, user_old_location
-            );
-            let user_details = user_details.unwrap();
+        } else {
             users_tables_helper::update_user(
                 db,
                 user_details.id,
@@ -381,26 +660,91 @@ impl UserHelper {
             )
             .await
             .context("Failed to update user in the database")?;
+
+            if user_old_location != new_location {
+                info!(
+                    "Moved user [HF: {}] {} from {:?} to {:?}",
+                    health_factor, user_address, user_old_location, new_location
+                );
+            } else {
+                info!(
+                    "User [HF: {}] {} is at {:?}, updated user",
+                    health_factor, user_address, user_old_location
+                );
+            }
         }
 
-        Ok(())
+        Ok(user_event)
+    }
+
+    /// Builds the event published for a refreshed user, if its tier changed or its
+    /// health factor moved by more than `HEALTH_FACTOR_CHANGE_THRESHOLD` percent
+    ///
+    /// # Arguments
+    /// * `local_config` - Local configuration settings
+    /// * `block_number` - Block number the user was refreshed at
+    /// * `account_state` - User's account state decoded from the blockchain
+    /// * `old_location` - Tier of the user before the refresh
+    /// * `old_health_factor` - Health factor of the user before the refresh
+    /// * `new_location` - Tier of the user after the refresh
+    ///
+    /// # Returns
+    /// * `Option<UserEvent>` - The event, `None` if nothing worth publishing changed
+    fn build_user_event(
+        local_config: &LocalConfig,
+        block_number: u64,
+        account_state: &models::UserAccountState,
+        old_location: &UserCurrentLocation,
+        old_health_factor: Option<Decimal>,
+        new_location: &UserCurrentLocation,
+    ) -> Option<UserEvent> {
+        let kind = UserEvent::kind_of_change(
+            local_config.health_factor_change_threshold,
+            old_location,
+            old_health_factor,
+            new_location,
+            account_state.health_factor,
+        )?;
+
+        let user_reserve_data = &account_state.user_reserve_data;
+        let mut reserves = user_reserve_data
+            .collateral_assets
+            .iter()
+            .chain(user_reserve_data.debt_assets.iter())
+            .map(|position| position.reserve_address.clone())
+            .collect::<Vec<_>>();
+        reserves.sort();
+        reserves.dedup();
+
+        Some(UserEvent {
+            kind,
+            user_address: account_state.user_address.clone(),
+            old_location: old_location.clone(),
+            new_location: new_location.clone(),
+            old_health_factor,
+            health_factor: account_state.health_factor,
+            total_debt_value_in_usd: account_state.total_debt_value_in_usd,
+            reserves,
+            block_number,
+            timestamp: Utc::now(),
+        })
     }
 
     /// Updates or adds a user's detailed collateral and debt positions in the database
     ///
     /// # Arguments
-    /// * `db` - Database connection handle
+    /// * `db` - Database connection handle or transaction
     /// * `user_address` - Ethereum address of the user
-    /// * `collateral_assets` - Vector of (asset_address, amount) pairs for collateral
-    /// * `debt_assets` - Vector of (asset_address, amount) pairs for debt
+    /// * `collateral_assets` - Collateral positions of the user
+    /// * `debt_assets` - Debt positions of the user
     ///
     /// # Returns
     /// * `Result<()>` - Success or error result of the database operation
     async fn add_or_update_user_debt_collateral(
-        db: &DatabaseConnection,
+        db: &impl ConnectionTrait,
         user_address: &str,
-        collateral_assets: Vec<(String, f32)>,
-        debt_assets: Vec<(String, f32)>,
+        collateral_assets: Vec<UserPosition>,
+        debt_assets: Vec<UserPosition>,
     ) -> Result<()> {
         user_debt_collateral_helper::add_or_update_user_debt_collateral(
             db,
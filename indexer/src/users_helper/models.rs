@@ -1,41 +1,110 @@
+use indexer_database::{
+    liquidation_opportunities_helper::LiquidationOpportunity,
+    user_debt_collateral_helper::UserPosition,
+};
+use rust_decimal::Decimal;
+
+use crate::reserves_helper::AaveReserve;
+
 #[derive(Debug, Clone)]
 pub struct UserReserveData {
     pub leading_collateral_reserve: String,
     pub leading_debt_reserve: String,
-    pub leading_collateral_reserve_token_value: f32,
-    pub leading_debt_reserve_token_value: f32,
-    pub collateral_assets: Vec<(String, f32)>,
-    pub debt_assets: Vec<(String, f32)>,
+    pub leading_collateral_reserve_value: Decimal,
+    pub leading_debt_reserve_value: Decimal,
+    pub collateral_assets: Vec<UserPosition>,
+    pub debt_assets: Vec<UserPosition>,
+    /// Whether the user's only enabled collateral is an isolated reserve
+    pub is_in_isolation_mode: bool,
+    /// Whether the user borrows a siloed reserve
+    pub has_siloed_borrowing: bool,
 }
 
 impl UserReserveData {
-    pub fn new(collateral_assets: Vec<(String, f32)>, debt_assets: Vec<(String, f32)>) -> Self {
+    pub fn new(
+        collateral_assets: Vec<UserPosition>,
+        debt_assets: Vec<UserPosition>,
+        aave_reserves: &[AaveReserve],
+        emode_category: u8,
+        is_in_isolation_mode: bool,
+        has_siloed_borrowing: bool,
+    ) -> Self {
         let mut leading_collateral_reserve = String::new();
         let mut leading_debt_reserve = String::new();
-        let mut leading_collateral_reserve_token_value = 0.0;
-        let mut leading_debt_reserve_token_value = 0.0;
+        let mut leading_collateral_reserve_value = Decimal::ZERO;
+        let mut leading_debt_reserve_value = Decimal::ZERO;
+
+        // Collateral reserves are compared by their weight in the health factor, their USD
+        // value times the liquidation threshold that applies to the user (e-mode included).
+        // Collateral the user disabled doesn't count in the health factor
+        let mut leading_collateral_weight = Decimal::ZERO;
+        for position in collateral_assets
+            .iter()
+            .filter(|position| position.usage_as_collateral_enabled)
+        {
+            let liquidation_threshold = aave_reserves
+                .iter()
+                .find(|reserve| reserve.address.to_string() == position.reserve_address)
+                .map(|reserve| reserve.liquidation_parameters(emode_category).0)
+                .unwrap_or_default();
+            let weight = position.value_in_usd.saturating_mul(liquidation_threshold);
+
+            if weight > leading_collateral_weight {
+                leading_collateral_reserve = position.reserve_address.clone();
+                leading_collateral_reserve_value = position.value_in_usd;
+                leading_collateral_weight = weight;
+            }
+        }
 
-        for (address, value) in collateral_assets.iter() {
-            if value > &leading_collateral_reserve_token_value {
-                leading_collateral_reserve = address.clone();
-                leading_collateral_reserve_token_value = *value;
+        // Stable and variable debt in the same reserve are repaid as a single asset
+        let mut debt_per_reserve: Vec<(&String, Decimal)> = Vec::new();
+        for position in debt_assets.iter() {
+            match debt_per_reserve
+                .iter_mut()
+                .find(|(reserve_address, _)| **reserve_address == position.reserve_address)
+            {
+                Some((_, value)) => *value = value.saturating_add(position.value_in_usd),
+                None => debt_per_reserve.push((&position.reserve_address, position.value_in_usd)),
             }
         }
 
-        for (address, value) in debt_assets.iter() {
-            if value > &leading_debt_reserve_token_value {
-                leading_debt_reserve = address.clone();
-                leading_debt_reserve_token_value = *value;
+        for (reserve_address, value) in debt_per_reserve {
+            if value > leading_debt_reserve_value {
+                leading_debt_reserve = reserve_address.clone();
+                leading_debt_reserve_value = value;
             }
         }
 
         Self {
             leading_collateral_reserve,
             leading_debt_reserve,
-            leading_collateral_reserve_token_value,
-            leading_debt_reserve_token_value,
+            leading_collateral_reserve_value,
+            leading_debt_reserve_value,
             collateral_assets,
             debt_assets,
+            is_in_isolation_mode,
+            has_siloed_borrowing,
         }
     }
 }
+
+/// A user's account state decoded from a multicall at a given block
+#[derive(Debug, Clone)]
+pub struct UserAccountState {
+    pub user_address: String,
+    pub health_factor: Decimal,
+    pub total_collateral_value_in_usd: Decimal,
+    pub total_debt_value_in_usd: Decimal,
+    /// E-mode category of the user, 0 when not in e-mode
+    pub emode_category: u8,
+    pub user_reserve_data: UserReserveData,
+    /// Most profitable liquidation of the user, only set for liquidatable users
+    pub liquidation: Option<LiquidationOpportunity>,
+}
+
+/// A user that could not be refreshed, with the reason why
+#[derive(Debug, Clone)]
+pub struct UserUpdateFailure {
+    pub user_address: String,
+    pub error: String,
+}
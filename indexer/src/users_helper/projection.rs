@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use indexer_database::{
+    entities::user_debt_collateral, user_debt_collateral_helper, users_tables_helper,
+};
+use rust_decimal::Decimal;
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use tracing::info;
+
+use super::UserHelper;
+use crate::{config::LocalConfig, metrics::METRICS, reserves_helper::AaveReserve};
+
+impl UserHelper {
+    /// Projects the health factor of many users from their stored positions and the
+    /// given prices, stores the projections and returns the users to refresh on-chain
+    ///
+    /// Querying `getUserAccountData` for every user on each price change is expensive,
+    /// so the health factor is estimated locally from `user_debt_collateral`, the
+    /// reserve prices and the liquidation thresholds that apply to each user (e-mode
+    /// included). Users whose projected tier differs from their stored tier are
+    /// escalated for a real refresh. Users that can't be projected, because a reserve
+    /// of theirs has no known price or isn't listed anymore, keep their last projection.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `prices` - Price of each reserve in USD, keyed by reserve address
+    /// * `user_addresses` - Ethereum addresses of the users to project
+    ///
+    /// # Returns
+    /// * `Result<Vec<String>>` - Users whose projected health factor crossed a tier boundary
+    pub async fn project_health_factors(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        aave_reserves: &[AaveReserve],
+        prices: &HashMap<String, Decimal>,
+        user_addresses: &[String],
+    ) -> Result<Vec<String>> {
+        let users = users_tables_helper::get_users(db, user_addresses).await?;
+
+        let mut positions_per_user: HashMap<String, Vec<user_debt_collateral::Model>> =
+            HashMap::new();
+        for position in user_debt_collateral_helper::get_users_positions(db, user_addresses).await?
+        {
+            positions_per_user
+                .entry(position.user_address.clone())
+                .or_default()
+                .push(position);
+        }
+
+        let max_health_factor = Decimal::from(local_config.max_cap_on_health_factor);
+        let mut projected_health_factors = Vec::new();
+        let mut escalated_users = Vec::new();
+
+        for user in users {
+            let positions = positions_per_user
+                .get(&user.user_address)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let emode_category = user.emode_category as u8;
+
+            let Some(projected_health_factor) = Self::project_health_factor(
+                user.health_factor,
+                positions,
+                prices,
+                |reserve_address| {
+                    aave_reserves
+                        .iter()
+                        .find(|reserve| reserve.address.to_string() == reserve_address)
+                        .map(|reserve| reserve.liquidation_parameters(emode_category).0)
+                },
+                max_health_factor,
+            ) else {
+                continue;
+            };
+
+            let projected_location = Self::get_user_new_location(
+                projected_health_factor,
+                local_config.at_risk_health_factor,
+            );
+            if projected_location != user.current_location {
+                info!(
+                    "Projected HF of user {} is {}, escalating from {:?} to {:?}",
+                    user.user_address,
+                    projected_health_factor,
+                    user.current_location,
+                    projected_location
+                );
+                escalated_users.push(user.user_address.clone());
+            }
+
+            projected_health_factors.push((user.user_address, projected_health_factor));
+        }
+
+        let timer = METRICS
+            .db_write_duration
+            .with_label_values(&["projected_health_factors"])
+            .start_timer();
+        let txn = db.begin().await?;
+        users_tables_helper::update_projected_health_factors(&txn, projected_health_factors)
+            .await
+            .context("Failed to store projected health factors")?;
+        txn.commit().await?;
+        timer.observe_duration();
+
+        Ok(escalated_users)
+    }
+
+    /// Estimates a user's health factor at the given prices from their positions
+    ///
+    /// The Aave pool computes the health factor as the sum of the collateral values
+    /// weighted by their liquidation threshold, divided by the sum of the debt values.
+    /// Rather than recomputing it from scratch, the on-chain health factor is scaled by
+    /// how much that ratio changed between the values stored at the last refresh and
+    /// the values at the given prices. Collateral the user disabled is left out, and
+    /// positions stored without a value fall back to the ratio at the given prices.
+    ///
+    /// # Arguments
+    /// * `health_factor` - User's on-chain health factor at the last refresh
+    /// * `positions` - Collateral and debt positions of the user
+    /// * `prices` - Price of each reserve in USD, keyed by reserve address
+    /// * `liquidation_threshold` - Liquidation threshold that applies to the user for a reserve
+    /// * `max_health_factor` - Maximum allowed health factor value
+    ///
+    /// # Returns
+    /// * `Option<Decimal>` - The projected health factor, `None` if a reserve of the user
+    ///   has no known price or liquidation threshold
+    pub fn project_health_factor(
+        health_factor: Decimal,
+        positions: &[user_debt_collateral::Model],
+        prices: &HashMap<String, Decimal>,
+        liquidation_threshold: impl Fn(&str) -> Option<Decimal>,
+        max_health_factor: Decimal,
+    ) -> Option<Decimal> {
+        let (weighted_collateral, debt) = Self::weighted_collateral_and_debt(
+            positions,
+            |position| {
+                prices
+                    .get(&position.reserve_address)
+                    .copied()
+                    .filter(|price| !price.is_zero())
+                    .map(|price| position.amount.saturating_mul(price))
+            },
+            &liquidation_threshold,
+        )?;
+
+        if debt.is_zero() {
+            return Some(max_health_factor);
+        }
+        let ratio = weighted_collateral.checked_div(debt)?;
+
+        let stored_ratio = Self::weighted_collateral_and_debt(
+            positions,
+            |position| position.value_in_usd,
+            &liquidation_threshold,
+        )
+        .and_then(|(weighted_collateral, debt)| weighted_collateral.checked_div(debt))
+        .filter(|stored_ratio| !stored_ratio.is_zero());
+
+        let projected_health_factor = match stored_ratio {
+            // A capped health factor can't be scaled, its real value is unknown
+            Some(stored_ratio) if health_factor < max_health_factor => health_factor
+                .saturating_mul(ratio)
+                .checked_div(stored_ratio)
+                .unwrap_or(max_health_factor),
+            _ => ratio,
+        };
+
+        Some(projected_health_factor.min(max_health_factor))
+    }
+
+    /// Sums the collateral values weighted by their liquidation threshold and the debt
+    /// values of a user's positions, skipping the collateral the user disabled
+    ///
+    /// # Arguments
+    /// * `positions` - Collateral and debt positions of the user
+    /// * `value` - USD value of a position
+    /// * `liquidation_threshold` - Liquidation threshold that applies to the user for a reserve
+    ///
+    /// # Returns
+    /// * `Option<(Decimal, Decimal)>` - Tuple containing (weighted_collateral, debt), `None`
+    ///   if a position has no value or liquidation threshold
+    fn weighted_collateral_and_debt(
+        positions: &[user_debt_collateral::Model],
+        value: impl Fn(&user_debt_collateral::Model) -> Option<Decimal>,
+        liquidation_threshold: &impl Fn(&str) -> Option<Decimal>,
+    ) -> Option<(Decimal, Decimal)> {
+        let mut weighted_collateral = Decimal::ZERO;
+        let mut debt = Decimal::ZERO;
+
+        for position in positions {
+            if position.is_collateral && !position.usage_as_collateral_enabled {
+                continue;
+            }
+            let value = value(position)?;
+
+            if position.is_collateral {
+                let liquidation_threshold = liquidation_threshold(&position.reserve_address)?;
+                weighted_collateral =
+                    weighted_collateral.saturating_add(value.saturating_mul(liquidation_threshold));
+            } else {
+                debt = debt.saturating_add(value);
+            }
+        }
+
+        Some((weighted_collateral, debt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indexer_database::entities::sea_orm_active_enums::RateMode;
+
+    use super::*;
+
+    const COLLATERAL: &str = "0x0101010101010101010101010101010101010101";
+    const DEBT: &str = "0x0303030303030303030303030303030303030303";
+
+    fn position(
+        reserve_address: &str,
+        amount: Decimal,
+        value_in_usd: Option<Decimal>,
+        is_collateral: bool,
+        usage_as_collateral_enabled: bool,
+    ) -> user_debt_collateral::Model {
+        user_debt_collateral::Model {
+            id: 0,
+            user_address: String::new(),
+            reserve_address: reserve_address.to_string(),
+            amount,
+            is_collateral,
+            timestamp: Default::default(),
+            amount_raw: None,
+            rate_mode: if is_collateral {
+                RateMode::None
+            } else {
+                RateMode::Variable
+            },
+            value_in_usd,
+            usage_as_collateral_enabled,
+        }
+    }
+
+    /// 100 collateral tokens stored at $10 and a debt of $500, a stored ratio of 1.6
+    fn positions() -> Vec<user_debt_collateral::Model> {
+        vec![
+            position(
+                COLLATERAL,
+                Decimal::from(100),
+                Some(Decimal::from(1_000)),
+                true,
+                true,
+            ),
+            position(
+                DEBT,
+                Decimal::from(500),
+                Some(Decimal::from(500)),
+                false,
+                false,
+            ),
+        ]
+    }
+
+    fn prices(collateral_price: Decimal) -> HashMap<String, Decimal> {
+        HashMap::from([
+            (COLLATERAL.to_string(), collateral_price),
+            (DEBT.to_string(), Decimal::ONE),
+        ])
+    }
+
+    fn project(
+        health_factor: Decimal,
+        positions: &[user_debt_collateral::Model],
+        prices: &HashMap<String, Decimal>,
+    ) -> Option<Decimal> {
+        UserHelper::project_health_factor(
+            health_factor,
+            positions,
+            prices,
+            |_| Some(Decimal::new(8, 1)),
+            Decimal::from(100),
+        )
+    }
+
+    #[test]
+    fn scales_the_health_factor_by_the_ratio_change() {
+        // The ratio halves from 1.6 to 0.8
+        assert_eq!(
+            project(Decimal::new(15, 1), &positions(), &prices(Decimal::from(5))),
+            Some(Decimal::new(75, 2))
+        );
+    }
+
+    #[test]
+    fn unchanged_prices_keep_the_health_factor() {
+        assert_eq!(
+            project(
+                Decimal::new(15, 1),
+                &positions(),
+                &prices(Decimal::from(10))
+            ),
+            Some(Decimal::new(15, 1))
+        );
+    }
+
+    #[test]
+    fn capped_health_factors_use_the_ratio_at_the_given_prices() {
+        assert_eq!(
+            project(Decimal::from(100), &positions(), &prices(Decimal::from(5))),
+            Some(Decimal::new(8, 1))
+        );
+    }
+
+    #[test]
+    fn positions_without_a_stored_value_use_the_ratio_at_the_given_prices() {
+        let mut positions = positions();
+        positions[0].value_in_usd = None;
+
+        assert_eq!(
+            project(Decimal::new(15, 1), &positions, &prices(Decimal::from(5))),
+            Some(Decimal::new(8, 1))
+        );
+    }
+
+    #[test]
+    fn users_without_debt_get_the_maximum_health_factor() {
+        assert_eq!(
+            project(
+                Decimal::new(15, 1),
+                &positions()[..1],
+                &prices(Decimal::from(5))
+            ),
+            Some(Decimal::from(100))
+        );
+    }
+
+    #[test]
+    fn projected_health_factors_are_capped() {
+        assert_eq!(
+            project(
+                Decimal::new(15, 1),
+                &positions(),
+                &prices(Decimal::from(1_000_000))
+            ),
+            Some(Decimal::from(100))
+        );
+    }
+
+    #[test]
+    fn zero_or_missing_prices_cant_be_projected() {
+        assert_eq!(
+            project(Decimal::new(15, 1), &positions(), &prices(Decimal::ZERO)),
+            None
+        );
+
+        let mut prices = prices(Decimal::from(5));
+        prices.remove(COLLATERAL);
+        assert_eq!(project(Decimal::new(15, 1), &positions(), &prices), None);
+    }
+
+    #[test]
+    fn reserves_without_a_liquidation_threshold_cant_be_projected() {
+        assert_eq!(
+            UserHelper::project_health_factor(
+                Decimal::new(15, 1),
+                &positions(),
+                &prices(Decimal::from(5)),
+                |_| None,
+                Decimal::from(100),
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn disabled_collateral_is_left_out() {
+        let mut positions = positions();
+        positions.push(position(
+            COLLATERAL,
+            Decimal::from(1_000),
+            Some(Decimal::from(10_000)),
+            true,
+            false,
+        ));
+
+        assert_eq!(
+            project(Decimal::new(15, 1), &positions, &prices(Decimal::from(5))),
+            Some(Decimal::new(75, 2))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::{collections::HashSet, sync::Arc};
+
+use alloy::{network::Ethereum, providers::Provider};
+use anyhow::Result;
+use futures::{StreamExt, TryStreamExt};
+use indexer_database::{backfill_helper, entities::backfill_ranges, last_index_block_helper};
+use sea_orm::DatabaseConnection;
+use tracing::{error, info, instrument};
+
+use super::UsersIndexer;
+use crate::{
+    blockchain_manager::{multicall::MulticallManager, AaveHelperContract},
+    config::LocalConfig,
+    metrics::METRICS,
+    reserves_helper::AaveReserve,
+    user_events::UserEvents,
+    users_helper::UserHelper,
+};
+
+impl UsersIndexer {
+    /// Runs the historical backfill when one is in progress or the indexer is far behind the chain
+    ///
+    /// The range between the last indexed block and the sync point is split into
+    /// `LOG_PER_REQUEST` windows fetched by `BACKFILL_WORKERS` concurrent workers. The
+    /// discovered users are deduplicated and refreshed once at the sync point, in
+    /// multicall-sized batches.
+    /// Progress is stored per range and per user, so a restart resumes where it stopped.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `provider` - Blockchain provider
+    /// * `local_config` - Local configuration
+    /// * `aave_helper_contracts` - Aave helper contracts
+    /// * `aave_reserves` - Aave reserves
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<()>` - A result of the operation
+    #[instrument("BACKFILL", skip_all)]
+    pub(super) async fn run_backfill_if_needed<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        provider: &'a P,
+        local_config: &LocalConfig,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        multicall_manager: &mut MulticallManager<&'a P>,
+        user_events: &UserEvents,
+    ) -> Result<()> {
+        if local_config.backfill_workers == 0 {
+            return Ok(());
+        }
+
+        let mut ranges = backfill_helper::get_backfill_ranges(db).await?;
+
+        if ranges.is_empty() {
+            let from_block = last_index_block_helper::get_last_index_block(db)
+                .await?
+                .block_number as u64;
+            // Stay out of the reorg window, the regular loop indexes it with hash tracking
+            let sync_block = METRICS
+                .observe_rpc("eth_blockNumber", provider.get_block_number().await)?
+                .saturating_sub(local_config.reorg_confirmation_window);
+
+            // Close enough to the head, the regular loop will catch up on its own
+            if sync_block <= from_block + local_config.log_per_request {
+                return Ok(());
+            }
+
+            backfill_helper::create_backfill_ranges(
+                db,
+                Self::split_backfill_ranges(from_block, sync_block, local_config.log_per_request),
+                sync_block,
+            )
+            .await?;
+            ranges = backfill_helper::get_backfill_ranges(db).await?;
+        }
+
+        let sync_block = ranges[0].sync_block as u64;
+        let pending_ranges = ranges
+            .into_iter()
+            .filter(|range| !range.is_completed)
+            .collect::<Vec<_>>();
+
+        info!(
+            "Backfilling up to block {} | Pending ranges: {} | Workers: {}",
+            sync_block,
+            pending_ranges.len(),
+            local_config.backfill_workers
+        );
+
+        futures::stream::iter(pending_ranges)
+            .map(|range| Self::backfill_range(db, provider, local_config, range))
+            .buffer_unordered(local_config.backfill_workers as usize)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        let users = backfill_helper::get_unrefreshed_backfill_users(db).await?;
+        info!(
+            "Refreshing {} backfilled users at block {}",
+            users.len(),
+            sync_block
+        );
+
+        // Refresh in multicall-sized batches, storing the progress after each one
+        let users_per_batch = UserHelper::users_per_multicall(local_config, aave_reserves.len());
+        let mut failed_users = 0;
+        for batch in users.chunks(users_per_batch) {
+            let failures = UserHelper::force_update_users_batch(
+                db,
+                local_config,
+                batch,
+                sync_block,
+                aave_helper_contracts,
+                aave_reserves,
+                multicall_manager,
+                user_events,
+            )
+            .await?;
+
+            for failure in failures.iter() {
+                error!(
+                    "Failed to update user {}: {}",
+                    failure.user_address, failure.error
+                );
+            }
+
+            let failed = failures
+                .iter()
+                .map(|failure| failure.user_address.as_str())
+                .collect::<HashSet<_>>();
+            let refreshed = batch
+                .iter()
+                .filter(|user_address| !failed.contains(user_address.as_str()))
+                .cloned()
+                .collect::<Vec<_>>();
+            backfill_helper::mark_backfill_users_refreshed(db, &refreshed).await?;
+            failed_users += failures.len();
+        }
+
+        // Leave the backfill in progress, the next run retries the failed users only
+        if failed_users > 0 {
+            return Err(anyhow::anyhow!(
+                "Failed to refresh {} backfilled users",
+                failed_users
+            ));
+        }
+
+        let last_index_block = last_index_block_helper::get_last_index_block(db).await?;
+        last_index_block_helper::update_last_index_block(db, last_index_block, sync_block).await?;
+        backfill_helper::clear_backfill(db).await?;
+
+        info!("Backfill completed at block {}", sync_block);
+
+        Ok(())
+    }
+
+    /// Fetches the logs of a single backfill range and stores the users it discovered
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `provider` - Blockchain provider
+    /// * `local_config` - Local configuration
+    /// * `range` - The backfill range to process
+    ///
+    /// # Returns
+    /// * `Result<()>` - A result of the operation
+    async fn backfill_range(
+        db: &DatabaseConnection,
+        provider: &impl Provider,
+        local_config: &LocalConfig,
+        range: backfill_ranges::Model,
+    ) -> Result<()> {
+        let logs = Self::fetch_logs(
+            provider,
+            local_config,
+            range.from_block as u64,
+            range.to_block as u64,
+        )
+        .await?;
+
+        let users = Self::process_pool_events(&logs)?
+            .into_iter()
+            .map(|(user, _)| user.to_string())
+            .collect::<Vec<_>>();
+
+        info!(
+            "Backfilled blocks {} - {} | Users found: {}",
+            range.from_block,
+            range.to_block,
+            users.len()
+        );
+
+        backfill_helper::complete_backfill_range(db, range, users).await
+    }
+
+    /// Splits a block range into consecutive, non-overlapping inclusive windows
+    ///
+    /// # Arguments
+    /// * `from_block` - First block of the range
+    /// * `to_block` - Last block of the range
+    /// * `blocks_per_range` - Maximum number of blocks per window, minus one
+    ///
+    /// # Returns
+    /// * `Vec<(u64, u64)>` - Vector of (from_block, to_block) windows
+    fn split_backfill_ranges(
+        from_block: u64,
+        to_block: u64,
+        blocks_per_range: u64,
+    ) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let mut range_start = from_block;
+
+        while range_start <= to_block {
+            let range_end = (range_start + blocks_per_range).min(to_block);
+            ranges.push((range_start, range_end));
+            range_start = range_end + 1;
+        }
+
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_backfill_ranges_covers_the_range_without_overlap() {
+        assert_eq!(
+            UsersIndexer::split_backfill_ranges(100, 350, 99),
+            vec![(100, 199), (200, 299), (300, 350)]
+        );
+    }
+
+    #[test]
+    fn split_backfill_ranges_of_an_exact_multiple() {
+        assert_eq!(
+            UsersIndexer::split_backfill_ranges(0, 199, 99),
+            vec![(0, 99), (100, 199)]
+        );
+    }
+
+    #[test]
+    fn split_backfill_ranges_of_a_single_block() {
+        assert_eq!(
+            UsersIndexer::split_backfill_ranges(42, 42, 99),
+            vec![(42, 42)]
+        );
+    }
+
+    #[test]
+    fn split_backfill_ranges_of_an_empty_range() {
+        assert!(UsersIndexer::split_backfill_ranges(43, 42, 99).is_empty());
+    }
+
+    #[test]
+    fn split_backfill_ranges_with_one_block_per_range() {
+        assert_eq!(
+            UsersIndexer::split_backfill_ranges(1, 3, 0),
+            vec![(1, 1), (2, 2), (3, 3)]
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use alloy::{
+    primitives::{Address, B256},
+    sol_types::SolEvent,
+};
+
+use crate::utils::contracts::AavePoolContract::{self, AavePoolContractEvents as PoolEvent};
+
+/// Returns the topic0 of every Aave Pool event that can change a user's position
+///
+/// # Returns
+/// * `Vec<B256>` - Event signature hashes used to filter the pool logs
+pub fn position_changing_event_signatures() -> Vec<B256> {
+    vec![
+        AavePoolContract::Supply::SIGNATURE_HASH,
+        AavePoolContract::Withdraw::SIGNATURE_HASH,
+        AavePoolContract::Borrow::SIGNATURE_HASH,
+        AavePoolContract::Repay::SIGNATURE_HASH,
+        AavePoolContract::LiquidationCall::SIGNATURE_HASH,
+        AavePoolContract::ReserveUsedAsCollateralEnabled::SIGNATURE_HASH,
+        AavePoolContract::ReserveUsedAsCollateralDisabled::SIGNATURE_HASH,
+        AavePoolContract::UserEModeSet::SIGNATURE_HASH,
+    ]
+}
+
+/// Maps a decoded Aave Pool event to the users whose positions it affects
+///
+/// # Arguments
+/// * `event` - Decoded Aave Pool event
+///
+/// # Returns
+/// * `Vec<Address>` - Affected user addresses, empty for events that don't change positions
+pub fn affected_users(event: &PoolEvent) -> Vec<Address> {
+    match event {
+        PoolEvent::Supply(event) => with_on_behalf_of(event.user, event.onBehalfOf),
+        PoolEvent::Borrow(event) => with_on_behalf_of(event.user, event.onBehalfOf),
+        PoolEvent::Withdraw(event) => vec![event.user],
+        PoolEvent::Repay(event) => vec![event.user],
+        // Both the liquidated user and the liquidator (who may receive aTokens) change
+        PoolEvent::LiquidationCall(event) => with_on_behalf_of(event.liquidator, event.user),
+        PoolEvent::ReserveUsedAsCollateralEnabled(event) => vec![event.user],
+        PoolEvent::ReserveUsedAsCollateralDisabled(event) => vec![event.user],
+        PoolEvent::UserEModeSet(event) => vec![event.user],
+        _ => vec![],
+    }
+}
+
+/// Returns the sender and, if it differs, the account the action was made on behalf of
+fn with_on_behalf_of(sender: Address, on_behalf_of: Address) -> Vec<Address> {
+    if sender == on_behalf_of {
+        vec![sender]
+    } else {
+        vec![sender, on_behalf_of]
+    }
+}
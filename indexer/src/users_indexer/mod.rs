@@ -1,10 +1,11 @@
-use std::sync::Arc;
+mod backfill;
+mod events;
+mod reorg;
+
+use std::{collections::HashMap, sync::Arc};
 
 use alloy::{
-    network::Ethereum,
-    primitives::{b256, Address},
-    providers::Provider,
-    rpc::types::Filter,
+    network::Ethereum, primitives::Address, providers::Provider, rpc::types::Filter,
     sol_types::SolEventInterface,
 };
 use anyhow::{Context, Result};
@@ -16,13 +17,20 @@ use tracing::{error, info, instrument};
 use crate::{
     blockchain_manager::{multicall::MulticallManager, AaveHelperContract, BlockchainManager},
     config::LocalConfig,
+    metrics::METRICS,
+    reserves_helper::{self, AaveReserve, ReserveRegistry},
+    user_events::UserEvents,
     users_helper::UserHelper,
-    utils::contracts::AavePoolContract::{self, AavePoolContractEvents},
+    users_updater_service::UpdateScheduler,
+    utils::contracts::AavePoolContract::AavePoolContractEvents,
 };
 
 /// Represents the main indexer for tracking user activities on Aave Pool
 pub struct UsersIndexer;
 
+/// Users touched by the processed logs, with the last block they were touched in
+type TouchedUsers = Vec<(Address, u64)>;
+
 /// Holds the current state of the Users Indexer
 #[derive(Debug)]
 pub struct UsersIndexerState {
@@ -36,6 +44,14 @@ pub struct UsersIndexerState {
     pub max_block_out_of_sync: u64,
     /// Number of blocks to process per iteration
     pub log_blocks_per_read: u64,
+    /// Number of blocks behind the chain head whose hashes are kept for reorg detection
+    pub reorg_confirmation_window: u64,
+}
+
+impl Default for UsersIndexer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UsersIndexer {
@@ -52,22 +68,36 @@ impl UsersIndexer {
     /// # Arguments
     /// * `db` - Arc wrapped database connection
     /// * `local_config` - Arc wrapped local configuration
+    /// * `reserve_registry` - Registry of the Aave reserves
+    /// * `update_scheduler` - Priority queue of the users to refresh, users touched by
+    ///   events are pushed to its front
+    /// * `user_events` - Publisher of the users' tier and health factor changes
     ///
     /// # Returns
     /// * `Result<JoinHandle<Result<()>>>` - A handle to the spawned indexing task
-    #[instrument("USERS_INDEXER", skip(db, local_config))]
+    #[instrument(
+        "USERS_INDEXER",
+        skip(db, local_config, reserve_registry, update_scheduler, user_events)
+    )]
     pub async fn start_users_indexer(
         db: &Arc<DatabaseConnection>,
         local_config: &Arc<LocalConfig>,
+        reserve_registry: &Arc<ReserveRegistry>,
+        update_scheduler: &Arc<UpdateScheduler>,
+        user_events: &Arc<UserEvents>,
     ) -> Result<JoinHandle<Result<()>>> {
         let db = db.clone();
         let local_config = local_config.clone();
+        let reserve_registry = reserve_registry.clone();
+        let update_scheduler = update_scheduler.clone();
+        let user_events = user_events.clone();
 
         let handle = tokio::spawn(async move {
             info!("Starting indexer");
 
             // Initialize the last indexed block in database
-            last_index_block_helper::init_last_index_block(&db, local_config.start_block).await?;
+            last_index_block_helper::init_last_index_block(db.as_ref(), local_config.start_block)
+                .await?;
 
             let provider = BlockchainManager::get_provider(&local_config).await?;
 
@@ -77,12 +107,16 @@ impl UsersIndexer {
                 BlockchainManager::get_aave_helper_contracts(&provider, &local_config).await?,
             );
 
-            let aave_reserves = aave_helper_contracts
-                .pool_contract
-                .getReservesList()
-                .call()
-                .await?
-                ._0;
+            Self::run_backfill_if_needed(
+                &db,
+                &provider,
+                &local_config,
+                &aave_helper_contracts,
+                &reserve_registry.reserves(),
+                &mut multicall_manager,
+                &user_events,
+            )
+            .await?;
 
             let mut users_indexer_state =
                 Self::initialize_indexer_state(&db, &provider, &local_config).await?;
@@ -94,19 +128,51 @@ impl UsersIndexer {
 
                 if Self::should_wait(users_indexer_state.current_block as i64, next_to_block) {
                     tokio::time::sleep(std::time::Duration::from_secs(20)).await;
-                    users_indexer_state.current_block = provider.get_block_number().await?;
+                    users_indexer_state.current_block = METRICS
+                        .observe_rpc("eth_blockNumber", provider.get_block_number().await)?;
+                    METRICS.record_sync(
+                        users_indexer_state.last_index_block.block_number as u64,
+                        users_indexer_state.current_block,
+                    );
                     continue;
                 }
 
-                let logs = Self::fetch_logs(
+                // Roll back to the common ancestor if the chain reorganized under us
+                if Self::check_and_handle_reorg(
+                    &db,
                     &provider,
                     &local_config,
-                    users_indexer_state.last_index_block.block_number as u64,
+                    &aave_helper_contracts,
+                    &reserve_registry.reserves(),
+                    &mut users_indexer_state,
+                    &mut multicall_manager,
+                    &user_events,
+                )
+                .await?
+                {
+                    continue;
+                }
+
+                let from_block = users_indexer_state.last_index_block.block_number as u64;
+
+                // Apply configuration changes first so the users below see the new constraints
+                let configuration_logs = Self::fetch_reserve_configuration_logs(
+                    &provider,
+                    &local_config,
+                    from_block,
                     next_to_block as u64,
                 )
                 .await?;
+                reserve_registry
+                    .apply_configuration_logs(&db, &aave_helper_contracts, &configuration_logs)
+                    .await?;
+                let aave_reserves = reserve_registry.reserves();
+
+                let logs =
+                    Self::fetch_logs(&provider, &local_config, from_block, next_to_block as u64)
+                        .await?;
 
-                Self::process_logs(
+                let (touched_users, skipped_users) = Self::process_logs(
                     &logs,
                     &db,
                     &local_config,
@@ -114,6 +180,21 @@ impl UsersIndexer {
                     &aave_reserves,
                     &users_indexer_state,
                     &mut multicall_manager,
+                    &user_events,
+                )
+                .await?;
+
+                // Users with recent data are skipped above, have the updater refresh them next
+                update_scheduler.prioritize(&skipped_users);
+
+                Self::record_confirmation_window(
+                    &db,
+                    &provider,
+                    &users_indexer_state,
+                    from_block,
+                    next_to_block as u64,
+                    &logs,
+                    &touched_users,
                 )
                 .await?;
 
@@ -139,46 +220,51 @@ impl UsersIndexer {
     /// * `aave_helper_contracts` - Aave helper contracts
     /// * `aave_reserves` - Aave reserves
     /// * `users_indexer_state` - Users indexer state
+    /// * `user_events` - Publisher of the users' tier and health factor changes
     ///
     /// # Returns
-    /// * `Result<()>` - A result of the operation
+    /// * `Result<(TouchedUsers, Vec<String>)>` - Tuple containing (touched users with the
+    ///   last block they were touched in, touched users skipped for having recent data)
     #[instrument("USERS_INDEXER", skip_all)]
+    #[allow(clippy::too_many_arguments)]
     async fn process_logs<'a, P: Provider<Ethereum>>(
         logs: &[alloy::rpc::types::Log],
         db: &DatabaseConnection,
         local_config: &LocalConfig,
         aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
-        aave_reserves: &Vec<Address>,
+        aave_reserves: &[AaveReserve],
         users_indexer_state: &UsersIndexerState,
         multicall_manager: &mut MulticallManager<&'a P>,
-    ) -> Result<()> {
-        let borrow_events = Self::process_borrow_events(&logs)?;
-
-        if !borrow_events.is_empty() {
-            // Proccess each user by sending them to queue of mpsc channel
-            for borrow_event in borrow_events {
-                let user_address = borrow_event.user.to_string();
-                info!("Updating user: {}", user_address);
-                match UserHelper::update_user(
-                    &db,
-                    &local_config,
-                    &user_address,
-                    users_indexer_state.current_block,
-                    &aave_helper_contracts,
-                    &aave_reserves,
-                    multicall_manager,
-                )
-                .await
-                {
-                    Ok(_) => (),
-                    Err(e) => {
-                        error!("Failed to update user: {}", e);
-                        return Err(e);
-                    }
+        user_events: &UserEvents,
+    ) -> Result<(TouchedUsers, Vec<String>)> {
+        let affected_users = Self::process_pool_events(logs)?;
+        let mut skipped_users = Vec::new();
+
+        // Each user is refreshed once per batch, however many events touched them
+        for (user_address, _) in affected_users.iter() {
+            let user_address = user_address.to_string();
+            info!("Updating user: {}", user_address);
+            match UserHelper::update_user(
+                db,
+                local_config,
+                &user_address,
+                users_indexer_state.current_block,
+                aave_helper_contracts,
+                aave_reserves,
+                multicall_manager,
+                user_events,
+            )
+            .await
+            {
+                Ok(true) => (),
+                Ok(false) => skipped_users.push(user_address),
+                Err(e) => {
+                    error!("Failed to update user: {}", e);
+                    return Err(e);
                 }
             }
         }
-        Ok(())
+        Ok((affected_users, skipped_users))
     }
 
     /// Updates the indexer states in database and prints the current status
@@ -197,17 +283,28 @@ impl UsersIndexer {
         provider: &impl Provider,
         next_to_block: u64,
     ) -> Result<()> {
-        users_indexer_state.current_block = provider.get_block_number().await?;
+        users_indexer_state.current_block =
+            METRICS.observe_rpc("eth_blockNumber", provider.get_block_number().await)?;
         users_indexer_state.last_index_block.block_number = next_to_block as i32;
 
+        let timer = METRICS
+            .db_write_duration
+            .with_label_values(&["last_index_block"])
+            .start_timer();
         last_index_block_helper::update_last_index_block(
             db,
             users_indexer_state.last_index_block.clone(),
             users_indexer_state.last_index_block.block_number as u64,
         )
         .await?;
+        timer.observe_duration();
+
+        METRICS.record_sync(
+            users_indexer_state.last_index_block.block_number as u64,
+            users_indexer_state.current_block,
+        );
 
-        Self::print_status(&users_indexer_state);
+        Self::print_status(users_indexer_state);
 
         Ok(())
     }
@@ -229,12 +326,12 @@ impl UsersIndexer {
         Ok(UsersIndexerState {
             start_block: local_config.start_block,
             last_index_block: last_index_block_helper::get_last_index_block(db).await?,
-            current_block: provider
-                .get_block_number()
-                .await
+            current_block: METRICS
+                .observe_rpc("eth_blockNumber", provider.get_block_number().await)
                 .context("Failed to get current block")?,
             max_block_out_of_sync: local_config.max_block_lag,
             log_blocks_per_read: local_config.log_per_request,
+            reorg_confirmation_window: local_config.reorg_confirmation_window,
         })
     }
 
@@ -245,7 +342,6 @@ impl UsersIndexer {
     /// * `local_config` - Local configuration
     /// * `from_block` - Starting block number
     /// * `to_block` - Ending block number
-    /// * `event_signature` - Event signature to filter logs
     ///
     /// # Returns
     /// * `Result<Vec<Log>>` - Vector of fetched logs
@@ -257,34 +353,74 @@ impl UsersIndexer {
     ) -> Result<Vec<alloy::rpc::types::Log>> {
         let filter = Filter::new()
             .address(vec![local_config.pool_address.parse()?])
-            .event_signature(b256!(
-                "b3d084820fb1a9decffb176436bd02558d15fac9b0ddfed8c465bc7359d7dce0"
-            ))
+            .event_signature(events::position_changing_event_signatures())
             .from_block(from_block)
             .to_block(to_block);
 
-        provider.get_logs(&filter).await.map_err(Into::into)
+        METRICS
+            .observe_rpc("eth_getLogs", provider.get_logs(&filter).await)
+            .map_err(Into::into)
     }
 
-    /// Processes blockchain logs to extract borrow events
+    /// Fetches the reserve configuration logs of the pool and the pool configurator for
+    /// the specified block range
+    ///
+    /// # Arguments
+    /// * `provider` - Blockchain provider
+    /// * `local_config` - Local configuration
+    /// * `from_block` - Starting block number
+    /// * `to_block` - Ending block number
+    ///
+    /// # Returns
+    /// * `Result<Vec<Log>>` - Vector of fetched logs
+    async fn fetch_reserve_configuration_logs(
+        provider: &impl Provider,
+        local_config: &LocalConfig,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<alloy::rpc::types::Log>> {
+        let filter = Filter::new()
+            .address(vec![
+                local_config.pool_address.parse()?,
+                local_config.pool_configurator.parse()?,
+            ])
+            .event_signature(reserves_helper::configuration_event_signatures())
+            .from_block(from_block)
+            .to_block(to_block);
+
+        METRICS
+            .observe_rpc("eth_getLogs", provider.get_logs(&filter).await)
+            .map_err(Into::into)
+    }
+
+    /// Processes blockchain logs to extract the users affected by position-changing events
     ///
     /// # Arguments
     /// * `logs` - Vector of blockchain logs
     ///
     /// # Returns
-    /// * `Result<Vec<AavePoolContract::Borrow>>` - Vector of processed borrow events
-    fn process_borrow_events(
-        logs: &[alloy::rpc::types::Log],
-    ) -> Result<Vec<AavePoolContract::Borrow>> {
-        Ok(logs
-            .iter()
-            .map(|log| {
-                AavePoolContractEvents::decode_log(&log.inner, false).map_err(anyhow::Error::from)
-            })
-            .collect::<Result<Vec<_>>>()?
-            .into_iter()
-            .filter_map(|event| event.as_borrow().cloned())
-            .collect())
+    /// * `Result<Vec<(Address, u64)>>` - Deduplicated affected users, in order of first appearance,
+    ///   with the last block they were touched in
+    fn process_pool_events(logs: &[alloy::rpc::types::Log]) -> Result<Vec<(Address, u64)>> {
+        let mut affected_users: Vec<(Address, u64)> = Vec::new();
+        let mut user_positions: HashMap<Address, usize> = HashMap::new();
+
+        for log in logs {
+            let event = AavePoolContractEvents::decode_log(&log.inner, false)?;
+            let block_number = log.block_number.unwrap_or_default();
+
+            for user in events::affected_users(&event) {
+                match user_positions.get(&user) {
+                    Some(&position) => affected_users[position].1 = block_number,
+                    None => {
+                        user_positions.insert(user, affected_users.len());
+                        affected_users.push((user, block_number));
+                    }
+                }
+            }
+        }
+
+        Ok(affected_users)
     }
 
     /// Calculates the next block number to process and ensures it doesn't exceed the current block number
@@ -0,0 +1,278 @@
+use std::{collections::HashMap, sync::Arc};
+
+use alloy::{
+    network::Ethereum,
+    primitives::{Address, B256},
+    providers::Provider,
+    rpc::types::{BlockTransactionsKind, Header, Log},
+};
+use anyhow::{Context, Result};
+use futures::future::try_join_all;
+use indexer_database::{indexed_blocks_helper, last_index_block_helper};
+use sea_orm::DatabaseConnection;
+use tracing::{error, info, instrument, warn};
+
+use super::{UsersIndexer, UsersIndexerState};
+use crate::{
+    blockchain_manager::{multicall::MulticallManager, AaveHelperContract},
+    config::LocalConfig,
+    metrics::METRICS,
+    reserves_helper::AaveReserve,
+    user_events::UserEvents,
+    users_helper::UserHelper,
+};
+
+impl UsersIndexer {
+    /// Checks the stored block hashes against the chain and rolls the indexer back if they diverged
+    ///
+    /// When a reorg is found, every user touched after the common ancestor is re-evaluated
+    /// at the current block, `last_index_block` is rewound to the ancestor and the stored
+    /// window is truncated, so the next iteration re-indexes the affected range.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `provider` - Blockchain provider
+    /// * `local_config` - Local configuration
+    /// * `aave_helper_contracts` - Aave helper contracts
+    /// * `aave_reserves` - Aave reserves
+    /// * `users_indexer_state` - Users indexer state
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<bool>` - True if a reorg was detected and handled
+    #[instrument("USERS_INDEXER", skip_all)]
+    #[allow(clippy::too_many_arguments)]
+    pub(super) async fn check_and_handle_reorg<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        provider: &'a P,
+        local_config: &LocalConfig,
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        users_indexer_state: &mut UsersIndexerState,
+        multicall_manager: &mut MulticallManager<&'a P>,
+        user_events: &UserEvents,
+    ) -> Result<bool> {
+        let Some(common_ancestor) =
+            Self::detect_reorg(db, provider, users_indexer_state.current_block).await?
+        else {
+            return Ok(false);
+        };
+
+        let rollback_block =
+            common_ancestor.min(users_indexer_state.last_index_block.block_number as u64);
+
+        warn!(
+            "Chain reorganization detected, rolling back from block {} to block {}",
+            users_indexer_state.last_index_block.block_number, rollback_block
+        );
+
+        users_indexer_state.current_block =
+            METRICS.observe_rpc("eth_blockNumber", provider.get_block_number().await)?;
+
+        // Re-evaluate users first so a crash mid-rollback is retried on restart
+        let touched_users =
+            indexed_blocks_helper::get_users_touched_after(db, rollback_block).await?;
+        for user_address in touched_users {
+            info!("Re-evaluating user after reorg: {}", user_address);
+            if let Err(e) = UserHelper::force_update_user(
+                db,
+                local_config,
+                &user_address,
+                users_indexer_state.current_block,
+                aave_helper_contracts,
+                aave_reserves,
+                multicall_manager,
+                user_events,
+            )
+            .await
+            {
+                error!("Failed to re-evaluate user: {}", e);
+                return Err(e);
+            }
+        }
+
+        users_indexer_state.last_index_block.block_number = rollback_block as i32;
+        last_index_block_helper::update_last_index_block(
+            db,
+            users_indexer_state.last_index_block.clone(),
+            rollback_block,
+        )
+        .await?;
+
+        indexed_blocks_helper::delete_blocks_after(db, rollback_block).await?;
+
+        Ok(true)
+    }
+
+    /// Finds the common ancestor between the stored block hashes and the chain
+    ///
+    /// The stored tip is first checked against its child's parent hash (or its own hash
+    /// when the child isn't mined yet). Only on a mismatch are the stored blocks walked
+    /// back one by one until a hash matches the chain again.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `provider` - Blockchain provider
+    /// * `current_block` - Current blockchain block number
+    ///
+    /// # Returns
+    /// * `Result<Option<u64>>` - The common ancestor block if a reorg was detected
+    async fn detect_reorg(
+        db: &DatabaseConnection,
+        provider: &impl Provider,
+        current_block: u64,
+    ) -> Result<Option<u64>> {
+        let stored_blocks = indexed_blocks_helper::get_indexed_blocks_descending(db).await?;
+
+        let Some(stored_tip) = stored_blocks.first() else {
+            return Ok(None);
+        };
+
+        let tip_number = stored_tip.block_number as u64;
+        let is_consistent = if tip_number < current_block {
+            let child = Self::get_block_header(provider, tip_number + 1).await?;
+            child.parent_hash.to_string() == stored_tip.block_hash
+        } else {
+            let tip = Self::get_block_header(provider, tip_number).await?;
+            tip.hash.to_string() == stored_tip.block_hash
+        };
+
+        if is_consistent {
+            return Ok(None);
+        }
+
+        for stored_block in stored_blocks.iter() {
+            let block_number = stored_block.block_number as u64;
+            let header = Self::get_block_header(provider, block_number).await?;
+            if header.hash.to_string() == stored_block.block_hash {
+                return Ok(Some(block_number));
+            }
+        }
+
+        // No stored block is canonical anymore, the reorg is deeper than the window
+        let lowest_block = stored_blocks
+            .last()
+            .map(|block| block.block_number as u64)
+            .unwrap_or(tip_number);
+        warn!(
+            "Reorg is deeper than the confirmation window, rolling back below block {}",
+            lowest_block
+        );
+
+        Ok(Some(lowest_block.saturating_sub(1)))
+    }
+
+    /// Stores the hashes of the processed blocks and the users touched in them,
+    /// limited to the blocks inside the confirmation window
+    ///
+    /// The headers are fetched after the logs, so a reorg in between can return another
+    /// fork. Blocks with logs are stored with the hash of their logs, and the window stops
+    /// at the first block whose header doesn't match it, so the next reorg check rolls
+    /// back the logs indexed from the orphaned block.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `provider` - Blockchain provider
+    /// * `users_indexer_state` - Users indexer state
+    /// * `from_block` - First processed block
+    /// * `to_block` - Last processed block
+    /// * `logs` - The processed logs
+    /// * `touched_users` - Users touched by the processed logs, with the last block they were touched in
+    ///
+    /// # Returns
+    /// * `Result<()>` - A result of the operation
+    pub(super) async fn record_confirmation_window(
+        db: &DatabaseConnection,
+        provider: &impl Provider,
+        users_indexer_state: &UsersIndexerState,
+        from_block: u64,
+        to_block: u64,
+        logs: &[Log],
+        touched_users: &[(Address, u64)],
+    ) -> Result<()> {
+        let window_start = users_indexer_state
+            .current_block
+            .saturating_sub(users_indexer_state.reorg_confirmation_window);
+        let first_block = from_block.max(window_start);
+
+        if first_block <= to_block {
+            let log_block_hashes = logs
+                .iter()
+                .filter_map(|log| Some((log.block_number?, log.block_hash?)))
+                .collect::<HashMap<u64, B256>>();
+
+            let headers = try_join_all(
+                (first_block..=to_block)
+                    .map(|block_number| Self::get_block_header(provider, block_number)),
+            )
+            .await?;
+
+            let mut previous_hash =
+                indexed_blocks_helper::get_indexed_block(db, first_block.saturating_sub(1))
+                    .await?
+                    .map(|block| block.block_hash);
+
+            // Only keep a linked chain, a broken link is picked up by the next reorg check
+            let mut blocks = Vec::with_capacity(headers.len());
+            for header in headers {
+                let parent_hash = header.parent_hash.to_string();
+                if previous_hash
+                    .as_ref()
+                    .is_some_and(|hash| *hash != parent_hash)
+                {
+                    warn!(
+                        "Block {} does not link to the stored parent hash",
+                        header.number
+                    );
+                    break;
+                }
+                let block_hash = log_block_hashes
+                    .get(&header.number)
+                    .copied()
+                    .unwrap_or(header.hash);
+                blocks.push((header.number, block_hash.to_string(), parent_hash));
+                if block_hash != header.hash {
+                    warn!(
+                        "Logs of block {} come from another fork than its header",
+                        header.number
+                    );
+                    break;
+                }
+                previous_hash = Some(block_hash.to_string());
+            }
+
+            indexed_blocks_helper::add_indexed_blocks(db, blocks).await?;
+
+            let touched_users = touched_users
+                .iter()
+                .filter(|(_, block_number)| *block_number >= window_start)
+                .map(|(user, block_number)| (*block_number, user.to_string()))
+                .collect();
+            indexed_blocks_helper::add_touched_users(db, touched_users).await?;
+        }
+
+        indexed_blocks_helper::prune_blocks_before(db, window_start).await?;
+
+        Ok(())
+    }
+
+    /// Fetches a block header by number
+    ///
+    /// # Arguments
+    /// * `provider` - Blockchain provider
+    /// * `block_number` - Block number to fetch
+    ///
+    /// # Returns
+    /// * `Result<Header>` - The block header
+    async fn get_block_header(provider: &impl Provider, block_number: u64) -> Result<Header> {
+        let block = METRICS
+            .observe_rpc(
+                "eth_getBlockByNumber",
+                provider
+                    .get_block_by_number(block_number.into(), BlockTransactionsKind::Hashes)
+                    .await,
+            )?
+            .context(format!("Block {} not found", block_number))?;
+        Ok(block.header)
+    }
+}
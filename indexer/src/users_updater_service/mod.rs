@@ -1,35 +1,89 @@
-use std::sync::Arc;
+mod scheduler;
 
-use alloy::{network::Ethereum, primitives::Address, providers::Provider};
+pub use scheduler::UpdateScheduler;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use alloy::{network::Ethereum, providers::Provider};
 use anyhow::Result;
-use indexer_database::users_tables_helper;
+use futures::future::try_join_all;
+use indexer_database::{
+    user_debt_collateral_helper,
+    users_tables_helper::{self, UserCurrentLocation},
+};
 use sea_orm::DatabaseConnection;
-use tokio::task::JoinHandle;
-use tracing::{error, info, instrument};
+use tokio::{
+    sync::broadcast::{self, error::TryRecvError},
+    task::JoinHandle,
+};
+use tracing::{error, info, instrument, warn};
 
 use crate::{
     blockchain_manager::{multicall::MulticallManager, AaveHelperContract, BlockchainManager},
     config::LocalConfig,
-    users_helper::UserHelper,
+    metrics::METRICS,
+    reserves_helper::{AaveReserve, ReserveChange, ReserveRegistry},
+    user_events::UserEvents,
+    users_helper::{UserHelper, UserUpdateFailure},
 };
 
+/// Order in which the due users of a round are refreshed, users the scheduler doesn't
+/// know the tier of last
+const TIERS_REFRESH_ORDER: [UserCurrentLocation; 4] = [
+    UserCurrentLocation::Liquidatable,
+    UserCurrentLocation::AtRisk,
+    UserCurrentLocation::Healthy,
+    UserCurrentLocation::NotFound,
+];
+
 pub struct UsersUpdaterService;
 
 impl UsersUpdaterService {
-    #[instrument("UPDATER_SERVICE", skip(db, local_config))]
+    /// Starts refreshing the users in the order of the update scheduler
+    ///
+    /// Each cycle refreshes the due users, the most overdue first, until none is due or
+    /// `UPDATER_CYCLE_BUDGET` seconds are spent, and schedules each refreshed user again
+    /// from its new state. Between cycles the service sleeps until the next user is due
+    /// or users are pushed to the front of the queue. Every `HEALTHY_USERS_UPDATE_FREQUENCY`
+    /// seconds the stored accounts are synced into the scheduler, which picks up the
+    /// users added by the indexer. Users holding a reserve dropped from the pool are
+    /// pushed to the front of the queue, so their positions in it are removed.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Arc wrapped local configuration
+    /// * `reserve_registry` - Registry of the Aave reserves
+    /// * `update_scheduler` - Priority queue of the users to refresh
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<JoinHandle<Result<()>>>` - A handle to the spawned updater task
+    #[instrument(
+        "UPDATER_SERVICE",
+        skip(db, local_config, reserve_registry, update_scheduler, user_events)
+    )]
     pub async fn start_users_updater_service(
         db: &DatabaseConnection,
         local_config: &Arc<LocalConfig>,
+        reserve_registry: &Arc<ReserveRegistry>,
+        update_scheduler: &Arc<UpdateScheduler>,
+        user_events: &Arc<UserEvents>,
     ) -> Result<JoinHandle<Result<()>>> {
         let db = db.clone();
         let local_config = local_config.clone();
+        let reserve_registry = reserve_registry.clone();
+        let update_scheduler = update_scheduler.clone();
+        let user_events = user_events.clone();
+        let mut reserve_changes = reserve_registry.subscribe();
 
         let handle = tokio::spawn(async move {
             info!("Starting updater service");
 
-            let mut last_liquidatable_users_update = chrono::Utc::now().timestamp() as u64;
-            let mut last_at_risk_users_update = chrono::Utc::now().timestamp() as u64;
-            let mut last_healthy_users_update = chrono::Utc::now().timestamp() as u64;
+            let mut last_accounts_sync = None;
 
             let provider = BlockchainManager::get_provider(&local_config).await?;
 
@@ -37,171 +91,392 @@ impl UsersUpdaterService {
                 BlockchainManager::get_aave_helper_contracts(&provider, &local_config).await?,
             );
 
-            let aave_reserves = aave_helper_contracts
-                .pool_contract
-                .getReservesList()
-                .call()
-                .await?
-                ._0;
-
-            let mut multicall_manager = MulticallManager::new(&provider).await?;
-
             loop {
                 let now = chrono::Utc::now().timestamp() as u64;
-                let block_number = provider.get_block_number().await?;
 
-                // Update liquidatable users
-                if now - last_liquidatable_users_update
-                    >= local_config.liquidatable_users_update_frequency
-                {
-                    info!("Updating liquidatable users");
-                    match Self::update_liquidatable_users(
-                        &db,
-                        &local_config,
-                        &aave_helper_contracts,
-                        &aave_reserves,
-                        block_number,
-                        &mut multicall_manager,
-                    )
-                    .await
-                    {
+                // Sync the stored accounts, so users added since the last sync are scheduled
+                if last_accounts_sync.is_none_or(|last_accounts_sync| {
+                    now - last_accounts_sync >= local_config.healthy_users_update_frequency
+                }) {
+                    match Self::sync_scheduled_users(&db, &local_config, &update_scheduler).await {
                         Ok(_) => {
-                            info!("Liquidatable users updated");
-                            last_liquidatable_users_update = now;
+                            info!("{} users scheduled", update_scheduler.len());
+                            last_accounts_sync = Some(now);
                         }
-                        Err(e) => error!("Error updating liquidatable users: {}", e),
+                        Err(e) => error!("Error syncing scheduled users: {}", e),
                     }
                 }
 
-                // Update at risk users
-                if now - last_at_risk_users_update >= local_config.at_risk_users_update_frequency {
-                    info!("Updating at risk users");
-                    match Self::update_at_risk_users(
-                        &db,
-                        &local_config,
-                        &aave_helper_contracts,
-                        &aave_reserves,
-                        block_number,
-                        &mut multicall_manager,
-                    )
-                    .await
-                    {
-                        Ok(_) => {
-                            info!("At risk users updated");
-                            last_at_risk_users_update = now;
-                        }
-                        Err(e) => error!("Error updating at risk users: {}", e),
-                    }
+                if let Err(e) =
+                    Self::prioritize_dropped_reserves(&db, &update_scheduler, &mut reserve_changes)
+                        .await
+                {
+                    error!("Error prioritizing users of dropped reserves: {}", e);
                 }
 
-                // Update healthy users
-                if now - last_healthy_users_update >= local_config.healthy_users_update_frequency {
-                    info!("Updating healthy users");
-                    match Self::update_healthy_users(
-                        &db,
-                        &local_config,
-                        &aave_helper_contracts,
-                        &aave_reserves,
-                        block_number,
-                        &mut multicall_manager,
-                    )
-                    .await
-                    {
-                        Ok(_) => {
-                            info!("Healthy users updated");
-                            last_healthy_users_update = now;
-                        }
-                        Err(e) => error!("Error updating healthy users: {}", e),
-                    }
+                let block_number =
+                    METRICS.observe_rpc("eth_blockNumber", provider.get_block_number().await)?;
+                let aave_reserves = reserve_registry.reserves();
+
+                match Self::run_update_cycle(
+                    &db,
+                    &local_config,
+                    &aave_helper_contracts,
+                    &aave_reserves,
+                    &provider,
+                    block_number,
+                    &update_scheduler,
+                    &user_events,
+                )
+                .await
+                {
+                    Ok(0) => (),
+                    Ok(refreshed_users) => info!("Refreshed {} due users", refreshed_users),
+                    Err(e) => error!("Error refreshing due users: {}", e),
                 }
 
-                // Wait for the next update
-                tokio::time::sleep(std::time::Duration::from_secs(
-                    local_config.liquidatable_users_update_frequency,
-                ))
-                .await;
+                // Wait for the next due user, or for users pushed to the front
+                let now = chrono::Utc::now().timestamp() as u64;
+                let wait = update_scheduler
+                    .next_due_at()
+                    .map(|due_at| due_at.saturating_sub(now))
+                    .unwrap_or(local_config.liquidatable_users_update_frequency)
+                    .min(local_config.liquidatable_users_update_frequency);
+                if wait > 0 {
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(wait)) => (),
+                        _ = update_scheduler.prioritized() => (),
+                    }
+                }
             }
         });
         Ok(handle)
     }
 
-    #[instrument("UPDATE_LIQUIDATABLE_USERS", skip_all)]
-    async fn update_liquidatable_users<'a, P: Provider<Ethereum>>(
+    /// Refreshes the due users until none is due or the cycle's time budget is spent
+    ///
+    /// Users are popped in rounds of as many users as the workers refresh at once, the
+    /// budget is checked between rounds. The users of a round are refreshed tier by tier,
+    /// the most urgent first, and the time spent on each tier is recorded once per cycle.
+    /// Every popped user is scheduled again from its stored state, refreshed or not, and
+    /// users that aren't stored anymore are dropped.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `provider` - Blockchain provider
+    /// * `block_number` - Current block number
+    /// * `update_scheduler` - Priority queue of the users to refresh
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    ///
+    /// # Returns
+    /// * `Result<usize>` - Number of users refreshed in the cycle, failed users left out
+    #[instrument("UPDATE_DUE_USERS", skip_all)]
+    #[allow(clippy::too_many_arguments)]
+    async fn run_update_cycle<'a, P: Provider<Ethereum>>(
         db: &DatabaseConnection,
-        local_config: &Arc<LocalConfig>,
+        local_config: &LocalConfig,
         aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
-        aave_reserves: &Vec<Address>,
+        aave_reserves: &[AaveReserve],
+        provider: &'a P,
         block_number: u64,
-        multicall_manager: &mut MulticallManager<&'a P>,
+        update_scheduler: &UpdateScheduler,
+        user_events: &UserEvents,
+    ) -> Result<usize> {
+        let cycle_start = Instant::now();
+        let cycle_budget = Duration::from_secs(local_config.updater_cycle_budget);
+        let users_per_round = UserHelper::users_per_multicall(local_config, aave_reserves.len())
+            * local_config.updater_concurrency.max(1) as usize;
+        let mut refreshed_users = 0;
+        let mut tier_durations: HashMap<UserCurrentLocation, Duration> = HashMap::new();
+        let mut outcome: Result<()> = Ok(());
+
+        while outcome.is_ok() && cycle_start.elapsed() < cycle_budget {
+            let now = chrono::Utc::now().timestamp() as u64;
+            let users = update_scheduler.pop_due(now, users_per_round);
+            if users.is_empty() {
+                break;
+            }
+
+            let mut users_per_tier: HashMap<UserCurrentLocation, Vec<String>> = HashMap::new();
+            for user_address in users.iter() {
+                users_per_tier
+                    .entry(update_scheduler.location(user_address))
+                    .or_default()
+                    .push(user_address.clone());
+            }
+
+            for location in TIERS_REFRESH_ORDER {
+                let Some(tier_users) = users_per_tier.remove(&location) else {
+                    continue;
+                };
+
+                let tier_start = Instant::now();
+                let result = Self::update_users_concurrently(
+                    db,
+                    local_config,
+                    &tier_users,
+                    aave_helper_contracts,
+                    aave_reserves,
+                    provider,
+                    block_number,
+                    user_events,
+                    true,
+                )
+                .await;
+                *tier_durations.entry(location).or_default() += tier_start.elapsed();
+
+                match result {
+                    Ok(failures) => {
+                        refreshed_users += tier_users.len().saturating_sub(failures.len())
+                    }
+                    Err(e) => {
+                        outcome = Err(e);
+                        break;
+                    }
+                }
+            }
+
+            let rescheduled =
+                Self::reschedule_users(db, local_config, update_scheduler, &users).await;
+            outcome = outcome.and(rescheduled);
+        }
+
+        for (location, duration) in tier_durations {
+            METRICS
+                .updater_cycle_duration
+                .with_label_values(&[location.name()])
+                .observe(duration.as_secs_f64());
+        }
+
+        outcome.map(|_| refreshed_users)
+    }
+
+    /// Schedules the given users again from their stored state
+    ///
+    /// If the users can't be read, they are pushed to the front of the queue instead so
+    /// they aren't lost.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `update_scheduler` - Priority queue of the users to refresh
+    /// * `user_addresses` - Ethereum addresses of the users
+    ///
+    /// # Returns
+    /// * `Result<()>` - Error if the users couldn't be read
+    async fn reschedule_users(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        update_scheduler: &UpdateScheduler,
+        user_addresses: &[String],
     ) -> Result<()> {
-        let liquidatable_users = users_tables_helper::get_all_liquidatable_users(db).await?;
-        for user in liquidatable_users {
-            info!("Updating user: {}", user);
+        let users = match users_tables_helper::get_users(db, user_addresses).await {
+            Ok(users) => users,
+            Err(e) => {
+                update_scheduler.prioritize(user_addresses);
+                return Err(e);
+            }
+        };
 
-            UserHelper::update_user(
-                db,
-                local_config,
-                &user,
-                block_number,
-                aave_helper_contracts,
-                aave_reserves,
-                multicall_manager,
-            )
-            .await?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        for user in users.iter() {
+            update_scheduler.schedule(local_config, user, now);
+        }
+        for user_address in user_addresses {
+            if !users.iter().any(|user| user.user_address == *user_address) {
+                update_scheduler.remove(user_address);
+            }
         }
+
         Ok(())
     }
 
-    #[instrument("UPDATE_AT_RISK_USERS", skip_all)]
-    async fn update_at_risk_users<'a, P: Provider<Ethereum>>(
+    /// Pushes the users holding a reserve dropped from the pool to the front of the queue
+    ///
+    /// Their next refresh only covers the listed reserves, which removes their stale
+    /// positions in the dropped reserve. Added reserves need nothing, every refresh
+    /// covers the whole reserve list.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `update_scheduler` - Priority queue of the users to refresh
+    /// * `reserve_changes` - Receiver of the reserve list changes
+    ///
+    /// # Returns
+    /// * `Result<()>` - Error if the users of the dropped reserves couldn't be read
+    async fn prioritize_dropped_reserves(
         db: &DatabaseConnection,
-        local_config: &Arc<LocalConfig>,
-        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
-        aave_reserves: &Vec<Address>,
-        block_number: u64,
-        multicall_manager: &mut MulticallManager<&'a P>,
+        update_scheduler: &UpdateScheduler,
+        reserve_changes: &mut broadcast::Receiver<ReserveChange>,
     ) -> Result<()> {
-        let at_risk_users = users_tables_helper::get_all_at_risk_users(db).await?;
-        for user in at_risk_users {
-            info!("Updating user: {}", user);
+        let mut dropped_reserves = Vec::new();
+        loop {
+            match reserve_changes.try_recv() {
+                Ok(ReserveChange::Dropped(reserve_address)) => {
+                    dropped_reserves.push(reserve_address.to_string())
+                }
+                Ok(ReserveChange::Added(_)) => (),
+                Err(TryRecvError::Lagged(missed_changes)) => {
+                    warn!("Missed {} reserve list changes", missed_changes)
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
 
-            UserHelper::update_user(
-                db,
-                local_config,
-                &user,
-                block_number,
-                aave_helper_contracts,
-                aave_reserves,
-                multicall_manager,
-            )
-            .await?;
+        if dropped_reserves.is_empty() {
+            return Ok(());
         }
+
+        let user_addresses = user_debt_collateral_helper::get_users_with_positions_in_reserves(
+            db,
+            &dropped_reserves,
+        )
+        .await?;
+        info!(
+            "Prioritizing {} users of {} dropped reserves",
+            user_addresses.len(),
+            dropped_reserves.len()
+        );
+        update_scheduler.prioritize(&user_addresses);
+
         Ok(())
     }
 
-    #[instrument("UPDATE_HEALTHY_USERS", skip_all)]
-    async fn update_healthy_users<'a, P: Provider<Ethereum>>(
+    /// Schedules the stored accounts that aren't scheduled yet, from their last refresh
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `update_scheduler` - Priority queue of the users to refresh
+    ///
+    /// # Returns
+    /// * `Result<()>` - Success or error result of the database operation
+    async fn sync_scheduled_users(
         db: &DatabaseConnection,
-        local_config: &Arc<LocalConfig>,
-        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
-        aave_reserves: &Vec<Address>,
-        block_number: u64,
-        multicall_manager: &mut MulticallManager<&'a P>,
+        local_config: &LocalConfig,
+        update_scheduler: &UpdateScheduler,
     ) -> Result<()> {
-        let healthy_users = users_tables_helper::get_all_healthy_users(db).await?;
-        for user in healthy_users {
-            info!("Updating user: {}", user);
-            UserHelper::update_user(
-                db,
+        let mut user_addresses = users_tables_helper::get_all_liquidatable_users(db).await?;
+        user_addresses.extend(users_tables_helper::get_all_at_risk_users(db).await?);
+        user_addresses.extend(users_tables_helper::get_all_healthy_users(db).await?);
+
+        for user in users_tables_helper::get_users(db, &user_addresses).await? {
+            update_scheduler.schedule_if_missing(
                 local_config,
                 &user,
-                block_number,
-                aave_helper_contracts,
-                aave_reserves,
-                multicall_manager,
-            )
-            .await?;
+                user.timestamp.timestamp() as u64,
+            );
         }
+
         Ok(())
     }
+
+    /// Refreshes users with a bounded pool of concurrent workers
+    ///
+    /// Users are split into multicall-sized batches that `UPDATER_CONCURRENCY` workers
+    /// pull from a shared queue, each worker owning its own `MulticallManager`. Users
+    /// that fail are collected and reported instead of aborting the whole tier pass.
+    /// Unless `force` is set, users refreshed less than `MAX_BLOCK_LAG` blocks ago are skipped.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection handle
+    /// * `local_config` - Local configuration settings
+    /// * `users` - Ethereum addresses of the users to refresh
+    /// * `aave_helper_contracts` - Arc reference to Aave protocol contract helpers
+    /// * `aave_reserves` - List of Aave reserves
+    /// * `provider` - Blockchain provider
+    /// * `block_number` - Current block number
+    /// * `user_events` - Publisher of the users' tier and health factor changes
+    /// * `force` - Whether recently refreshed users are refreshed again
+    ///
+    /// # Returns
+    /// * `Result<Vec<UserUpdateFailure>>` - The users that failed, already logged, or an
+    ///   error only if a worker could not be started
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn update_users_concurrently<'a, P: Provider<Ethereum>>(
+        db: &DatabaseConnection,
+        local_config: &LocalConfig,
+        users: &[String],
+        aave_helper_contracts: &Arc<AaveHelperContract<'a, P>>,
+        aave_reserves: &[AaveReserve],
+        provider: &'a P,
+        block_number: u64,
+        user_events: &UserEvents,
+        force: bool,
+    ) -> Result<Vec<UserUpdateFailure>> {
+        let users_per_batch = UserHelper::users_per_multicall(local_config, aave_reserves.len());
+        let batches = Mutex::new(users.chunks(users_per_batch));
+
+        let workers = (0..local_config.updater_concurrency.max(1)).map(|_| async {
+            let mut multicall_manager = MulticallManager::new(provider).await?;
+            let mut failures = Vec::new();
+
+            loop {
+                let Some(batch) = batches.lock().expect("batch queue poisoned").next() else {
+                    break;
+                };
+
+                let result = if force {
+                    UserHelper::force_update_users_batch(
+                        db,
+                        local_config,
+                        batch,
+                        block_number,
+                        aave_helper_contracts,
+                        aave_reserves,
+                        &mut multicall_manager,
+                        user_events,
+                    )
+                    .await
+                } else {
+                    UserHelper::update_users_batch(
+                        db,
+                        local_config,
+                        batch,
+                        block_number,
+                        aave_helper_contracts,
+                        aave_reserves,
+                        &mut multicall_manager,
+                        user_events,
+                    )
+                    .await
+                };
+
+                match result {
+                    Ok(batch_failures) => failures.extend(batch_failures),
+                    Err(e) => failures.extend(batch.iter().map(|user_address| UserUpdateFailure {
+                        user_address: user_address.clone(),
+                        error: format!("{:#}", e),
+                    })),
+                }
+            }
+
+            Ok::<_, anyhow::Error>(failures)
+        });
+
+        let failures = try_join_all(workers)
+            .await?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if !failures.is_empty() {
+            for failure in failures.iter() {
+                error!(
+                    "Failed to update user {}: {}",
+                    failure.user_address, failure.error
+                );
+            }
+            error!(
+                "Failed to update {} of {} users",
+                failures.len(),
+                users.len()
+            );
+        }
+
+        Ok(failures)
+    }
 }
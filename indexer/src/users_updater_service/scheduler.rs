@@ -0,0 +1,541 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    sync::Mutex,
+};
+
+use indexer_database::users_tables_helper::{UserCurrentLocation, UserDetails};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use tokio::sync::Notify;
+
+use crate::{
+    config::LocalConfig,
+    utils::constants::{LIQUIDATION_THRESHOLD, PRIORITY_DEBT_IN_USD, VOLATILITY_SENSITIVITY},
+};
+
+/// Number of replaced entries tolerated in the queue before it is rebuilt
+const MIN_QUEUE_SIZE_TO_COMPACT: usize = 1024;
+
+/// Scheduled refresh of a user
+#[derive(Debug, Clone)]
+struct ScheduledUser {
+    /// Unix timestamp (in seconds) at which the user is due
+    due_at: u64,
+    /// Health factor of the user when scheduled, used to measure its volatility
+    health_factor: Option<Decimal>,
+    /// Tier of the user when scheduled, `NotFound` if it was never scheduled from its details
+    location: UserCurrentLocation,
+}
+
+#[derive(Debug, Default)]
+struct SchedulerQueue {
+    /// Users ordered by due time, entries replaced by a later schedule are skipped when popped
+    heap: BinaryHeap<Reverse<(u64, String)>>,
+    users: HashMap<String, ScheduledUser>,
+}
+
+/// Priority queue of the users to refresh, keyed on their next due time
+///
+/// The due time of a user is derived from its health factor distance to 1.0, its debt
+/// size and how much its health factor moved since the previous refresh, so a user at
+/// 1.001 is refreshed far more often than one at 1.99. Any service can push users to
+/// the front of the queue with `prioritize`.
+#[derive(Debug, Default)]
+pub struct UpdateScheduler {
+    queue: Mutex<SchedulerQueue>,
+    prioritized: Notify,
+}
+
+impl UpdateScheduler {
+    /// Creates an empty scheduler
+    ///
+    /// # Returns
+    /// * `Self` - A new UpdateScheduler instance
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules the next refresh of a user from its stored details, replacing any
+    /// previous schedule of the user
+    ///
+    /// # Arguments
+    /// * `local_config` - Local configuration settings
+    /// * `user` - Stored details of the user
+    /// * `refreshed_at` - Unix timestamp (in seconds) the delay is counted from
+    pub fn schedule(&self, local_config: &LocalConfig, user: &UserDetails, refreshed_at: u64) {
+        let mut queue = self.queue.lock().expect("update scheduler poisoned");
+
+        let previous_health_factor = queue
+            .users
+            .get(&user.user_address)
+            .and_then(|scheduled_user| scheduled_user.health_factor);
+        let volatility = match previous_health_factor {
+            Some(previous_health_factor) if !previous_health_factor.is_zero() => {
+                ((user.health_factor - previous_health_factor) / previous_health_factor).abs()
+            }
+            _ => Decimal::ZERO,
+        };
+
+        let due_at = refreshed_at
+            + Self::next_update_delay(
+                local_config,
+                user.health_factor,
+                user.total_debt_value_in_usd,
+                volatility,
+            );
+
+        Self::push(
+            &mut queue,
+            user.user_address.clone(),
+            ScheduledUser {
+                due_at,
+                health_factor: Some(user.health_factor),
+                location: user.current_location.clone(),
+            },
+        );
+    }
+
+    /// Schedules a user if it isn't scheduled yet
+    ///
+    /// # Arguments
+    /// * `local_config` - Local configuration settings
+    /// * `user` - Stored details of the user
+    /// * `refreshed_at` - Unix timestamp (in seconds) the delay is counted from
+    pub fn schedule_if_missing(
+        &self,
+        local_config: &LocalConfig,
+        user: &UserDetails,
+        refreshed_at: u64,
+    ) {
+        let is_scheduled = self
+            .queue
+            .lock()
+            .expect("update scheduler poisoned")
+            .users
+            .contains_key(&user.user_address);
+
+        if !is_scheduled {
+            self.schedule(local_config, user, refreshed_at);
+        }
+    }
+
+    /// Pushes users to the front of the queue, so they are refreshed in the next cycle
+    ///
+    /// # Arguments
+    /// * `user_addresses` - Ethereum addresses of the users
+    pub fn prioritize(&self, user_addresses: &[String]) {
+        if user_addresses.is_empty() {
+            return;
+        }
+
+        {
+            let mut queue = self.queue.lock().expect("update scheduler poisoned");
+            for user_address in user_addresses {
+                let (health_factor, location) = queue
+                    .users
+                    .get(user_address)
+                    .map(|scheduled_user| {
+                        (
+                            scheduled_user.health_factor,
+                            scheduled_user.location.clone(),
+                        )
+                    })
+                    .unwrap_or((None, UserCurrentLocation::NotFound));
+                Self::push(
+                    &mut queue,
+                    user_address.clone(),
+                    ScheduledUser {
+                        due_at: 0,
+                        health_factor,
+                        location,
+                    },
+                );
+            }
+        }
+
+        self.prioritized.notify_one();
+    }
+
+    /// Removes a user from the queue
+    ///
+    /// # Arguments
+    /// * `user_address` - Ethereum address of the user
+    pub fn remove(&self, user_address: &str) {
+        self.queue
+            .lock()
+            .expect("update scheduler poisoned")
+            .users
+            .remove(user_address);
+    }
+
+    /// Pops the users due at the given time, the most overdue first
+    ///
+    /// Popped users stay known to the scheduler, with their last health factor, but are
+    /// only popped again once they are scheduled or prioritized again, so every popped
+    /// user must be either rescheduled or removed.
+    ///
+    /// # Arguments
+    /// * `now` - Current unix timestamp (in seconds)
+    /// * `max_users` - Maximum number of users to pop
+    ///
+    /// # Returns
+    /// * `Vec<String>` - Ethereum addresses of the due users
+    pub fn pop_due(&self, now: u64, max_users: usize) -> Vec<String> {
+        let mut queue = self.queue.lock().expect("update scheduler poisoned");
+        let mut due_users = Vec::new();
+
+        while due_users.len() < max_users {
+            let Some(Reverse((due_at, _))) = queue.heap.peek() else {
+                break;
+            };
+            if *due_at > now {
+                break;
+            }
+            let Some(Reverse((due_at, user_address))) = queue.heap.pop() else {
+                break;
+            };
+
+            // Skip entries replaced by a later schedule, removed or already popped
+            if let Some(scheduled_user) = queue
+                .users
+                .get_mut(&user_address)
+                .filter(|scheduled_user| scheduled_user.due_at == due_at)
+            {
+                scheduled_user.due_at = u64::MAX;
+                due_users.push(user_address);
+            }
+        }
+
+        due_users
+    }
+
+    /// Returns the tier of a user when it was last scheduled
+    ///
+    /// # Arguments
+    /// * `user_address` - Ethereum address of the user
+    ///
+    /// # Returns
+    /// * `UserCurrentLocation` - Tier of the user, `NotFound` if it is unknown to the scheduler
+    ///   or was only prioritized
+    pub fn location(&self, user_address: &str) -> UserCurrentLocation {
+        self.queue
+            .lock()
+            .expect("update scheduler poisoned")
+            .users
+            .get(user_address)
+            .map(|scheduled_user| scheduled_user.location.clone())
+            .unwrap_or(UserCurrentLocation::NotFound)
+    }
+
+    /// Returns the unix timestamp (in seconds) of the next due user
+    ///
+    /// A replaced schedule may still be at the front of the queue, so the actual next
+    /// user can be due later than returned, never earlier.
+    ///
+    /// # Returns
+    /// * `Option<u64>` - Due time of the first entry in the queue, `None` if it is empty
+    pub fn next_due_at(&self) -> Option<u64> {
+        self.queue
+            .lock()
+            .expect("update scheduler poisoned")
+            .heap
+            .peek()
+            .map(|Reverse((due_at, _))| *due_at)
+    }
+
+    /// Returns the number of scheduled users
+    ///
+    /// # Returns
+    /// * `usize` - Number of users known to the scheduler
+    pub fn len(&self) -> usize {
+        self.queue
+            .lock()
+            .expect("update scheduler poisoned")
+            .users
+            .len()
+    }
+
+    /// Returns whether no user is scheduled
+    ///
+    /// # Returns
+    /// * `bool` - True if no user is known to the scheduler
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Waits until users are pushed to the front of the queue
+    pub async fn prioritized(&self) {
+        self.prioritized.notified().await
+    }
+
+    /// Computes the delay before the next refresh of a user
+    ///
+    /// The delay grows continuously with the health factor: from
+    /// `LIQUIDATABLE_USERS_UPDATE_FREQUENCY` at 1.0 to `AT_RISK_USERS_UPDATE_FREQUENCY` at
+    /// `AT_RISK_HEALTH_FACTOR`, then up to `HEALTHY_USERS_UPDATE_FREQUENCY` once the health
+    /// factor is as far above `AT_RISK_HEALTH_FACTOR` as it is above 1.0. It is then
+    /// shortened for large debts, up to halved, and for users whose health factor moved
+    /// since the previous refresh.
+    ///
+    /// # Arguments
+    /// * `local_config` - Local configuration settings
+    /// * `health_factor` - Health factor of the user
+    /// * `total_debt_in_usd` - Debt of the user in USD
+    /// * `volatility` - Relative change of the health factor since the previous refresh
+    ///
+    /// # Returns
+    /// * `u64` - Delay in seconds, at least one second
+    fn next_update_delay(
+        local_config: &LocalConfig,
+        health_factor: Decimal,
+        total_debt_in_usd: Decimal,
+        volatility: Decimal,
+    ) -> u64 {
+        let liquidatable_delay = Decimal::from(local_config.liquidatable_users_update_frequency);
+        let at_risk_delay = Decimal::from(local_config.at_risk_users_update_frequency);
+        let healthy_delay = Decimal::from(local_config.healthy_users_update_frequency);
+        let at_risk_band =
+            (local_config.at_risk_health_factor - LIQUIDATION_THRESHOLD).max(Decimal::new(1, 2));
+
+        let base_delay = if health_factor <= LIQUIDATION_THRESHOLD {
+            liquidatable_delay
+        } else if health_factor <= local_config.at_risk_health_factor {
+            let progress = (health_factor - LIQUIDATION_THRESHOLD) / at_risk_band;
+            liquidatable_delay + (at_risk_delay - liquidatable_delay) * progress
+        } else {
+            let progress = ((health_factor - local_config.at_risk_health_factor) / at_risk_band)
+                .min(Decimal::ONE);
+            at_risk_delay + (healthy_delay - at_risk_delay) * progress
+        };
+
+        let total_debt_in_usd = total_debt_in_usd.max(Decimal::ZERO);
+        let debt_factor = Decimal::ONE
+            + total_debt_in_usd
+                .checked_div(total_debt_in_usd + PRIORITY_DEBT_IN_USD)
+                .unwrap_or_default();
+        let volatility_factor = Decimal::ONE + volatility.saturating_mul(VOLATILITY_SENSITIVITY);
+
+        let delay = base_delay / debt_factor / volatility_factor;
+
+        delay.to_u64().unwrap_or(1).max(1)
+    }
+
+    /// Adds a schedule to the queue, replacing the previous one of the user
+    ///
+    /// The queue is rebuilt from the current schedules once replaced entries make up
+    /// most of it, so rescheduling users doesn't grow it without bound.
+    fn push(queue: &mut SchedulerQueue, user_address: String, scheduled_user: ScheduledUser) {
+        queue
+            .heap
+            .push(Reverse((scheduled_user.due_at, user_address.clone())));
+        queue.users.insert(user_address, scheduled_user);
+
+        if queue.heap.len() > 2 * queue.users.len() + MIN_QUEUE_SIZE_TO_COMPACT {
+            queue.heap = queue
+                .users
+                .iter()
+                .map(|(user_address, scheduled_user)| {
+                    Reverse((scheduled_user.due_at, user_address.clone()))
+                })
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_config() -> LocalConfig {
+        LocalConfig {
+            rpc_url: String::new(),
+            start_block: 0,
+            pool_address: String::new(),
+            pool_data_provider: String::new(),
+            pool_configurator: String::new(),
+            price_oracle: String::new(),
+            log_per_request: 0,
+            backfill_workers: 0,
+            multicall_max_calls: 0,
+            updater_concurrency: 0,
+            updater_cycle_budget: 0,
+            max_block_lag: 0,
+            reorg_confirmation_window: 0,
+            max_cap_on_health_factor: 100,
+            at_risk_health_factor: Decimal::TWO,
+            liquidatable_users_update_frequency: 30,
+            at_risk_users_update_frequency: 120,
+            healthy_users_update_frequency: 3600,
+            reserves_refresh_frequency: 0,
+            price_poll_interval: 0,
+            price_change_threshold: Decimal::ZERO,
+            health_factor_change_threshold: Decimal::ZERO,
+            api_address: String::new(),
+            readiness_block_lag_factor: 0,
+            readiness_liquidatable_staleness_factor: 0,
+        }
+    }
+
+    fn user(user_address: &str, health_factor: Decimal) -> UserDetails {
+        let current_location = if health_factor < Decimal::ONE {
+            UserCurrentLocation::Liquidatable
+        } else if health_factor <= Decimal::TWO {
+            UserCurrentLocation::AtRisk
+        } else {
+            UserCurrentLocation::Healthy
+        };
+
+        UserDetails {
+            id: 0,
+            user_address: user_address.to_string(),
+            last_updated_block_number: 0,
+            health_factor,
+            total_collateral_value_in_usd: Decimal::ZERO,
+            total_debt_value_in_usd: Decimal::ZERO,
+            emode_category: 0,
+            is_in_isolation_mode: false,
+            has_siloed_borrowing: false,
+            leading_collateral_reserve: String::new(),
+            leading_debt_reserve: String::new(),
+            leading_collateral_reserve_value: Decimal::ZERO,
+            leading_debt_reserve_value: Decimal::ZERO,
+            projected_health_factor: health_factor,
+            timestamp: Default::default(),
+            current_location,
+        }
+    }
+
+    fn delay(health_factor: Decimal, total_debt_in_usd: Decimal, volatility: Decimal) -> u64 {
+        UpdateScheduler::next_update_delay(
+            &local_config(),
+            health_factor,
+            total_debt_in_usd,
+            volatility,
+        )
+    }
+
+    #[test]
+    fn delay_grows_with_the_health_factor() {
+        assert_eq!(delay(Decimal::new(5, 1), Decimal::ZERO, Decimal::ZERO), 30);
+        assert_eq!(delay(Decimal::ONE, Decimal::ZERO, Decimal::ZERO), 30);
+        assert_eq!(delay(Decimal::new(15, 1), Decimal::ZERO, Decimal::ZERO), 75);
+        assert_eq!(delay(Decimal::TWO, Decimal::ZERO, Decimal::ZERO), 120);
+        assert_eq!(
+            delay(Decimal::new(25, 1), Decimal::ZERO, Decimal::ZERO),
+            1860
+        );
+        assert_eq!(delay(Decimal::from(3), Decimal::ZERO, Decimal::ZERO), 3600);
+        assert_eq!(
+            delay(Decimal::from(100), Decimal::ZERO, Decimal::ZERO),
+            3600
+        );
+    }
+
+    #[test]
+    fn large_debts_shorten_the_delay_up_to_half() {
+        assert_eq!(delay(Decimal::TWO, PRIORITY_DEBT_IN_USD, Decimal::ZERO), 80);
+        assert_eq!(
+            delay(
+                Decimal::from(3),
+                Decimal::from(1_000_000_000_000u64),
+                Decimal::ZERO
+            ),
+            1800
+        );
+        // Negative debts don't lengthen the delay
+        assert_eq!(
+            delay(Decimal::TWO, Decimal::from(-1_000), Decimal::ZERO),
+            120
+        );
+    }
+
+    #[test]
+    fn volatile_health_factors_shorten_the_delay() {
+        assert_eq!(delay(Decimal::TWO, Decimal::ZERO, Decimal::new(1, 1)), 60);
+    }
+
+    #[test]
+    fn delay_is_at_least_one_second() {
+        assert_eq!(
+            delay(Decimal::new(5, 1), PRIORITY_DEBT_IN_USD, Decimal::from(100)),
+            1
+        );
+    }
+
+    #[test]
+    fn pops_due_users_most_overdue_first() {
+        let scheduler = UpdateScheduler::new();
+        let local_config = local_config();
+        scheduler.schedule(&local_config, &user("0xa", Decimal::TWO), 1_000);
+        scheduler.schedule(&local_config, &user("0xb", Decimal::ONE), 1_000);
+        scheduler.schedule(&local_config, &user("0xc", Decimal::from(3)), 1_000);
+
+        assert!(scheduler.pop_due(1_029, 10).is_empty());
+        assert_eq!(scheduler.next_due_at(), Some(1_030));
+        assert_eq!(scheduler.pop_due(1_030, 10), vec!["0xb"]);
+        assert_eq!(scheduler.pop_due(5_000, 10), vec!["0xa", "0xc"]);
+    }
+
+    #[test]
+    fn users_due_at_the_same_time_are_all_popped() {
+        let scheduler = UpdateScheduler::new();
+        let local_config = local_config();
+        for user_address in ["0xc", "0xa", "0xb"] {
+            scheduler.schedule(&local_config, &user(user_address, Decimal::ONE), 1_000);
+        }
+
+        assert_eq!(scheduler.pop_due(1_030, 2), vec!["0xa", "0xb"]);
+        assert_eq!(scheduler.pop_due(1_030, 2), vec!["0xc"]);
+        assert!(scheduler.pop_due(1_030, 2).is_empty());
+    }
+
+    #[test]
+    fn popped_users_are_only_popped_again_once_rescheduled() {
+        let scheduler = UpdateScheduler::new();
+        let local_config = local_config();
+        scheduler.schedule(&local_config, &user("0xa", Decimal::ONE), 1_000);
+
+        assert_eq!(scheduler.pop_due(1_030, 10), vec!["0xa"]);
+        assert!(scheduler.pop_due(10_000, 10).is_empty());
+        assert_eq!(scheduler.len(), 1);
+
+        scheduler.schedule(&local_config, &user("0xa", Decimal::ONE), 2_000);
+        assert_eq!(scheduler.pop_due(2_030, 10), vec!["0xa"]);
+    }
+
+    #[test]
+    fn replaced_and_removed_schedules_are_skipped() {
+        let scheduler = UpdateScheduler::new();
+        let local_config = local_config();
+        scheduler.schedule(&local_config, &user("0xa", Decimal::ONE), 1_000);
+        scheduler.schedule(&local_config, &user("0xa", Decimal::ONE), 2_000);
+        scheduler.schedule(&local_config, &user("0xb", Decimal::ONE), 1_000);
+        scheduler.remove("0xb");
+
+        assert!(scheduler.pop_due(1_030, 10).is_empty());
+        assert_eq!(scheduler.pop_due(2_030, 10), vec!["0xa"]);
+    }
+
+    #[test]
+    fn prioritized_users_are_due_immediately() {
+        let scheduler = UpdateScheduler::new();
+        let local_config = local_config();
+        scheduler.schedule(&local_config, &user("0xa", Decimal::from(3)), 1_000);
+        scheduler.prioritize(&["0xa".to_string(), "0xb".to_string()]);
+
+        assert_eq!(scheduler.next_due_at(), Some(0));
+        assert_eq!(scheduler.pop_due(0, 10), vec!["0xa", "0xb"]);
+    }
+
+    #[test]
+    fn keeps_the_tier_of_scheduled_users() {
+        let scheduler = UpdateScheduler::new();
+        let local_config = local_config();
+        scheduler.schedule(&local_config, &user("0xa", Decimal::new(5, 1)), 1_000);
+        scheduler.prioritize(&["0xa".to_string(), "0xb".to_string()]);
+
+        assert_eq!(scheduler.location("0xa"), UserCurrentLocation::Liquidatable);
+        assert_eq!(scheduler.location("0xb"), UserCurrentLocation::NotFound);
+        assert_eq!(scheduler.location("0xc"), UserCurrentLocation::NotFound);
+    }
+}
@@ -1,4 +1,19 @@
-pub const LIQUIDATION_THRESHOLD: f64 = 1.0;
+use rust_decimal::Decimal;
+
+pub const LIQUIDATION_THRESHOLD: Decimal = Decimal::ONE;
 pub const HEALTH_FACTOR_DECIMALS: u8 = 18;
 pub const USD_VALUE_DECIMALS: u8 = 8;
-pub const TOKEN_BALANCE_DECIMALS: u8 = 18;
+/// Decimals of Aave percentages (basis points, 10000 = 100%)
+pub const PERCENTAGE_DECIMALS: u8 = 4;
+/// Decimals of isolation mode debt ceilings and total debts (USD cents)
+pub const DEBT_CEILING_DECIMALS: u8 = 2;
+/// Health factor at or below which a position can be liquidated in full
+pub const CLOSE_FACTOR_HF_THRESHOLD: Decimal = Decimal::from_parts(95, 0, 0, false, 2);
+/// Share of a debt that can be repaid in a single liquidation
+pub const DEFAULT_LIQUIDATION_CLOSE_FACTOR: Decimal = Decimal::from_parts(5, 0, 0, false, 1);
+/// Share of a debt that can be repaid in a single liquidation at or below `CLOSE_FACTOR_HF_THRESHOLD`
+pub const MAX_LIQUIDATION_CLOSE_FACTOR: Decimal = Decimal::ONE;
+/// Debt in USD at which the refresh delay of a user is shortened by a third, up to halved for the largest debts
+pub const PRIORITY_DEBT_IN_USD: Decimal = Decimal::from_parts(100_000, 0, 0, false, 0);
+/// How strongly a health factor move shortens the refresh delay, a 10% move halves it
+pub const VOLATILITY_SENSITIVITY: Decimal = Decimal::from_parts(10, 0, 0, false, 0);
@@ -1,3 +1,6 @@
+// The bindings mirror the contract functions, whatever their number of arguments
+#![allow(clippy::too_many_arguments)]
+
 use alloy::sol;
 
 // Aave Pool Contract
@@ -18,6 +21,33 @@ sol!(
     "abis/aave_pool_data_provider.json"
 );
 
+// Aave Pool Configurator Contract (reserve configuration events)
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc, extra_methods)]
+    #[derive(Debug)]
+    AavePoolConfiguratorContract,
+    "abis/aave_pool_configurator.json"
+);
+
+// Aave Pool Addresses Provider Contract (resolves the pool configurator)
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc, extra_methods)]
+    #[derive(Debug)]
+    AavePoolAddressesProviderContract,
+    "abis/aave_pool_addresses_provider.json"
+);
+
+// Aave Oracle Contract
+sol!(
+    #[allow(missing_docs)]
+    #[sol(rpc, extra_methods)]
+    #[derive(Debug)]
+    AaveOracleContract,
+    "abis/aave_oracle.json"
+);
+
 // --------- Multicall ---------
 sol!(
     #[allow(missing_docs)]
@@ -1,39 +1,96 @@
 use alloy::primitives::U256;
+use rust_decimal::Decimal;
 
-pub fn divide_by_precision_f64(value: U256, precision: u8) -> f64 {
-    let ray = U256::from(10).pow(U256::from(precision));
-
-    // Perform integer division and get both quotient and remainder
-    let quotient = match value.checked_div(ray) {
-        Some(q) => q,
-        None => return f64::MAX,
-    };
-
-    let remainder = match value.checked_rem(ray) {
-        Some(r) => r,
-        None => return f64::MAX,
-    };
-
-    // Try to convert quotient to u128, return MAX if too large
-    let quotient_u128 = match u128::try_from(quotient) {
-        Ok(q) => q,
-        Err(_) => return f64::MAX,
-    };
-
-    // Try to convert remainder to u128, return MAX if too large
-    let remainder_u128 = match u128::try_from(remainder) {
-        Ok(r) => r,
-        Err(_) => return f64::MAX,
-    };
-
-    let ray_u128 = match u128::try_from(ray) {
-        Ok(r) => r,
-        Err(_) => return f64::MAX,
-    };
-
-    // Convert to f64 and combine
-    let quotient_f64 = quotient_u128 as f64;
-    let remainder_f64 = (remainder_u128 as f64) / (ray_u128 as f64);
-
-    quotient_f64 + remainder_f64
+/// Largest mantissa a `Decimal` can hold (96 bits)
+const MAX_DECIMAL_MANTISSA: u128 = (1 << 96) - 1;
+
+/// Largest number of decimal places a `Decimal` can hold
+const MAX_DECIMAL_SCALE: u32 = 28;
+
+/// Converts a fixed-point on-chain value into a `Decimal` with the given number of decimals
+///
+/// The conversion is exact as long as the value fits in 28 significant digits. Beyond
+/// that the least significant digits are truncated, and values too large to be
+/// represented at all saturate to `Decimal::MAX`.
+///
+/// # Arguments
+/// * `value` - Raw on-chain value
+/// * `precision` - Number of decimals of the value
+///
+/// # Returns
+/// * `Decimal` - The value divided by 10^precision
+pub fn divide_by_precision(value: U256, precision: u8) -> Decimal {
+    let mut mantissa = value;
+    let mut scale = precision as u32;
+
+    while scale > MAX_DECIMAL_SCALE || mantissa > U256::from(MAX_DECIMAL_MANTISSA) {
+        if scale == 0 {
+            return Decimal::MAX;
+        }
+        mantissa /= U256::from(10);
+        scale -= 1;
+    }
+
+    Decimal::from_i128_with_scale(mantissa.to::<i128>(), scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divide_by_precision_is_exact_within_28_digits() {
+        assert_eq!(
+            divide_by_precision(U256::from(1_500_000u64), 6),
+            Decimal::new(15, 1)
+        );
+        assert_eq!(
+            divide_by_precision(U256::from(1_234_567_890_123_456_789u64), 18),
+            Decimal::from_i128_with_scale(1_234_567_890_123_456_789, 18)
+        );
+        assert_eq!(divide_by_precision(U256::ZERO, 18), Decimal::ZERO);
+        assert_eq!(divide_by_precision(U256::from(42u64), 0), Decimal::from(42));
+    }
+
+    #[test]
+    fn divide_by_precision_keeps_the_largest_mantissa() {
+        assert_eq!(
+            divide_by_precision(U256::from(MAX_DECIMAL_MANTISSA), 0),
+            Decimal::MAX
+        );
+        assert_eq!(
+            divide_by_precision(U256::from(MAX_DECIMAL_MANTISSA), 10),
+            Decimal::from_i128_with_scale(MAX_DECIMAL_MANTISSA as i128, 10)
+        );
+    }
+
+    #[test]
+    fn divide_by_precision_truncates_the_least_significant_digits() {
+        // 10^30 + 1 with 18 decimals has 31 significant digits, the trailing 1 is dropped
+        let value = U256::from(10u64).pow(U256::from(30)) + U256::from(1);
+        assert_eq!(
+            divide_by_precision(value, 18),
+            Decimal::from(1_000_000_000_000u64)
+        );
+    }
+
+    #[test]
+    fn divide_by_precision_truncates_beyond_28_decimals() {
+        assert_eq!(
+            divide_by_precision(U256::from(123u64), 30),
+            Decimal::new(1, 28)
+        );
+        assert_eq!(divide_by_precision(U256::from(99u64), 30), Decimal::ZERO);
+    }
+
+    #[test]
+    fn divide_by_precision_saturates_values_too_large() {
+        assert_eq!(
+            divide_by_precision(U256::from(MAX_DECIMAL_MANTISSA) + U256::from(1), 0),
+            Decimal::MAX
+        );
+        assert_eq!(divide_by_precision(U256::MAX, 0), Decimal::MAX);
+        // 2^256 - 1 still has 51 integer digits with 27 decimals
+        assert_eq!(divide_by_precision(U256::MAX, 27), Decimal::MAX);
+    }
 }
@@ -3,6 +3,20 @@ pub use sea_orm_migration::prelude::*;
 mod m20220101_000001_create_user_tables;
 mod m20220101_000002_create_user_debt_collateral;
 mod m20220101_000003_create_last_block_indexed;
+mod m20220101_000004_create_indexed_blocks;
+mod m20220101_000005_create_backfill_progress;
+mod m20220101_000006_create_accounts;
+mod m20220101_000007_use_numeric_columns;
+mod m20220101_000008_create_reserves;
+mod m20220101_000009_add_debt_rate_mode;
+mod m20220101_000010_add_position_value;
+mod m20220101_000011_create_liquidation_opportunities;
+mod m20220101_000012_add_account_emode_category;
+mod m20220101_000013_add_isolation_and_siloed_borrowing;
+mod m20220101_000014_add_reserve_registry_columns;
+mod m20220101_000015_add_projected_health_factor;
+mod m20220101_000016_add_position_usage_as_collateral;
+mod m20220101_000017_add_reserve_emode_liquidation_threshold;
 
 pub struct Migrator;
 
@@ -13,6 +27,20 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_create_user_tables::Migration),
             Box::new(m20220101_000002_create_user_debt_collateral::Migration),
             Box::new(m20220101_000003_create_last_block_indexed::Migration),
+            Box::new(m20220101_000004_create_indexed_blocks::Migration),
+            Box::new(m20220101_000005_create_backfill_progress::Migration),
+            Box::new(m20220101_000006_create_accounts::Migration),
+            Box::new(m20220101_000007_use_numeric_columns::Migration),
+            Box::new(m20220101_000008_create_reserves::Migration),
+            Box::new(m20220101_000009_add_debt_rate_mode::Migration),
+            Box::new(m20220101_000010_add_position_value::Migration),
+            Box::new(m20220101_000011_create_liquidation_opportunities::Migration),
+            Box::new(m20220101_000012_add_account_emode_category::Migration),
+            Box::new(m20220101_000013_add_isolation_and_siloed_borrowing::Migration),
+            Box::new(m20220101_000014_add_reserve_registry_columns::Migration),
+            Box::new(m20220101_000015_add_projected_health_factor::Migration),
+            Box::new(m20220101_000016_add_position_usage_as_collateral::Migration),
+            Box::new(m20220101_000017_add_reserve_emode_liquidation_threshold::Migration),
         ]
     }
 }
@@ -0,0 +1,84 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create the indexed_blocks table (block hashes inside the confirmation window)
+        manager
+            .create_table(
+                Table::create()
+                    .table(IndexedBlocks::Table)
+                    .if_not_exists()
+                    .col(pk_auto(IndexedBlocks::Id))
+                    .col(integer(IndexedBlocks::BlockNumber))
+                    .col(string(IndexedBlocks::BlockHash))
+                    .col(string(IndexedBlocks::ParentHash))
+                    .col(timestamp(IndexedBlocks::Timestamp))
+                    .index(
+                        Index::create()
+                            .name("idx_indexed_blocks_block_number")
+                            .unique()
+                            .col(IndexedBlocks::BlockNumber),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Create the indexed_block_users table (users touched inside the confirmation window)
+        manager
+            .create_table(
+                Table::create()
+                    .table(IndexedBlockUsers::Table)
+                    .if_not_exists()
+                    .col(pk_auto(IndexedBlockUsers::Id))
+                    .col(integer(IndexedBlockUsers::BlockNumber))
+                    .col(string(IndexedBlockUsers::UserAddress))
+                    .col(timestamp(IndexedBlockUsers::Timestamp))
+                    .index(
+                        Index::create()
+                            .name("idx_indexed_block_users_unique")
+                            .unique()
+                            .col(IndexedBlockUsers::BlockNumber)
+                            .col(IndexedBlockUsers::UserAddress),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Drop tables in reverse order
+        manager
+            .drop_table(Table::drop().table(IndexedBlockUsers::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(IndexedBlocks::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum IndexedBlocks {
+    Table,
+    Id,
+    BlockNumber,
+    BlockHash,
+    ParentHash,
+    Timestamp,
+}
+
+#[derive(DeriveIden)]
+enum IndexedBlockUsers {
+    Table,
+    Id,
+    BlockNumber,
+    UserAddress,
+    Timestamp,
+}
@@ -0,0 +1,85 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create the backfill_ranges table (per-range progress of the historical backfill)
+        manager
+            .create_table(
+                Table::create()
+                    .table(BackfillRanges::Table)
+                    .if_not_exists()
+                    .col(pk_auto(BackfillRanges::Id))
+                    .col(integer(BackfillRanges::FromBlock))
+                    .col(integer(BackfillRanges::ToBlock))
+                    .col(integer(BackfillRanges::SyncBlock))
+                    .col(boolean(BackfillRanges::IsCompleted))
+                    .col(timestamp(BackfillRanges::Timestamp))
+                    .index(
+                        Index::create()
+                            .name("idx_backfill_ranges_from_block")
+                            .unique()
+                            .col(BackfillRanges::FromBlock),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Create the backfill_users table (distinct users discovered by the backfill)
+        manager
+            .create_table(
+                Table::create()
+                    .table(BackfillUsers::Table)
+                    .if_not_exists()
+                    .col(pk_auto(BackfillUsers::Id))
+                    .col(string(BackfillUsers::UserAddress))
+                    .col(boolean(BackfillUsers::IsRefreshed))
+                    .col(timestamp(BackfillUsers::Timestamp))
+                    .index(
+                        Index::create()
+                            .name("idx_backfill_users_user_address")
+                            .unique()
+                            .col(BackfillUsers::UserAddress),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Drop tables in reverse order
+        manager
+            .drop_table(Table::drop().table(BackfillUsers::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(BackfillRanges::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum BackfillRanges {
+    Table,
+    Id,
+    FromBlock,
+    ToBlock,
+    SyncBlock,
+    IsCompleted,
+    Timestamp,
+}
+
+#[derive(DeriveIden)]
+enum BackfillUsers {
+    Table,
+    Id,
+    UserAddress,
+    IsRefreshed,
+    Timestamp,
+}
@@ -0,0 +1,208 @@
+use sea_orm_migration::{
+    prelude::{extension::postgres::Type, *},
+    schema::*,
+};
+
+/// The per-tier tables replaced by the `accounts` table, with the tier each one held
+const TIER_TABLES: [(&str, &str); 3] = [
+    ("liquidatable_accounts", "liquidatable"),
+    ("at_risk_accounts", "at_risk"),
+    ("healthy_accounts", "healthy"),
+];
+
+/// Columns shared by the `accounts` table and the per-tier tables, except the id
+const ACCOUNT_COLUMNS: &str = "user_address, last_updated_block_number, health_factor, \
+    total_collateral_value_in_usd, total_debt_value_in_usd, leading_collateral_reserve, \
+    leading_debt_reserve, leading_collateral_reserve_value, leading_debt_reserve_value, timestamp";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Create the account_tier enum type
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(AccountTier::Enum)
+                    .values([
+                        AccountTier::Liquidatable,
+                        AccountTier::AtRisk,
+                        AccountTier::Healthy,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        // Create the accounts table (all users, whatever their tier)
+        manager
+            .create_table(
+                Table::create()
+                    .table(Accounts::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Accounts::Id))
+                    .col(string(Accounts::UserAddress))
+                    .col(enumeration(
+                        Accounts::Tier,
+                        AccountTier::Enum,
+                        [
+                            AccountTier::Liquidatable,
+                            AccountTier::AtRisk,
+                            AccountTier::Healthy,
+                        ],
+                    ))
+                    .col(integer(Accounts::LastUpdatedBlockNumber))
+                    .col(float(Accounts::HealthFactor))
+                    .col(float(Accounts::TotalCollateralValueInUsd))
+                    .col(float(Accounts::TotalDebtValueInUsd))
+                    .col(string(Accounts::LeadingCollateralReserve))
+                    .col(string(Accounts::LeadingDebtReserve))
+                    .col(float(Accounts::LeadingCollateralReserveValue))
+                    .col(float(Accounts::LeadingDebtReserveValue))
+                    .col(timestamp(Accounts::Timestamp))
+                    .index(
+                        Index::create()
+                            .name("idx_accounts_user_address")
+                            .unique()
+                            .col(Accounts::UserAddress),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_accounts_tier_health_factor")
+                    .table(Accounts::Table)
+                    .col(Accounts::Tier)
+                    .col(Accounts::HealthFactor)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Carry over the existing rows, keeping the most recent one if a user is in several tables.
+        // Ids were generated by three different sequences, so the rows get new ones.
+        for (table, tier) in TIER_TABLES {
+            db.execute_unprepared(&format!(
+                "INSERT INTO accounts ({columns}, tier) \
+                 SELECT {columns}, '{tier}'::account_tier FROM {table} \
+                 ON CONFLICT (user_address) DO UPDATE SET \
+                 tier = EXCLUDED.tier, \
+                 last_updated_block_number = EXCLUDED.last_updated_block_number, \
+                 health_factor = EXCLUDED.health_factor, \
+                 total_collateral_value_in_usd = EXCLUDED.total_collateral_value_in_usd, \
+                 total_debt_value_in_usd = EXCLUDED.total_debt_value_in_usd, \
+                 leading_collateral_reserve = EXCLUDED.leading_collateral_reserve, \
+                 leading_debt_reserve = EXCLUDED.leading_debt_reserve, \
+                 leading_collateral_reserve_value = EXCLUDED.leading_collateral_reserve_value, \
+                 leading_debt_reserve_value = EXCLUDED.leading_debt_reserve_value, \
+                 timestamp = EXCLUDED.timestamp \
+                 WHERE EXCLUDED.last_updated_block_number > accounts.last_updated_block_number",
+                columns = ACCOUNT_COLUMNS,
+            ))
+            .await?;
+        }
+
+        // Replace the per-tier tables with views so existing readers keep working
+        for (table, tier) in TIER_TABLES {
+            manager
+                .drop_table(Table::drop().table(Alias::new(table)).to_owned())
+                .await?;
+            db.execute_unprepared(&format!(
+                "CREATE VIEW {table} AS SELECT id, {columns} FROM accounts WHERE tier = '{tier}'",
+                columns = ACCOUNT_COLUMNS,
+            ))
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Recreate the per-tier tables from the accounts table
+        for (table, tier) in TIER_TABLES {
+            db.execute_unprepared(&format!("DROP VIEW IF EXISTS {table}"))
+                .await?;
+            manager
+                .create_table(
+                    Table::create()
+                        .table(Alias::new(table))
+                        .if_not_exists()
+                        .col(pk_auto(Accounts::Id))
+                        .col(string(Accounts::UserAddress))
+                        .col(integer(Accounts::LastUpdatedBlockNumber))
+                        .col(float(Accounts::HealthFactor))
+                        .col(float(Accounts::TotalCollateralValueInUsd))
+                        .col(float(Accounts::TotalDebtValueInUsd))
+                        .col(string(Accounts::LeadingCollateralReserve))
+                        .col(string(Accounts::LeadingDebtReserve))
+                        .col(float(Accounts::LeadingCollateralReserveValue))
+                        .col(float(Accounts::LeadingDebtReserveValue))
+                        .col(timestamp(Accounts::Timestamp))
+                        .index(
+                            Index::create()
+                                .name(format!("idx_{table}_user_address"))
+                                .unique()
+                                .col(Accounts::UserAddress),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+            db.execute_unprepared(&format!(
+                "INSERT INTO {table} (id, {columns}) \
+                 SELECT id, {columns} FROM accounts WHERE tier = '{tier}'",
+                columns = ACCOUNT_COLUMNS,
+            ))
+            .await?;
+            db.execute_unprepared(&format!(
+                "SELECT setval(pg_get_serial_sequence('{table}', 'id'), \
+                 COALESCE((SELECT MAX(id) FROM {table}), 0) + 1, false)"
+            ))
+            .await?;
+        }
+
+        manager
+            .drop_table(Table::drop().table(Accounts::Table).to_owned())
+            .await?;
+        manager
+            .drop_type(Type::drop().name(AccountTier::Enum).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Accounts {
+    Table,
+    Id,
+    UserAddress,
+    Tier,
+    LastUpdatedBlockNumber,
+    HealthFactor,
+    TotalCollateralValueInUsd,
+    TotalDebtValueInUsd,
+    LeadingCollateralReserve,
+    LeadingDebtReserve,
+    LeadingCollateralReserveValue,
+    LeadingDebtReserveValue,
+    Timestamp,
+}
+
+#[derive(DeriveIden)]
+enum AccountTier {
+    #[sea_orm(iden = "account_tier")]
+    Enum,
+    #[sea_orm(iden = "liquidatable")]
+    Liquidatable,
+    #[sea_orm(iden = "at_risk")]
+    AtRisk,
+    #[sea_orm(iden = "healthy")]
+    Healthy,
+}
@@ -0,0 +1,127 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+/// The compatibility views over the `accounts` table, with the tier each one shows
+const TIER_VIEWS: [(&str, &str); 3] = [
+    ("liquidatable_accounts", "liquidatable"),
+    ("at_risk_accounts", "at_risk"),
+    ("healthy_accounts", "healthy"),
+];
+
+/// Columns exposed by the compatibility views
+const VIEW_COLUMNS: &str = "id, user_address, last_updated_block_number, health_factor, \
+    total_collateral_value_in_usd, total_debt_value_in_usd, leading_collateral_reserve, \
+    leading_debt_reserve, leading_collateral_reserve_value, leading_debt_reserve_value, timestamp";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The views depend on the columns whose type changes
+        drop_views(manager).await?;
+
+        // Store amounts, USD values and health factors as exact NUMERIC values
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .modify_column(decimal(Accounts::HealthFactor))
+                    .modify_column(decimal(Accounts::TotalCollateralValueInUsd))
+                    .modify_column(decimal(Accounts::TotalDebtValueInUsd))
+                    .modify_column(decimal(Accounts::LeadingCollateralReserveValue))
+                    .modify_column(decimal(Accounts::LeadingDebtReserveValue))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Keep the raw on-chain amount next to the normalized one, unknown for existing
+        // positions until they are refreshed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserDebtCollateral::Table)
+                    .modify_column(decimal(UserDebtCollateral::Amount))
+                    .add_column(string_null(UserDebtCollateral::AmountRaw))
+                    .to_owned(),
+            )
+            .await?;
+
+        create_views(manager).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        drop_views(manager).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserDebtCollateral::Table)
+                    .drop_column(UserDebtCollateral::AmountRaw)
+                    .modify_column(float(UserDebtCollateral::Amount))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .modify_column(float(Accounts::HealthFactor))
+                    .modify_column(float(Accounts::TotalCollateralValueInUsd))
+                    .modify_column(float(Accounts::TotalDebtValueInUsd))
+                    .modify_column(float(Accounts::LeadingCollateralReserveValue))
+                    .modify_column(float(Accounts::LeadingDebtReserveValue))
+                    .to_owned(),
+            )
+            .await?;
+
+        create_views(manager).await?;
+
+        Ok(())
+    }
+}
+
+/// Drops the compatibility views over the `accounts` table
+async fn drop_views(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    for (view, _) in TIER_VIEWS {
+        manager
+            .get_connection()
+            .execute_unprepared(&format!("DROP VIEW IF EXISTS {view}"))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Creates the compatibility views over the `accounts` table
+async fn create_views(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+    for (view, tier) in TIER_VIEWS {
+        manager
+            .get_connection()
+            .execute_unprepared(&format!(
+                "CREATE VIEW {view} AS SELECT {columns} FROM accounts WHERE tier = '{tier}'",
+                columns = VIEW_COLUMNS,
+            ))
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(DeriveIden)]
+enum Accounts {
+    Table,
+    HealthFactor,
+    TotalCollateralValueInUsd,
+    TotalDebtValueInUsd,
+    LeadingCollateralReserveValue,
+    LeadingDebtReserveValue,
+}
+
+#[derive(DeriveIden)]
+enum UserDebtCollateral {
+    Table,
+    Amount,
+    AmountRaw,
+}
@@ -0,0 +1,48 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create the reserves table (configuration of every Aave reserve)
+        manager
+            .create_table(
+                Table::create()
+                    .table(Reserves::Table)
+                    .if_not_exists()
+                    .col(pk_auto(Reserves::Id))
+                    .col(string(Reserves::ReserveAddress))
+                    .col(integer(Reserves::Decimals))
+                    .col(timestamp(Reserves::Timestamp))
+                    .index(
+                        Index::create()
+                            .name("idx_reserves_reserve_address")
+                            .unique()
+                            .col(Reserves::ReserveAddress),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Reserves::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Reserves {
+    Table,
+    Id,
+    ReserveAddress,
+    Decimals,
+    Timestamp,
+}
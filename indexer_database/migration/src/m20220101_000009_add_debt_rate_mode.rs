@@ -0,0 +1,84 @@
+use sea_orm_migration::prelude::{extension::postgres::Type, *};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Create the rate_mode enum type, `none` is used for collateral positions
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(RateMode::Enum)
+                    .values([RateMode::None, RateMode::Stable, RateMode::Variable])
+                    .to_owned(),
+            )
+            .await?;
+
+        // Only variable debt was tracked so far
+        db.execute_unprepared(
+            "ALTER TABLE user_debt_collateral ADD COLUMN rate_mode rate_mode NOT NULL DEFAULT 'none'",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE user_debt_collateral SET rate_mode = 'variable' WHERE NOT is_collateral",
+        )
+        .await?;
+        db.execute_unprepared(
+            "ALTER TABLE user_debt_collateral ALTER COLUMN rate_mode DROP DEFAULT",
+        )
+        .await?;
+
+        // A user can now hold both a stable and a variable debt position in the same reserve
+        db.execute_unprepared(
+            "ALTER TABLE user_debt_collateral DROP CONSTRAINT idx_user_debt_collateral_unique",
+        )
+        .await?;
+        db.execute_unprepared(
+            "ALTER TABLE user_debt_collateral ADD CONSTRAINT idx_user_debt_collateral_unique \
+             UNIQUE (user_address, reserve_address, is_collateral, rate_mode)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        db.execute_unprepared("DELETE FROM user_debt_collateral WHERE rate_mode = 'stable'")
+            .await?;
+        db.execute_unprepared(
+            "ALTER TABLE user_debt_collateral DROP CONSTRAINT idx_user_debt_collateral_unique",
+        )
+        .await?;
+        db.execute_unprepared(
+            "ALTER TABLE user_debt_collateral ADD CONSTRAINT idx_user_debt_collateral_unique \
+             UNIQUE (user_address, reserve_address, is_collateral)",
+        )
+        .await?;
+        db.execute_unprepared("ALTER TABLE user_debt_collateral DROP COLUMN rate_mode")
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(RateMode::Enum).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum RateMode {
+    #[sea_orm(iden = "rate_mode")]
+    Enum,
+    #[sea_orm(iden = "none")]
+    None,
+    #[sea_orm(iden = "stable")]
+    Stable,
+    #[sea_orm(iden = "variable")]
+    Variable,
+}
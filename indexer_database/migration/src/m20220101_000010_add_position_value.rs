@@ -0,0 +1,41 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Base-currency value of each position, unknown for existing positions until
+        // they are refreshed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserDebtCollateral::Table)
+                    .add_column(decimal_null(UserDebtCollateral::ValueInUsd))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserDebtCollateral::Table)
+                    .drop_column(UserDebtCollateral::ValueInUsd)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserDebtCollateral {
+    Table,
+    ValueInUsd,
+}
@@ -0,0 +1,76 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Create the liquidation_opportunities table (best liquidation of every liquidatable account)
+        manager
+            .create_table(
+                Table::create()
+                    .table(LiquidationOpportunities::Table)
+                    .if_not_exists()
+                    .col(pk_auto(LiquidationOpportunities::Id))
+                    .col(string(LiquidationOpportunities::UserAddress))
+                    .col(integer(LiquidationOpportunities::LastUpdatedBlockNumber))
+                    .col(string(LiquidationOpportunities::CollateralReserve))
+                    .col(string(LiquidationOpportunities::DebtReserve))
+                    .col(decimal(LiquidationOpportunities::DebtToCover))
+                    .col(decimal(LiquidationOpportunities::DebtToCoverInUsd))
+                    .col(decimal(LiquidationOpportunities::CollateralToSeize))
+                    .col(decimal(LiquidationOpportunities::CollateralToSeizeInUsd))
+                    .col(decimal(LiquidationOpportunities::ProfitInUsd))
+                    .col(timestamp(LiquidationOpportunities::Timestamp))
+                    .index(
+                        Index::create()
+                            .name("idx_liquidation_opportunities_user_address")
+                            .unique()
+                            .col(LiquidationOpportunities::UserAddress),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_liquidation_opportunities_profit_in_usd")
+                    .table(LiquidationOpportunities::Table)
+                    .col(LiquidationOpportunities::ProfitInUsd)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(LiquidationOpportunities::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum LiquidationOpportunities {
+    Table,
+    Id,
+    UserAddress,
+    LastUpdatedBlockNumber,
+    CollateralReserve,
+    DebtReserve,
+    DebtToCover,
+    DebtToCoverInUsd,
+    CollateralToSeize,
+    CollateralToSeizeInUsd,
+    ProfitInUsd,
+    Timestamp,
+}
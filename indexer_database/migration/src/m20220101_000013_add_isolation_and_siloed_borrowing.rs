@@ -0,0 +1,82 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Isolation mode, siloed borrowing and caps of each reserve, existing reserves are
+        // assumed unconstrained until the configuration is reloaded at startup
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .add_column(decimal(Reserves::DebtCeiling).default(0))
+                    .add_column(decimal(Reserves::IsolationModeTotalDebt).default(0))
+                    .add_column(boolean(Reserves::SiloedBorrowing).default(false))
+                    .add_column(decimal(Reserves::BorrowCap).default(0))
+                    .add_column(decimal(Reserves::SupplyCap).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Constraints of each account, existing accounts are assumed unconstrained until
+        // they are refreshed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .add_column(boolean(Accounts::IsInIsolationMode).default(false))
+                    .add_column(boolean(Accounts::HasSiloedBorrowing).default(false))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .drop_column(Accounts::IsInIsolationMode)
+                    .drop_column(Accounts::HasSiloedBorrowing)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .drop_column(Reserves::DebtCeiling)
+                    .drop_column(Reserves::IsolationModeTotalDebt)
+                    .drop_column(Reserves::SiloedBorrowing)
+                    .drop_column(Reserves::BorrowCap)
+                    .drop_column(Reserves::SupplyCap)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Reserves {
+    Table,
+    DebtCeiling,
+    IsolationModeTotalDebt,
+    SiloedBorrowing,
+    BorrowCap,
+    SupplyCap,
+}
+
+#[derive(DeriveIden)]
+enum Accounts {
+    Table,
+    IsInIsolationMode,
+    HasSiloedBorrowing,
+}
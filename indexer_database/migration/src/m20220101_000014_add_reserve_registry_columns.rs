@@ -0,0 +1,80 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Token addresses, state and liquidation parameters of each reserve, filled in by
+        // the next reserve registry refresh
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .add_column(string(Reserves::Symbol).default(""))
+                    .add_column(string(Reserves::ATokenAddress).default(""))
+                    .add_column(string(Reserves::StableDebtTokenAddress).default(""))
+                    .add_column(string(Reserves::VariableDebtTokenAddress).default(""))
+                    .add_column(boolean(Reserves::IsActive).default(true))
+                    .add_column(boolean(Reserves::IsFrozen).default(false))
+                    .add_column(boolean(Reserves::IsPaused).default(false))
+                    .add_column(boolean(Reserves::BorrowingEnabled).default(false))
+                    .add_column(boolean(Reserves::UsageAsCollateralEnabled).default(false))
+                    .add_column(decimal(Reserves::Ltv).default(0))
+                    .add_column(decimal(Reserves::LiquidationThreshold).default(0))
+                    .add_column(decimal(Reserves::LiquidationBonus).default(0))
+                    .add_column(decimal(Reserves::LiquidationProtocolFee).default(0))
+                    .add_column(integer(Reserves::EmodeCategory).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .drop_column(Reserves::Symbol)
+                    .drop_column(Reserves::ATokenAddress)
+                    .drop_column(Reserves::StableDebtTokenAddress)
+                    .drop_column(Reserves::VariableDebtTokenAddress)
+                    .drop_column(Reserves::IsActive)
+                    .drop_column(Reserves::IsFrozen)
+                    .drop_column(Reserves::IsPaused)
+                    .drop_column(Reserves::BorrowingEnabled)
+                    .drop_column(Reserves::UsageAsCollateralEnabled)
+                    .drop_column(Reserves::Ltv)
+                    .drop_column(Reserves::LiquidationThreshold)
+                    .drop_column(Reserves::LiquidationBonus)
+                    .drop_column(Reserves::LiquidationProtocolFee)
+                    .drop_column(Reserves::EmodeCategory)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Reserves {
+    Table,
+    Symbol,
+    ATokenAddress,
+    StableDebtTokenAddress,
+    VariableDebtTokenAddress,
+    IsActive,
+    IsFrozen,
+    IsPaused,
+    BorrowingEnabled,
+    UsageAsCollateralEnabled,
+    Ltv,
+    LiquidationThreshold,
+    LiquidationBonus,
+    LiquidationProtocolFee,
+    EmodeCategory,
+}
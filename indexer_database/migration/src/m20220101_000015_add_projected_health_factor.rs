@@ -0,0 +1,98 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Last oracle price of each reserve, unknown until the price watcher reads it
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .add_column(decimal(Reserves::Price).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Health factor projected from the stored positions and cached prices, existing
+        // accounts start from their on-chain health factor
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .add_column(decimal(Accounts::ProjectedHealthFactor).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Accounts::Table)
+                    .value(
+                        Accounts::ProjectedHealthFactor,
+                        Expr::col(Accounts::HealthFactor),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_accounts_projected_health_factor")
+                    .table(Accounts::Table)
+                    .col(Accounts::ProjectedHealthFactor)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_accounts_projected_health_factor")
+                    .table(Accounts::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Accounts::Table)
+                    .drop_column(Accounts::ProjectedHealthFactor)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .drop_column(Reserves::Price)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Reserves {
+    Table,
+    Price,
+}
+
+#[derive(DeriveIden)]
+enum Accounts {
+    Table,
+    HealthFactor,
+    ProjectedHealthFactor,
+}
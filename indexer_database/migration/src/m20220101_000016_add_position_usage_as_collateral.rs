@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Collateral flag of each position, existing collateral positions are assumed
+        // enabled until they are refreshed
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserDebtCollateral::Table)
+                    .add_column(
+                        boolean(UserDebtCollateral::UsageAsCollateralEnabled).default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(UserDebtCollateral::Table)
+                    .value(UserDebtCollateral::UsageAsCollateralEnabled, true)
+                    .and_where(Expr::col(UserDebtCollateral::IsCollateral).eq(true))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(UserDebtCollateral::Table)
+                    .drop_column(UserDebtCollateral::UsageAsCollateralEnabled)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserDebtCollateral {
+    Table,
+    IsCollateral,
+    UsageAsCollateralEnabled,
+}
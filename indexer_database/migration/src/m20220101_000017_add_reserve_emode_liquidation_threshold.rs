@@ -0,0 +1,54 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Liquidation threshold of each reserve's e-mode category, existing reserves keep
+        // their own threshold until the reserve registry stores them again
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .add_column(decimal(Reserves::EmodeLiquidationThreshold).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Reserves::Table)
+                    .value(
+                        Reserves::EmodeLiquidationThreshold,
+                        Expr::col(Reserves::LiquidationThreshold),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Reserves::Table)
+                    .drop_column(Reserves::EmodeLiquidationThreshold)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Reserves {
+    Table,
+    LiquidationThreshold,
+    EmodeLiquidationThreshold,
+}
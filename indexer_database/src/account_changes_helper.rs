@@ -0,0 +1,170 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use sea_orm::{
+    prelude::Decimal, sqlx::postgres::PgListener, ActiveEnum, ConnectionTrait, DbBackend,
+    Statement, Value,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::entities::sea_orm_active_enums::AccountTier;
+
+/// Postgres channel the account tier changes are notified on
+pub const ACCOUNT_CHANGES_CHANNEL: &str = "account_changes";
+
+/// Maximum number of notifications sent by a single statement, keeps us below the Postgres bind parameter limit
+const MAX_NOTIFICATIONS_PER_STATEMENT: usize = 1000;
+
+/// A change of an account's tier, notified when the transaction writing it commits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountChange {
+    pub user_address: String,
+    /// Tier before the write, `None` for a new account
+    pub old_tier: Option<AccountTier>,
+    pub new_tier: AccountTier,
+    pub health_factor: Decimal,
+    pub block_number: i32,
+}
+
+/// JSON payload of a notification on `ACCOUNT_CHANGES_CHANNEL`
+///
+/// Tiers are named as in the `account_tier` enum and the health factor is a string,
+/// so no precision is lost, e.g.
+/// `{"user_address":"0x...","old_tier":"at_risk","new_tier":"liquidatable","health_factor":"0.98","block_number":123}`
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountChangePayload {
+    user_address: String,
+    old_tier: Option<String>,
+    new_tier: String,
+    health_factor: String,
+    block_number: i32,
+}
+
+impl From<&AccountChange> for AccountChangePayload {
+    fn from(change: &AccountChange) -> Self {
+        Self {
+            user_address: change.user_address.clone(),
+            old_tier: change.old_tier.map(|tier| tier.to_value()),
+            new_tier: change.new_tier.to_value(),
+            health_factor: change.health_factor.to_string(),
+            block_number: change.block_number,
+        }
+    }
+}
+
+impl TryFrom<AccountChangePayload> for AccountChange {
+    type Error = anyhow::Error;
+
+    fn try_from(payload: AccountChangePayload) -> Result<Self> {
+        Ok(Self {
+            old_tier: payload
+                .old_tier
+                .map(|tier| AccountTier::try_from_value(&tier))
+                .transpose()
+                .context("Invalid old tier")?,
+            new_tier: AccountTier::try_from_value(&payload.new_tier).context("Invalid new tier")?,
+            health_factor: Decimal::from_str(&payload.health_factor)
+                .context("Invalid health factor")?,
+            user_address: payload.user_address,
+            block_number: payload.block_number,
+        })
+    }
+}
+
+/// Notifies account changes on `ACCOUNT_CHANGES_CHANNEL`
+///
+/// Postgres delivers the notifications when the transaction commits, and drops them
+/// if it rolls back, so pass the transaction writing the accounts.
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `changes` - The account changes, in the order they are written
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error if the notifications can't be queued
+pub(crate) async fn notify_account_changes(
+    db: &impl ConnectionTrait,
+    changes: Vec<AccountChange>,
+) -> Result<()> {
+    for changes in changes.chunks(MAX_NOTIFICATIONS_PER_STATEMENT) {
+        let mut values: Vec<Value> = vec![ACCOUNT_CHANGES_CHANNEL.into()];
+        for change in changes {
+            values.push(serde_json::to_string(&AccountChangePayload::from(change))?.into());
+        }
+
+        let payloads = (2..=changes.len() + 1)
+            .map(|index| format!("(${}::text)", index))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                "SELECT pg_notify($1, payload) FROM (VALUES {}) AS payloads(payload)",
+                payloads
+            ),
+            values,
+        ))
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Subscribes to the account changes notified on `ACCOUNT_CHANGES_CHANNEL`
+///
+/// The listener holds a dedicated connection, outside of the connection pool, and
+/// reconnects on its own if the connection drops. Changes committed while it was
+/// disconnected are lost, so consumers should resync from the accounts table then.
+///
+/// # Example
+/// ```ignore
+/// let mut listener = AccountChangesListener::connect(&database_url).await?;
+/// loop {
+///     let change = listener.recv().await?;
+///     println!("{} moved to {:?}", change.user_address, change.new_tier);
+/// }
+/// ```
+pub struct AccountChangesListener {
+    listener: PgListener,
+}
+
+impl AccountChangesListener {
+    /// Connects to the database and listens on `ACCOUNT_CHANGES_CHANNEL`
+    ///
+    /// # Arguments
+    ///
+    /// * `database_url` - PostgreSQL connection string
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self>` - The listener, or an error if the connection fails
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        let mut listener = PgListener::connect(database_url)
+            .await
+            .context("Failed to connect the account changes listener")?;
+        listener
+            .listen(ACCOUNT_CHANGES_CHANNEL)
+            .await
+            .context(format!("Failed to listen on {}", ACCOUNT_CHANGES_CHANNEL))?;
+
+        Ok(Self { listener })
+    }
+
+    /// Waits for the next account change
+    ///
+    /// # Returns
+    ///
+    /// * `Result<AccountChange>` - The change, or an error if the connection can't be
+    ///   re-established or the payload is invalid
+    pub async fn recv(&mut self) -> Result<AccountChange> {
+        let notification = self.listener.recv().await?;
+        let payload: AccountChangePayload = serde_json::from_str(notification.payload()).context(
+            format!("Invalid account change payload {}", notification.payload()),
+        )?;
+
+        payload.try_into()
+    }
+}
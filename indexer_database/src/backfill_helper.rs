@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use tracing::info;
+
+use crate::entities::{backfill_ranges, backfill_users};
+
+/// Retrieves all planned backfill ranges, ordered by their starting block
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+///
+/// * `Result<Vec<backfill_ranges::Model>>` - Planned ranges, empty if no backfill is in progress
+pub async fn get_backfill_ranges(db: &impl ConnectionTrait) -> Result<Vec<backfill_ranges::Model>> {
+    Ok(backfill_ranges::Entity::find()
+        .order_by_asc(backfill_ranges::Column::FromBlock)
+        .all(db)
+        .await?)
+}
+
+/// Plans a new backfill by storing its block ranges
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `ranges` - Vector of inclusive (from_block, to_block) ranges
+/// * `sync_block` - Block at which the discovered users will be refreshed
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn create_backfill_ranges(
+    db: &impl ConnectionTrait,
+    ranges: Vec<(u64, u64)>,
+    sync_block: u64,
+) -> Result<()> {
+    if ranges.is_empty() {
+        return Ok(());
+    }
+
+    info!(
+        "Planning backfill of {} ranges up to block {}",
+        ranges.len(),
+        sync_block
+    );
+
+    let timestamp = chrono::Utc::now().naive_utc();
+    let models = ranges
+        .into_iter()
+        .map(|(from_block, to_block)| backfill_ranges::ActiveModel {
+            from_block: Set(from_block as i32),
+            to_block: Set(to_block as i32),
+            sync_block: Set(sync_block as i32),
+            is_completed: Set(false),
+            timestamp: Set(timestamp),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    backfill_ranges::Entity::insert_many(models)
+        .exec(db)
+        .await
+        .context("Failed to plan backfill ranges")?;
+
+    Ok(())
+}
+
+/// Stores the users discovered in a backfill range and marks the range as completed
+///
+/// Users already discovered by another range are ignored, so re-running a range
+/// after a crash is harmless.
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `range` - The completed backfill range
+/// * `user_addresses` - Users discovered in the range
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn complete_backfill_range(
+    db: &impl ConnectionTrait,
+    range: backfill_ranges::Model,
+    user_addresses: Vec<String>,
+) -> Result<()> {
+    if !user_addresses.is_empty() {
+        let timestamp = chrono::Utc::now().naive_utc();
+        let models = user_addresses
+            .into_iter()
+            .map(|user_address| backfill_users::ActiveModel {
+                user_address: Set(user_address),
+                is_refreshed: Set(false),
+                timestamp: Set(timestamp),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        backfill_users::Entity::insert_many(models)
+            .on_conflict(
+                OnConflict::column(backfill_users::Column::UserAddress)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .do_nothing()
+            .exec(db)
+            .await
+            .context("Failed to store backfill users")?;
+    }
+
+    let mut active_model: backfill_ranges::ActiveModel = range.into();
+    active_model.is_completed = Set(true);
+    active_model.timestamp = Set(chrono::Utc::now().naive_utc());
+    active_model.update(db).await?;
+
+    Ok(())
+}
+
+/// Retrieves the discovered users that haven't been refreshed yet
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+///
+/// * `Result<Vec<String>>` - List of user addresses pending a refresh
+pub async fn get_unrefreshed_backfill_users(db: &impl ConnectionTrait) -> Result<Vec<String>> {
+    let users = backfill_users::Entity::find()
+        .filter(backfill_users::Column::IsRefreshed.eq(false))
+        .all(db)
+        .await?;
+    Ok(users.into_iter().map(|user| user.user_address).collect())
+}
+
+/// Marks discovered users as refreshed
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `user_addresses` - Ethereum addresses of the refreshed users
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn mark_backfill_users_refreshed(
+    db: &impl ConnectionTrait,
+    user_addresses: &[String],
+) -> Result<()> {
+    if user_addresses.is_empty() {
+        return Ok(());
+    }
+
+    backfill_users::Entity::update_many()
+        .col_expr(
+            backfill_users::Column::IsRefreshed,
+            sea_orm::sea_query::Expr::value(true),
+        )
+        .filter(backfill_users::Column::UserAddress.is_in(user_addresses.to_vec()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Deletes all backfill progress once the backfill is finished
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn clear_backfill(db: &impl ConnectionTrait) -> Result<()> {
+    backfill_users::Entity::delete_many().exec(db).await?;
+    backfill_ranges::Entity::delete_many().exec(db).await?;
+    Ok(())
+}
@@ -2,8 +2,13 @@
 
 pub mod prelude;
 
-pub mod at_risk_accounts;
-pub mod healthy_accounts;
+pub mod accounts;
+pub mod backfill_ranges;
+pub mod backfill_users;
+pub mod indexed_block_users;
+pub mod indexed_blocks;
 pub mod last_index_block;
-pub mod liquidatable_accounts;
+pub mod liquidation_opportunities;
+pub mod reserves;
+pub mod sea_orm_active_enums;
 pub mod user_debt_collateral;
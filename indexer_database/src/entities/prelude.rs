@@ -1,7 +1,11 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.4
 
-pub use super::at_risk_accounts::Entity as AtRiskAccounts;
-pub use super::healthy_accounts::Entity as HealthyAccounts;
+pub use super::accounts::Entity as Accounts;
+pub use super::backfill_ranges::Entity as BackfillRanges;
+pub use super::backfill_users::Entity as BackfillUsers;
+pub use super::indexed_block_users::Entity as IndexedBlockUsers;
+pub use super::indexed_blocks::Entity as IndexedBlocks;
 pub use super::last_index_block::Entity as LastIndexBlock;
-pub use super::liquidatable_accounts::Entity as LiquidatableAccounts;
+pub use super::liquidation_opportunities::Entity as LiquidationOpportunities;
+pub use super::reserves::Entity as Reserves;
 pub use super::user_debt_collateral::Entity as UserDebtCollateral;
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.4
 
+use super::sea_orm_active_enums::RateMode;
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
@@ -9,10 +10,15 @@ pub struct Model {
     pub id: i32,
     pub user_address: String,
     pub reserve_address: String,
-    #[sea_orm(column_type = "Float")]
-    pub amount: f32,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub amount: Decimal,
     pub is_collateral: bool,
     pub timestamp: DateTime,
+    pub amount_raw: Option<String>,
+    pub rate_mode: RateMode,
+    #[sea_orm(column_type = "Decimal(None)", nullable)]
+    pub value_in_usd: Option<Decimal>,
+    pub usage_as_collateral_enabled: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
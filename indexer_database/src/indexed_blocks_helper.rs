@@ -0,0 +1,219 @@
+use anyhow::{Context, Result};
+use sea_orm::{
+    sea_query::OnConflict, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, Set,
+};
+use tracing::debug;
+
+use crate::entities::{indexed_block_users, indexed_blocks};
+
+/// Stores the hashes of indexed blocks inside the confirmation window
+///
+/// Blocks that are already stored get their hashes overwritten, so re-indexing
+/// a range after a reorg replaces the orphaned hashes with the canonical ones.
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `blocks` - Vector of (block_number, block_hash, parent_hash) tuples
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn add_indexed_blocks(
+    db: &impl ConnectionTrait,
+    blocks: Vec<(u64, String, String)>,
+) -> Result<()> {
+    if blocks.is_empty() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().naive_utc();
+    let models = blocks
+        .into_iter()
+        .map(
+            |(block_number, block_hash, parent_hash)| indexed_blocks::ActiveModel {
+                block_number: Set(block_number as i32),
+                block_hash: Set(block_hash),
+                parent_hash: Set(parent_hash),
+                timestamp: Set(timestamp),
+                ..Default::default()
+            },
+        )
+        .collect::<Vec<_>>();
+
+    debug!("Storing {} indexed block hashes", models.len());
+
+    indexed_blocks::Entity::insert_many(models)
+        .on_conflict(
+            OnConflict::column(indexed_blocks::Column::BlockNumber)
+                .update_columns([
+                    indexed_blocks::Column::BlockHash,
+                    indexed_blocks::Column::ParentHash,
+                    indexed_blocks::Column::Timestamp,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .context("Failed to store indexed block hashes")?;
+
+    Ok(())
+}
+
+/// Retrieves all stored indexed blocks, highest block first
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+///
+/// * `Result<Vec<indexed_blocks::Model>>` - Stored blocks ordered by descending block number
+pub async fn get_indexed_blocks_descending(
+    db: &impl ConnectionTrait,
+) -> Result<Vec<indexed_blocks::Model>> {
+    Ok(indexed_blocks::Entity::find()
+        .order_by_desc(indexed_blocks::Column::BlockNumber)
+        .all(db)
+        .await?)
+}
+
+/// Retrieves the stored indexed block with the given number
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `block_number` - Block number to look up
+///
+/// # Returns
+///
+/// * `Result<Option<indexed_blocks::Model>>` - The stored block if found
+pub async fn get_indexed_block(
+    db: &impl ConnectionTrait,
+    block_number: u64,
+) -> Result<Option<indexed_blocks::Model>> {
+    Ok(indexed_blocks::Entity::find()
+        .filter(indexed_blocks::Column::BlockNumber.eq(block_number as i32))
+        .one(db)
+        .await?)
+}
+
+/// Records which users were touched by events in which blocks of the confirmation window
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `touched_users` - Vector of (block_number, user_address) pairs
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn add_touched_users(
+    db: &impl ConnectionTrait,
+    touched_users: Vec<(u64, String)>,
+) -> Result<()> {
+    if touched_users.is_empty() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().naive_utc();
+    let models = touched_users
+        .into_iter()
+        .map(
+            |(block_number, user_address)| indexed_block_users::ActiveModel {
+                block_number: Set(block_number as i32),
+                user_address: Set(user_address),
+                timestamp: Set(timestamp),
+                ..Default::default()
+            },
+        )
+        .collect::<Vec<_>>();
+
+    indexed_block_users::Entity::insert_many(models)
+        .on_conflict(
+            OnConflict::columns([
+                indexed_block_users::Column::BlockNumber,
+                indexed_block_users::Column::UserAddress,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .do_nothing()
+        .exec(db)
+        .await
+        .context("Failed to store touched users")?;
+
+    Ok(())
+}
+
+/// Retrieves the distinct users touched by events after the given block
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `block_number` - Exclusive lower bound of the block range
+///
+/// # Returns
+///
+/// * `Result<Vec<String>>` - List of distinct user addresses
+pub async fn get_users_touched_after(
+    db: &impl ConnectionTrait,
+    block_number: u64,
+) -> Result<Vec<String>> {
+    let users = indexed_block_users::Entity::find()
+        .select_only()
+        .column(indexed_block_users::Column::UserAddress)
+        .distinct()
+        .filter(indexed_block_users::Column::BlockNumber.gt(block_number as i32))
+        .into_tuple::<String>()
+        .all(db)
+        .await?;
+    Ok(users)
+}
+
+/// Deletes stored block hashes and touched users after the given block
+///
+/// Used to roll back the confirmation window to the common ancestor after a reorg.
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `block_number` - Exclusive lower bound of the block range to delete
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn delete_blocks_after(db: &impl ConnectionTrait, block_number: u64) -> Result<()> {
+    indexed_blocks::Entity::delete_many()
+        .filter(indexed_blocks::Column::BlockNumber.gt(block_number as i32))
+        .exec(db)
+        .await?;
+    indexed_block_users::Entity::delete_many()
+        .filter(indexed_block_users::Column::BlockNumber.gt(block_number as i32))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Deletes stored block hashes and touched users that fell out of the confirmation window
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `block_number` - Exclusive upper bound of the block range to delete
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn prune_blocks_before(db: &impl ConnectionTrait, block_number: u64) -> Result<()> {
+    indexed_blocks::Entity::delete_many()
+        .filter(indexed_blocks::Column::BlockNumber.lt(block_number as i32))
+        .exec(db)
+        .await?;
+    indexed_block_users::Entity::delete_many()
+        .filter(indexed_block_users::Column::BlockNumber.lt(block_number as i32))
+        .exec(db)
+        .await?;
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, Set};
+use sea_orm::{ActiveModelTrait, ConnectionTrait, DbErr, EntityTrait, Set};
 use tracing::info;
 
 use crate::entities::last_index_block::{ActiveModel as LastIndexBlockActiveModel, Model};
@@ -13,14 +13,17 @@ use crate::entities::prelude::LastIndexBlock;
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 /// * `start_block` - The initial block number to start indexing from
 ///
 /// # Returns
 ///
 /// * `Result<(), DbErr>` - Success if initialization is complete or block already exists,
-///                         error if database operations fail
-pub async fn init_last_index_block(db: &DatabaseConnection, start_block: u64) -> Result<(), DbErr> {
+///   error if database operations fail
+pub async fn init_last_index_block(
+    db: &impl ConnectionTrait,
+    start_block: u64,
+) -> Result<(), DbErr> {
     info!("Checking if last index block exists");
     let last_index_block = LastIndexBlock::find().one(db).await?;
     if last_index_block.is_some() {
@@ -46,15 +49,15 @@ pub async fn init_last_index_block(db: &DatabaseConnection, start_block: u64) ->
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 ///
 /// # Returns
 ///
 /// * `Result<Model>` - The last indexed block model if found,
-///                     error if not found or database operation fails
-pub async fn get_last_index_block(db: &DatabaseConnection) -> Result<Model> {
+///   error if not found or database operation fails
+pub async fn get_last_index_block(db: &impl ConnectionTrait) -> Result<Model> {
     let last_index_block = LastIndexBlock::find().one(db).await?;
-    Ok(last_index_block.ok_or(anyhow::anyhow!("Last index block not found"))?)
+    last_index_block.ok_or(anyhow::anyhow!("Last index block not found"))
 }
 
 /// Updates the last indexed block with a new block number
@@ -64,7 +67,7 @@ pub async fn get_last_index_block(db: &DatabaseConnection) -> Result<Model> {
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 /// * `model` - The existing last index block model to update
 /// * `block_number` - The new block number to set
 ///
@@ -72,7 +75,7 @@ pub async fn get_last_index_block(db: &DatabaseConnection) -> Result<Model> {
 ///
 /// * `Result<(), DbErr>` - Success if update is complete, error if database operation fails
 pub async fn update_last_index_block(
-    db: &DatabaseConnection,
+    db: &impl ConnectionTrait,
     model: Model,
     block_number: u64,
 ) -> Result<(), DbErr> {
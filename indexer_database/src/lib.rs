@@ -1,7 +1,12 @@
+pub mod account_changes_helper;
+pub mod backfill_helper;
 pub mod entities;
+pub mod indexed_blocks_helper;
 pub mod last_index_block_helper;
-pub mod users_tables_helper;
+pub mod liquidation_opportunities_helper;
+pub mod reserves_table_helper;
 pub mod user_debt_collateral_helper;
+pub mod users_tables_helper;
 use std::time::Duration;
 
 use anyhow::Result;
@@ -22,10 +27,10 @@ impl IndexerDatabase {
     ///
     /// # Returns
     /// * `Result<(), DbErr>` - Returns Ok(()) if initialization and migrations are successful,
-    ///                         or a DbErr if either the connection or migrations fail.
+    ///   or a DbErr if either the connection or migrations fail.
     ///
     /// # Example
-    /// ```
+    /// ```ignore
     /// IndexerDatabase::init().await?;
     /// ```
     pub async fn init() -> Result<()> {
@@ -64,7 +69,7 @@ impl IndexerDatabase {
     ///
     /// # Returns
     /// * `Result<DatabaseConnection, DbErr>` - Returns a database connection wrapped in Ok if successful,
-    ///                                        or a DbErr if the connection fails.
+    ///   or a DbErr if the connection fails.
     ///
     /// # Environment Variables
     /// * `DATABASE_URL` - Required PostgreSQL connection string
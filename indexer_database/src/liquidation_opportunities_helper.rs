@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use sea_orm::{
+    prelude::Decimal, sea_query::OnConflict, ColumnTrait, ConnectionTrait, EntityTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use tracing::debug;
+
+use crate::entities::liquidation_opportunities;
+
+/// Maximum number of rows written by a single bulk statement, keeps us below the Postgres bind parameter limit
+const MAX_ROWS_PER_STATEMENT: usize = 1000;
+
+/// The most profitable liquidation of a liquidatable user
+#[derive(Debug, Clone)]
+pub struct LiquidationOpportunity {
+    /// Reserve of the collateral to seize
+    pub collateral_reserve: String,
+    /// Reserve of the debt to repay
+    pub debt_reserve: String,
+    /// Maximum debt the liquidator can repay, normalized by the debt reserve's decimals
+    pub debt_to_cover: Decimal,
+    pub debt_to_cover_in_usd: Decimal,
+    /// Collateral seized for `debt_to_cover`, bonus included and protocol fee excluded
+    pub collateral_to_seize: Decimal,
+    pub collateral_to_seize_in_usd: Decimal,
+    /// Value of the collateral received minus the value of the debt repaid
+    pub profit_in_usd: Decimal,
+}
+
+/// Stores the current liquidation opportunity of many users
+///
+/// Users with an opportunity get it inserted or updated, the stored opportunity of
+/// users without one (no longer liquidatable, or nothing worth seizing) is deleted.
+///
+/// # Arguments
+/// * `db` - Database connection or transaction
+/// * `block_number` - Block the opportunities were computed at
+/// * `opportunities` - Vector of (user_address, opportunity) pairs
+///
+/// # Returns
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn update_liquidation_opportunities(
+    db: &impl ConnectionTrait,
+    block_number: u64,
+    opportunities: Vec<(String, Option<LiquidationOpportunity>)>,
+) -> Result<()> {
+    let timestamp = chrono::Utc::now().naive_utc();
+    let mut models = Vec::new();
+    let mut users_without_opportunity = Vec::new();
+
+    for (user_address, opportunity) in opportunities {
+        match opportunity {
+            Some(opportunity) => models.push(liquidation_opportunities::ActiveModel {
+                user_address: Set(user_address),
+                last_updated_block_number: Set(block_number as i32),
+                collateral_reserve: Set(opportunity.collateral_reserve),
+                debt_reserve: Set(opportunity.debt_reserve),
+                debt_to_cover: Set(opportunity.debt_to_cover),
+                debt_to_cover_in_usd: Set(opportunity.debt_to_cover_in_usd),
+                collateral_to_seize: Set(opportunity.collateral_to_seize),
+                collateral_to_seize_in_usd: Set(opportunity.collateral_to_seize_in_usd),
+                profit_in_usd: Set(opportunity.profit_in_usd),
+                timestamp: Set(timestamp),
+                ..Default::default()
+            }),
+            None => users_without_opportunity.push(user_address),
+        }
+    }
+
+    debug!("Updating {} liquidation opportunities", models.len());
+
+    for models in models.chunks(MAX_ROWS_PER_STATEMENT) {
+        liquidation_opportunities::Entity::insert_many(models.to_vec())
+            .on_conflict(
+                OnConflict::column(liquidation_opportunities::Column::UserAddress)
+                    .update_columns([
+                        liquidation_opportunities::Column::LastUpdatedBlockNumber,
+                        liquidation_opportunities::Column::CollateralReserve,
+                        liquidation_opportunities::Column::DebtReserve,
+                        liquidation_opportunities::Column::DebtToCover,
+                        liquidation_opportunities::Column::DebtToCoverInUsd,
+                        liquidation_opportunities::Column::CollateralToSeize,
+                        liquidation_opportunities::Column::CollateralToSeizeInUsd,
+                        liquidation_opportunities::Column::ProfitInUsd,
+                        liquidation_opportunities::Column::Timestamp,
+                    ])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await
+            .context("Failed to update liquidation opportunities")?;
+    }
+
+    for user_addresses in users_without_opportunity.chunks(MAX_ROWS_PER_STATEMENT) {
+        liquidation_opportunities::Entity::delete_many()
+            .filter(liquidation_opportunities::Column::UserAddress.is_in(user_addresses.to_vec()))
+            .exec(db)
+            .await
+            .context("Failed to delete liquidation opportunities")?;
+    }
+
+    Ok(())
+}
+
+/// Retrieves the stored liquidation opportunity of a user
+///
+/// # Arguments
+/// * `db` - Database connection or transaction
+/// * `user_address` - Ethereum address of the user
+///
+/// # Returns
+/// * `Result<Option<liquidation_opportunities::Model>>` - The opportunity, `None` if the user has none
+pub async fn get_liquidation_opportunity(
+    db: &impl ConnectionTrait,
+    user_address: &str,
+) -> Result<Option<liquidation_opportunities::Model>> {
+    Ok(liquidation_opportunities::Entity::find()
+        .filter(liquidation_opportunities::Column::UserAddress.eq(user_address))
+        .one(db)
+        .await?)
+}
+
+/// Retrieves all stored liquidation opportunities, most profitable first
+///
+/// # Arguments
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+/// * `Result<Vec<liquidation_opportunities::Model>>` - Stored liquidation opportunities
+pub async fn get_liquidation_opportunities(
+    db: &impl ConnectionTrait,
+) -> Result<Vec<liquidation_opportunities::Model>> {
+    Ok(liquidation_opportunities::Entity::find()
+        .order_by_desc(liquidation_opportunities::Column::ProfitInUsd)
+        .all(db)
+        .await?)
+}
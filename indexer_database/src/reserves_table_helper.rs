@@ -0,0 +1,202 @@
+use anyhow::{Context, Result};
+use sea_orm::{
+    prelude::Decimal,
+    sea_query::{Expr, OnConflict},
+    ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, Set,
+};
+use tracing::debug;
+
+use crate::entities::reserves;
+
+/// Configuration of an Aave reserve as stored in the reserves table
+#[derive(Debug, Clone)]
+pub struct ReserveDetails {
+    pub reserve_address: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub a_token_address: String,
+    pub stable_debt_token_address: String,
+    pub variable_debt_token_address: String,
+    pub is_active: bool,
+    pub is_frozen: bool,
+    pub is_paused: bool,
+    pub borrowing_enabled: bool,
+    pub usage_as_collateral_enabled: bool,
+    /// Share of the collateral value that can be borrowed against
+    pub ltv: Decimal,
+    /// Share of the collateral value counted towards the health factor
+    pub liquidation_threshold: Decimal,
+    /// Collateral received per unit of debt repaid by a liquidator, e.g. 1.05
+    pub liquidation_bonus: Decimal,
+    /// Share of the liquidation bonus kept by the protocol
+    pub liquidation_protocol_fee: Decimal,
+    /// E-mode category of the reserve, 0 if it isn't part of any
+    pub emode_category: u8,
+    /// Liquidation threshold for users in the reserve's e-mode category, the reserve's
+    /// own threshold if it isn't part of any
+    pub emode_liquidation_threshold: Decimal,
+    /// Maximum debt in USD that can be borrowed against the reserve in isolation mode,
+    /// zero if the reserve isn't isolated
+    pub debt_ceiling: Decimal,
+    /// Debt in USD currently borrowed against the reserve in isolation mode
+    pub isolation_mode_total_debt: Decimal,
+    /// Whether borrowing the reserve prevents borrowing any other reserve
+    pub siloed_borrowing: bool,
+    /// Maximum amount of tokens that can be borrowed, zero if uncapped
+    pub borrow_cap: Decimal,
+    /// Maximum amount of tokens that can be supplied, zero if uncapped
+    pub supply_cap: Decimal,
+}
+
+/// Inserts or updates the stored configuration of the given reserves
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `reserves` - Configuration of the reserves to store
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn add_or_update_reserves(
+    db: &impl ConnectionTrait,
+    reserves: Vec<ReserveDetails>,
+) -> Result<()> {
+    if reserves.is_empty() {
+        return Ok(());
+    }
+
+    let timestamp = chrono::Utc::now().naive_utc();
+    let models = reserves
+        .into_iter()
+        .map(|reserve| reserves::ActiveModel {
+            reserve_address: Set(reserve.reserve_address),
+            symbol: Set(reserve.symbol),
+            decimals: Set(reserve.decimals as i32),
+            a_token_address: Set(reserve.a_token_address),
+            stable_debt_token_address: Set(reserve.stable_debt_token_address),
+            variable_debt_token_address: Set(reserve.variable_debt_token_address),
+            is_active: Set(reserve.is_active),
+            is_frozen: Set(reserve.is_frozen),
+            is_paused: Set(reserve.is_paused),
+            borrowing_enabled: Set(reserve.borrowing_enabled),
+            usage_as_collateral_enabled: Set(reserve.usage_as_collateral_enabled),
+            ltv: Set(reserve.ltv),
+            liquidation_threshold: Set(reserve.liquidation_threshold),
+            liquidation_bonus: Set(reserve.liquidation_bonus),
+            liquidation_protocol_fee: Set(reserve.liquidation_protocol_fee),
+            emode_category: Set(reserve.emode_category as i32),
+            emode_liquidation_threshold: Set(reserve.emode_liquidation_threshold),
+            debt_ceiling: Set(reserve.debt_ceiling),
+            isolation_mode_total_debt: Set(reserve.isolation_mode_total_debt),
+            siloed_borrowing: Set(reserve.siloed_borrowing),
+            borrow_cap: Set(reserve.borrow_cap),
+            supply_cap: Set(reserve.supply_cap),
+            timestamp: Set(timestamp),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    debug!("Storing {} reserves", models.len());
+
+    reserves::Entity::insert_many(models)
+        .on_conflict(
+            OnConflict::column(reserves::Column::ReserveAddress)
+                .update_columns([
+                    reserves::Column::Symbol,
+                    reserves::Column::Decimals,
+                    reserves::Column::ATokenAddress,
+                    reserves::Column::StableDebtTokenAddress,
+                    reserves::Column::VariableDebtTokenAddress,
+                    reserves::Column::IsActive,
+                    reserves::Column::IsFrozen,
+                    reserves::Column::IsPaused,
+                    reserves::Column::BorrowingEnabled,
+                    reserves::Column::UsageAsCollateralEnabled,
+                    reserves::Column::Ltv,
+                    reserves::Column::LiquidationThreshold,
+                    reserves::Column::LiquidationBonus,
+                    reserves::Column::LiquidationProtocolFee,
+                    reserves::Column::EmodeCategory,
+                    reserves::Column::EmodeLiquidationThreshold,
+                    reserves::Column::DebtCeiling,
+                    reserves::Column::IsolationModeTotalDebt,
+                    reserves::Column::SiloedBorrowing,
+                    reserves::Column::BorrowCap,
+                    reserves::Column::SupplyCap,
+                    reserves::Column::Timestamp,
+                ])
+                .to_owned(),
+        )
+        .exec(db)
+        .await
+        .context("Failed to store reserves")?;
+
+    Ok(())
+}
+
+/// Deletes the given reserves, used once they are dropped from the pool
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `reserve_addresses` - Addresses of the reserves to delete
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn delete_reserves(
+    db: &impl ConnectionTrait,
+    reserve_addresses: Vec<String>,
+) -> Result<()> {
+    if reserve_addresses.is_empty() {
+        return Ok(());
+    }
+
+    reserves::Entity::delete_many()
+        .filter(reserves::Column::ReserveAddress.is_in(reserve_addresses))
+        .exec(db)
+        .await
+        .context("Failed to delete reserves")?;
+
+    Ok(())
+}
+
+/// Stores the last oracle price of the given reserves
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `prices` - Vector of (reserve_address, price) tuples, prices in USD
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn update_reserve_prices(
+    db: &impl ConnectionTrait,
+    prices: Vec<(String, Decimal)>,
+) -> Result<()> {
+    for (reserve_address, price) in prices {
+        reserves::Entity::update_many()
+            .col_expr(reserves::Column::Price, Expr::value(price))
+            .filter(reserves::Column::ReserveAddress.eq(reserve_address))
+            .exec(db)
+            .await
+            .context("Failed to store reserve price")?;
+    }
+
+    Ok(())
+}
+
+/// Retrieves all stored reserves
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+///
+/// * `Result<Vec<reserves::Model>>` - Stored reserves
+pub async fn get_reserves(db: &impl ConnectionTrait) -> Result<Vec<reserves::Model>> {
+    Ok(reserves::Entity::find().all(db).await?)
+}
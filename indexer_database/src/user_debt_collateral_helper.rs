@@ -1,8 +1,34 @@
 use anyhow::{Context, Result};
-use sea_orm::{sea_query::OnConflict, DatabaseConnection, EntityTrait, Set};
+use sea_orm::{
+    prelude::Decimal, sea_query::OnConflict, ColumnTrait, Condition, ConnectionTrait, EntityTrait,
+    QueryFilter, QuerySelect, Set,
+};
 use tracing::debug;
 
-use crate::entities::user_debt_collateral;
+use crate::entities::{sea_orm_active_enums::RateMode, user_debt_collateral};
+
+/// Maximum number of rows written by a single bulk statement, keeps us below the Postgres bind parameter limit
+const MAX_ROWS_PER_STATEMENT: usize = 1000;
+
+/// A single collateral or debt position of a user in a reserve
+#[derive(Debug, Clone)]
+pub struct UserPosition {
+    pub reserve_address: String,
+    /// Balance normalized by the reserve's decimals
+    pub amount: Decimal,
+    /// Raw on-chain balance as a decimal U256 string
+    pub amount_raw: String,
+    /// Interest rate mode of a debt position, `RateMode::None` for collateral
+    pub rate_mode: RateMode,
+    /// Value of the balance in the oracle's base currency (USD)
+    pub value_in_usd: Decimal,
+    /// Whether the user uses the reserve as collateral, always false for debt. A
+    /// collateral the user disabled can't be seized by a liquidation
+    pub usage_as_collateral_enabled: bool,
+}
+
+/// A user's collateral and debt positions as (user_address, collateral_assets, debt_assets)
+pub type UserPositions = (String, Vec<UserPosition>, Vec<UserPosition>);
 
 /// Updates or creates user's collateral and debt positions in the database
 ///
@@ -10,90 +36,237 @@ use crate::entities::user_debt_collateral;
 /// It uses a batch insert with ON CONFLICT DO UPDATE strategy for efficiency.
 ///
 /// # Arguments
-/// * `db` - Database connection handle
+/// * `db` - Database connection or transaction
 /// * `user_address` - Ethereum address of the user
-/// * `collateral_assets` - Vector of (asset_address, amount) pairs for collateral positions
-/// * `debt_assets` - Vector of (asset_address, amount) pairs for debt positions
+/// * `collateral_assets` - Collateral positions of the user
+/// * `debt_assets` - Debt positions of the user
 ///
 /// # Returns
 /// * `Result<()>` - Success or error result of the database operation
 pub async fn add_or_update_user_debt_collateral(
-    db: &DatabaseConnection,
+    db: &impl ConnectionTrait,
     user_address: &str,
-    collateral_assets: Vec<(String, f32)>,
-    debt_assets: Vec<(String, f32)>,
+    collateral_assets: Vec<UserPosition>,
+    debt_assets: Vec<UserPosition>,
+) -> Result<()> {
+    add_or_update_users_debt_collateral(
+        db,
+        vec![(user_address.to_string(), collateral_assets, debt_assets)],
+    )
+    .await
+}
+
+/// Updates or creates the collateral and debt positions of many users in the database
+///
+/// The given positions are the users' full position set: stored positions that are
+/// not part of it anymore, because the user repaid or withdrew the reserve to zero,
+/// are deleted. Pass a transaction to make the reconciliation atomic.
+///
+/// # Arguments
+/// * `db` - Database connection or transaction
+/// * `users_positions` - Vector of (user_address, collateral_assets, debt_assets) tuples
+///
+/// # Returns
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn add_or_update_users_debt_collateral(
+    db: &impl ConnectionTrait,
+    users_positions: Vec<UserPositions>,
 ) -> Result<()> {
     let timestamp = chrono::Utc::now().naive_utc();
-    let mut models = Vec::with_capacity(collateral_assets.len() + debt_assets.len());
-
-    // Process collateral positions
-    for (reserve_address, amount) in collateral_assets {
-        models.push(create_position_model(
-            user_address,
-            reserve_address,
-            amount,
-            true,
-            timestamp,
-        ));
+    let mut models = Vec::new();
+    // Per user, the stored positions whose key isn't in the fetched set, with the number of keys
+    let mut closed_positions = Vec::with_capacity(users_positions.len());
+
+    for (user_address, collateral_assets, debt_assets) in users_positions {
+        let mut user_closed_positions = Condition::all()
+            .add(user_debt_collateral::Column::UserAddress.eq(user_address.as_str()));
+        let positions_count = collateral_assets.len() + debt_assets.len();
+
+        // Process collateral positions
+        for position in collateral_assets {
+            user_closed_positions = user_closed_positions.add(other_positions(&position, true));
+            models.push(create_position_model(
+                &user_address,
+                position,
+                true,
+                timestamp,
+            ));
+        }
+
+        // Process debt positions
+        for position in debt_assets {
+            user_closed_positions = user_closed_positions.add(other_positions(&position, false));
+            models.push(create_position_model(
+                &user_address,
+                position,
+                false,
+                timestamp,
+            ));
+        }
+
+        closed_positions.push((user_closed_positions, positions_count));
     }
 
-    // Process debt positions
-    for (reserve_address, amount) in debt_assets {
-        models.push(create_position_model(
-            user_address,
-            reserve_address,
-            amount,
-            false,
-            timestamp,
-        ));
+    debug!("Updating {} positions", models.len());
+
+    // Perform batch insert/update
+    for models in models.chunks(MAX_ROWS_PER_STATEMENT) {
+        user_debt_collateral::Entity::insert_many(models.to_vec())
+            .on_conflict(
+                OnConflict::columns([
+                    user_debt_collateral::Column::UserAddress,
+                    user_debt_collateral::Column::ReserveAddress,
+                    user_debt_collateral::Column::IsCollateral,
+                    user_debt_collateral::Column::RateMode,
+                ])
+                .update_columns([
+                    user_debt_collateral::Column::Amount,
+                    user_debt_collateral::Column::AmountRaw,
+                    user_debt_collateral::Column::ValueInUsd,
+                    user_debt_collateral::Column::UsageAsCollateralEnabled,
+                    user_debt_collateral::Column::Timestamp,
+                ])
+                .to_owned(),
+            )
+            .exec(db)
+            .await
+            .context("Failed to update user positions")?;
     }
 
-    // Skip database operation if no positions to update
-    if models.is_empty() {
-        debug!("No positions to update for user {}", user_address);
-        return Ok(());
+    // Delete the closed positions, grouping users until a statement filters on
+    // `MAX_ROWS_PER_STATEMENT` position keys
+    let mut filter = Condition::any();
+    let mut filtered_positions = 0;
+    for (user_closed_positions, positions_count) in closed_positions {
+        filter = filter.add(user_closed_positions);
+        filtered_positions += positions_count + 1;
+
+        if filtered_positions >= MAX_ROWS_PER_STATEMENT {
+            delete_closed_positions(db, filter).await?;
+            filter = Condition::any();
+            filtered_positions = 0;
+        }
+    }
+    if filtered_positions > 0 {
+        delete_closed_positions(db, filter).await?;
     }
 
-    debug!(
-        "Updating {} positions for user {}",
-        models.len(),
-        user_address
-    );
+    Ok(())
+}
 
-    // Perform batch insert/update
-    user_debt_collateral::Entity::insert_many(models)
-        .on_conflict(
-            OnConflict::columns([
-                user_debt_collateral::Column::UserAddress,
-                user_debt_collateral::Column::ReserveAddress,
-                user_debt_collateral::Column::IsCollateral,
-            ])
-            .update_columns([
-                user_debt_collateral::Column::Amount,
-                user_debt_collateral::Column::Timestamp,
-            ])
-            .to_owned(),
-        )
+/// Matches the rows of a user's other positions than the given one
+///
+/// # Arguments
+/// * `position` - The position
+/// * `is_collateral` - Whether the position is a collateral
+///
+/// # Returns
+/// * `Condition` - Condition on the position key `(reserve_address, is_collateral, rate_mode)`
+fn other_positions(position: &UserPosition, is_collateral: bool) -> Condition {
+    Condition::any()
+        .add(user_debt_collateral::Column::ReserveAddress.ne(position.reserve_address.as_str()))
+        .add(user_debt_collateral::Column::IsCollateral.ne(is_collateral))
+        .add(user_debt_collateral::Column::RateMode.ne(position.rate_mode))
+}
+
+/// Deletes the positions matching the filter
+///
+/// # Arguments
+/// * `db` - Database connection or transaction
+/// * `filter` - Closed positions of some users
+///
+/// # Returns
+/// * `Result<()>` - Success or error result of the database operation
+async fn delete_closed_positions(db: &impl ConnectionTrait, filter: Condition) -> Result<()> {
+    let result = user_debt_collateral::Entity::delete_many()
+        .filter(filter)
         .exec(db)
         .await
-        .context("Failed to update user positions")?;
+        .context("Failed to delete closed user positions")?;
+
+    if result.rows_affected > 0 {
+        debug!("Deleted {} closed positions", result.rows_affected);
+    }
 
     Ok(())
 }
 
+/// Gets the users holding any of the given reserves as collateral or debt
+///
+/// # Arguments
+/// * `db` - Database connection or transaction
+/// * `reserve_addresses` - Addresses of the reserves
+///
+/// # Returns
+/// * `Result<Vec<String>>` - Addresses of the users, without duplicates
+pub async fn get_users_with_positions_in_reserves(
+    db: &impl ConnectionTrait,
+    reserve_addresses: &[String],
+) -> Result<Vec<String>> {
+    let mut user_addresses = Vec::new();
+
+    for reserve_addresses in reserve_addresses.chunks(MAX_ROWS_PER_STATEMENT) {
+        let users = user_debt_collateral::Entity::find()
+            .select_only()
+            .column(user_debt_collateral::Column::UserAddress)
+            .distinct()
+            .filter(user_debt_collateral::Column::ReserveAddress.is_in(reserve_addresses.to_vec()))
+            .into_tuple::<String>()
+            .all(db)
+            .await
+            .context("Failed to get users with positions in reserves")?;
+        user_addresses.extend(users);
+    }
+
+    user_addresses.sort_unstable();
+    user_addresses.dedup();
+
+    Ok(user_addresses)
+}
+
+/// Gets the stored collateral and debt positions of many users
+///
+/// # Arguments
+/// * `db` - Database connection or transaction
+/// * `user_addresses` - Ethereum addresses of the users
+///
+/// # Returns
+/// * `Result<Vec<user_debt_collateral::Model>>` - Positions of the users
+pub async fn get_users_positions(
+    db: &impl ConnectionTrait,
+    user_addresses: &[String],
+) -> Result<Vec<user_debt_collateral::Model>> {
+    let mut positions = Vec::new();
+
+    for user_addresses in user_addresses.chunks(MAX_ROWS_PER_STATEMENT) {
+        positions.extend(
+            user_debt_collateral::Entity::find()
+                .filter(user_debt_collateral::Column::UserAddress.is_in(user_addresses.to_vec()))
+                .all(db)
+                .await
+                .context("Failed to get user positions")?,
+        );
+    }
+
+    Ok(positions)
+}
+
 /// Creates an ActiveModel for a user's position
 fn create_position_model(
     user_address: &str,
-    reserve_address: String,
-    amount: f32,
+    position: UserPosition,
     is_collateral: bool,
     timestamp: chrono::NaiveDateTime,
 ) -> user_debt_collateral::ActiveModel {
     user_debt_collateral::ActiveModel {
         user_address: Set(user_address.to_string()),
-        reserve_address: Set(reserve_address),
-        amount: Set(amount),
+        reserve_address: Set(position.reserve_address),
+        amount: Set(position.amount),
+        amount_raw: Set(Some(position.amount_raw)),
+        rate_mode: Set(position.rate_mode),
+        value_in_usd: Set(Some(position.value_in_usd)),
         is_collateral: Set(is_collateral),
+        usage_as_collateral_enabled: Set(is_collateral && position.usage_as_collateral_enabled),
         timestamp: Set(timestamp),
         ..Default::default()
     }
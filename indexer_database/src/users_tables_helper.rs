@@ -1,11 +1,19 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use sea_orm::{
+    prelude::Decimal,
+    sea_query::{Expr, OnConflict},
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbBackend, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, Statement, Value,
+};
 
-use crate::entities::{at_risk_accounts, healthy_accounts, liquidatable_accounts};
+use crate::{
+    account_changes_helper::{self, AccountChange},
+    entities::{accounts, sea_orm_active_enums::AccountTier},
+};
 
 /// Represents the current status/location of a user's account in the system
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum UserCurrentLocation {
     Liquidatable,
     AtRisk,
@@ -13,333 +21,567 @@ pub enum UserCurrentLocation {
     NotFound,
 }
 
+impl UserCurrentLocation {
+    /// Returns the name of the tier, as used in the API responses and the metrics labels
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - The tier name
+    pub fn name(&self) -> &'static str {
+        match self {
+            UserCurrentLocation::Liquidatable => "liquidatable",
+            UserCurrentLocation::AtRisk => "at_risk",
+            UserCurrentLocation::Healthy => "healthy",
+            UserCurrentLocation::NotFound => "not_found",
+        }
+    }
+
+    /// Returns the tier stored in the accounts table for this location
+    ///
+    /// # Returns
+    ///
+    /// * `Result<AccountTier>` - The account tier, or an error for users not found
+    fn tier(&self) -> Result<AccountTier> {
+        match self {
+            UserCurrentLocation::Liquidatable => Ok(AccountTier::Liquidatable),
+            UserCurrentLocation::AtRisk => Ok(AccountTier::AtRisk),
+            UserCurrentLocation::Healthy => Ok(AccountTier::Healthy),
+            UserCurrentLocation::NotFound => Err(anyhow::anyhow!("User not found")),
+        }
+    }
+}
+
+impl From<AccountTier> for UserCurrentLocation {
+    fn from(tier: AccountTier) -> Self {
+        match tier {
+            AccountTier::Liquidatable => UserCurrentLocation::Liquidatable,
+            AccountTier::AtRisk => UserCurrentLocation::AtRisk,
+            AccountTier::Healthy => UserCurrentLocation::Healthy,
+        }
+    }
+}
+
+/// Column a page of accounts is sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountsSortColumn {
+    HealthFactor,
+    TotalDebt,
+}
+
+/// Maximum number of rows written by a single bulk statement, keeps us below the Postgres bind parameter limit
+const MAX_ROWS_PER_STATEMENT: usize = 1000;
+
 /// Contains detailed information about a user's account status and positions
+#[derive(Debug, Clone)]
 pub struct UserDetails {
     pub id: i32,
     pub user_address: String,
     pub last_updated_block_number: i32,
-    pub health_factor: f32,
-    pub total_collateral_value_in_usd: f32,
-    pub total_debt_value_in_usd: f32,
+    pub health_factor: Decimal,
+    pub total_collateral_value_in_usd: Decimal,
+    pub total_debt_value_in_usd: Decimal,
+    /// E-mode category of the user, 0 when not in e-mode
+    pub emode_category: i32,
+    /// Whether the user's only collateral is an isolated reserve
+    pub is_in_isolation_mode: bool,
+    /// Whether the user borrows a siloed reserve
+    pub has_siloed_borrowing: bool,
     pub leading_collateral_reserve: String,
     pub leading_debt_reserve: String,
-    pub leading_collateral_reserve_value: f32,
-    pub leading_debt_reserve_value: f32,
+    pub leading_collateral_reserve_value: Decimal,
+    pub leading_debt_reserve_value: Decimal,
+    /// Health factor projected from the stored positions and cached prices, equal to
+    /// `health_factor` right after a refresh
+    pub projected_health_factor: Decimal,
     pub timestamp: DateTime<Utc>,
     pub current_location: UserCurrentLocation,
 }
 
 /// Retrieves user details from the database based on their address
 ///
-/// This function searches for a user across three tables in order:
-/// 1. Liquidatable accounts
-/// 2. At-risk accounts
-/// 3. Healthy accounts
-///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 /// * `user_address` - Ethereum address of the user to search for
 ///
 /// # Returns
 ///
-/// * `Result<Option<UserDetails>>` - User details if found, None if not found in any table
-pub async fn get_user(db: &DatabaseConnection, user_address: &str) -> Result<Option<UserDetails>> {
-    // First check liquidatable accounts
-    if let Some(user) = liquidatable_accounts::Entity::find()
-        .filter(liquidatable_accounts::Column::UserAddress.eq(user_address))
-        .one(db)
-        .await?
-    {
-        return Ok(Some(UserDetails {
-            id: user.id,
-            user_address: user.user_address,
-            last_updated_block_number: user.last_updated_block_number,
-            health_factor: user.health_factor,
-            total_collateral_value_in_usd: user.total_collateral_value_in_usd,
-            total_debt_value_in_usd: user.total_debt_value_in_usd,
-            leading_collateral_reserve: user.leading_collateral_reserve,
-            leading_debt_reserve: user.leading_debt_reserve,
-            leading_collateral_reserve_value: user.leading_collateral_reserve_value,
-            leading_debt_reserve_value: user.leading_debt_reserve_value,
-            timestamp: DateTime::from_naive_utc_and_offset(user.timestamp, Utc),
-            current_location: UserCurrentLocation::Liquidatable,
-        }));
-    }
-
-    // Then check at risk accounts
-    if let Some(user) = at_risk_accounts::Entity::find()
-        .filter(at_risk_accounts::Column::UserAddress.eq(user_address))
-        .one(db)
-        .await?
-    {
-        return Ok(Some(UserDetails {
-            id: user.id,
-            user_address: user.user_address,
-            last_updated_block_number: user.last_updated_block_number,
-            health_factor: user.health_factor,
-            total_collateral_value_in_usd: user.total_collateral_value_in_usd,
-            total_debt_value_in_usd: user.total_debt_value_in_usd,
-            leading_collateral_reserve: user.leading_collateral_reserve,
-            leading_debt_reserve: user.leading_debt_reserve,
-            leading_collateral_reserve_value: user.leading_collateral_reserve_value,
-            leading_debt_reserve_value: user.leading_debt_reserve_value,
-            timestamp: DateTime::from_naive_utc_and_offset(user.timestamp, Utc),
-            current_location: UserCurrentLocation::AtRisk,
-        }));
-    }
-
-    // Finally check healthy accounts
-    if let Some(user) = healthy_accounts::Entity::find()
-        .filter(healthy_accounts::Column::UserAddress.eq(user_address))
+/// * `Result<Option<UserDetails>>` - User details if found, None if not found
+pub async fn get_user(
+    db: &impl ConnectionTrait,
+    user_address: &str,
+) -> Result<Option<UserDetails>> {
+    let user = accounts::Entity::find()
+        .filter(accounts::Column::UserAddress.eq(user_address))
         .one(db)
-        .await?
-    {
-        return Ok(Some(UserDetails {
-            id: user.id,
-            user_address: user.user_address,
-            last_updated_block_number: user.last_updated_block_number,
-            health_factor: user.health_factor,
-            total_collateral_value_in_usd: user.total_collateral_value_in_usd,
-            total_debt_value_in_usd: user.total_debt_value_in_usd,
-            leading_collateral_reserve: user.leading_collateral_reserve,
-            leading_debt_reserve: user.leading_debt_reserve,
-            leading_collateral_reserve_value: user.leading_collateral_reserve_value,
-            leading_debt_reserve_value: user.leading_debt_reserve_value,
-            timestamp: DateTime::from_naive_utc_and_offset(user.timestamp, Utc),
-            current_location: UserCurrentLocation::Healthy,
-        }));
-    }
-
-    // If user not found in any table
-    return Ok(None);
+        .await?;
+    Ok(user.map(account_to_user_details))
 }
 
-/// Deletes a user from their current location table in the database
+/// Deletes a user from the database
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 /// * `id` - User's ID in the database
-/// * `location` - Current location/status of the user (Liquidatable, AtRisk, or Healthy)
 ///
 /// # Returns
 ///
-/// * `Result<()>` - Success or error if user not found or deletion fails
-pub async fn delete_user(
-    db: &DatabaseConnection,
-    id: i32,
-    location: UserCurrentLocation,
-) -> Result<()> {
-    match location {
-        UserCurrentLocation::Liquidatable => {
-            liquidatable_accounts::Entity::delete_by_id(id)
-                .exec(db)
-                .await?;
-        }
-        UserCurrentLocation::AtRisk => {
-            at_risk_accounts::Entity::delete_by_id(id).exec(db).await?;
-        }
-        UserCurrentLocation::Healthy => {
-            healthy_accounts::Entity::delete_by_id(id).exec(db).await?;
-        }
-        UserCurrentLocation::NotFound => {
-            return Err(anyhow::anyhow!("User not found"));
-        }
-    }
+/// * `Result<()>` - Success or error if deletion fails
+pub async fn delete_user(db: &impl ConnectionTrait, id: i32) -> Result<()> {
+    accounts::Entity::delete_by_id(id).exec(db).await?;
     Ok(())
 }
 
-/// Adds a new user to the specified location table in the database
+/// Adds a new user to the database in the specified tier
+///
+/// The new account is notified on `ACCOUNT_CHANGES_CHANNEL`.
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 /// * `user` - User details to be added
-/// * `new_location` - Target table/location where the user should be added
+/// * `new_location` - Tier the user should be added to
 ///
 /// # Returns
 ///
 /// * `Result<()>` - Success or error if insertion fails
 pub async fn add_user(
-    db: &DatabaseConnection,
+    db: &impl ConnectionTrait,
     user: UserDetails,
     new_location: UserCurrentLocation,
 ) -> Result<()> {
-    match new_location {
-        UserCurrentLocation::Liquidatable => {
-            let active_model = user_details_to_liquidatable_account(&user);
-            active_model.insert(db).await?;
-        }
-        UserCurrentLocation::AtRisk => {
-            let active_model = user_details_to_at_risk_account(&user);
-            active_model.insert(db).await?;
-        }
-        UserCurrentLocation::Healthy => {
-            let active_model = user_details_to_healthy_account(&user);
-            active_model.insert(db).await?;
-        }
-        UserCurrentLocation::NotFound => {
-            return Err(anyhow::anyhow!("User not found"));
-        }
-    }
+    let active_model = user_details_to_account(&user, new_location.tier()?);
+    active_model.insert(db).await?;
+
+    account_changes_helper::notify_account_changes(db, tier_changes(&[user], &new_location)?)
+        .await?;
+
     Ok(())
 }
 
-/// Converts UserDetails to a liquidatable account active model
+/// Updates an existing user's details and tier in place
+///
+/// A tier change is a single update of the user's row, so readers never see
+/// the user missing or in two tiers at once, and is notified on `ACCOUNT_CHANGES_CHANNEL`.
 ///
 /// # Arguments
 ///
-/// * `user` - User details to convert
+/// * `db` - Database connection or transaction
+/// * `id` - User's ID in the database
+/// * `user` - Updated user details
+/// * `new_location` - Tier the user should be in after the update
 ///
 /// # Returns
 ///
-/// * `liquidatable_accounts::ActiveModel` - Active model ready for database operations
-fn user_details_to_liquidatable_account(user: &UserDetails) -> liquidatable_accounts::ActiveModel {
-    liquidatable_accounts::ActiveModel {
-        user_address: Set(user.user_address.clone()),
-        last_updated_block_number: Set(user.last_updated_block_number),
-        health_factor: Set(user.health_factor),
-        total_collateral_value_in_usd: Set(user.total_collateral_value_in_usd),
-        total_debt_value_in_usd: Set(user.total_debt_value_in_usd),
-        leading_collateral_reserve: Set(user.leading_collateral_reserve.clone()),
-        leading_debt_reserve: Set(user.leading_debt_reserve.clone()),
-        leading_collateral_reserve_value: Set(user.leading_collateral_reserve_value),
-        leading_debt_reserve_value: Set(user.leading_debt_reserve_value),
-        timestamp: Set(user.timestamp.naive_utc()),
-        ..Default::default()
+/// * `Result<()>` - Success or error if update fails
+pub async fn update_user(
+    db: &impl ConnectionTrait,
+    id: i32,
+    user: UserDetails,
+    new_location: UserCurrentLocation,
+) -> Result<()> {
+    let mut active_model = user_details_to_account(&user, new_location.tier()?);
+    active_model.id = Set(id);
+    active_model.update(db).await?;
+
+    account_changes_helper::notify_account_changes(db, tier_changes(&[user], &new_location)?)
+        .await?;
+
+    Ok(())
+}
+
+/// Converts an account model to UserDetails
+///
+/// # Arguments
+///
+/// * `user` - Account model to convert
+///
+/// # Returns
+///
+/// * `UserDetails` - User details with the location matching the account tier
+fn account_to_user_details(user: accounts::Model) -> UserDetails {
+    UserDetails {
+        id: user.id,
+        user_address: user.user_address,
+        last_updated_block_number: user.last_updated_block_number,
+        health_factor: user.health_factor,
+        total_collateral_value_in_usd: user.total_collateral_value_in_usd,
+        total_debt_value_in_usd: user.total_debt_value_in_usd,
+        emode_category: user.emode_category,
+        is_in_isolation_mode: user.is_in_isolation_mode,
+        has_siloed_borrowing: user.has_siloed_borrowing,
+        leading_collateral_reserve: user.leading_collateral_reserve,
+        leading_debt_reserve: user.leading_debt_reserve,
+        leading_collateral_reserve_value: user.leading_collateral_reserve_value,
+        leading_debt_reserve_value: user.leading_debt_reserve_value,
+        projected_health_factor: user.projected_health_factor,
+        timestamp: DateTime::from_naive_utc_and_offset(user.timestamp, Utc),
+        current_location: user.tier.into(),
     }
 }
 
-/// Converts UserDetails to an at-risk account active model
+/// Converts UserDetails to an account active model
 ///
 /// # Arguments
 ///
 /// * `user` - User details to convert
+/// * `tier` - Tier to store the account in
 ///
 /// # Returns
 ///
-/// * `at_risk_accounts::ActiveModel` - Active model ready for database operations
-fn user_details_to_at_risk_account(user: &UserDetails) -> at_risk_accounts::ActiveModel {
-    at_risk_accounts::ActiveModel {
+/// * `accounts::ActiveModel` - Active model ready for database operations
+fn user_details_to_account(user: &UserDetails, tier: AccountTier) -> accounts::ActiveModel {
+    accounts::ActiveModel {
         user_address: Set(user.user_address.clone()),
+        tier: Set(tier),
         last_updated_block_number: Set(user.last_updated_block_number),
         health_factor: Set(user.health_factor),
         total_collateral_value_in_usd: Set(user.total_collateral_value_in_usd),
         total_debt_value_in_usd: Set(user.total_debt_value_in_usd),
+        emode_category: Set(user.emode_category),
+        is_in_isolation_mode: Set(user.is_in_isolation_mode),
+        has_siloed_borrowing: Set(user.has_siloed_borrowing),
         leading_collateral_reserve: Set(user.leading_collateral_reserve.clone()),
         leading_debt_reserve: Set(user.leading_debt_reserve.clone()),
         leading_collateral_reserve_value: Set(user.leading_collateral_reserve_value),
         leading_debt_reserve_value: Set(user.leading_debt_reserve_value),
+        projected_health_factor: Set(user.projected_health_factor),
         timestamp: Set(user.timestamp.naive_utc()),
         ..Default::default()
     }
 }
 
-/// Converts UserDetails to a healthy account active model
+/// Retrieves user details for many users at once
 ///
 /// # Arguments
 ///
-/// * `user` - User details to convert
+/// * `db` - Database connection or transaction
+/// * `user_addresses` - Ethereum addresses of the users to search for
 ///
 /// # Returns
 ///
-/// * `healthy_accounts::ActiveModel` - Active model ready for database operations
-fn user_details_to_healthy_account(user: &UserDetails) -> healthy_accounts::ActiveModel {
-    healthy_accounts::ActiveModel {
-        user_address: Set(user.user_address.clone()),
-        last_updated_block_number: Set(user.last_updated_block_number),
-        health_factor: Set(user.health_factor),
-        total_collateral_value_in_usd: Set(user.total_collateral_value_in_usd),
-        total_debt_value_in_usd: Set(user.total_debt_value_in_usd),
-        leading_collateral_reserve: Set(user.leading_collateral_reserve.clone()),
-        leading_debt_reserve: Set(user.leading_debt_reserve.clone()),
-        leading_collateral_reserve_value: Set(user.leading_collateral_reserve_value),
-        leading_debt_reserve_value: Set(user.leading_debt_reserve_value),
-        timestamp: Set(user.timestamp.naive_utc()),
-        ..Default::default()
+/// * `Result<Vec<UserDetails>>` - Details of the users found
+pub async fn get_users(
+    db: &impl ConnectionTrait,
+    user_addresses: &[String],
+) -> Result<Vec<UserDetails>> {
+    let mut users = Vec::with_capacity(user_addresses.len());
+
+    for addresses in user_addresses.chunks(MAX_ROWS_PER_STATEMENT) {
+        let accounts = accounts::Entity::find()
+            .filter(accounts::Column::UserAddress.is_in(addresses.to_vec()))
+            .all(db)
+            .await?;
+        users.extend(accounts.into_iter().map(account_to_user_details));
     }
+
+    Ok(users)
 }
 
-/// Updates an existing user's details in their new location table
+/// Inserts or updates many users in the same tier in the database
+///
+/// Users are matched on their address, so existing rows are updated in place,
+/// tier included, and new users are inserted. New users and tier changes are
+/// notified on `ACCOUNT_CHANGES_CHANNEL`.
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
-/// * `id` - User's ID in the database
-/// * `user` - Updated user details
-/// * `new_location` - Target table/location where the user should be updated
+/// * `db` - Database connection or transaction
+/// * `users` - User details to be written
+/// * `location` - Tier the users should be in after the write
 ///
 /// # Returns
 ///
-/// * `Result<()>` - Success or error if update fails
-pub async fn update_user(
-    db: &DatabaseConnection,
-    id: i32,
-    user: UserDetails,
-    new_location: UserCurrentLocation,
+/// * `Result<()>` - Success or error if the write fails
+pub async fn upsert_users(
+    db: &impl ConnectionTrait,
+    users: Vec<UserDetails>,
+    location: UserCurrentLocation,
 ) -> Result<()> {
-    match new_location {
-        UserCurrentLocation::Liquidatable => {
-            let mut active_model = user_details_to_liquidatable_account(&user);
-            active_model.id = Set(id);
-            active_model.update(db).await?;
-        }
-        UserCurrentLocation::AtRisk => {
-            let mut active_model = user_details_to_at_risk_account(&user);
-            active_model.id = Set(id);
-            active_model.update(db).await?;
-        }
-        UserCurrentLocation::Healthy => {
-            let mut active_model = user_details_to_healthy_account(&user);
-            active_model.id = Set(id);
-            active_model.update(db).await?;
-        }
-        UserCurrentLocation::NotFound => {
-            return Err(anyhow::anyhow!("User not found"));
+    let tier = location.tier()?;
+
+    for users in users.chunks(MAX_ROWS_PER_STATEMENT) {
+        accounts::Entity::insert_many(users.iter().map(|user| user_details_to_account(user, tier)))
+            .on_conflict(
+                OnConflict::column(accounts::Column::UserAddress)
+                    .update_columns([
+                        accounts::Column::Tier,
+                        accounts::Column::LastUpdatedBlockNumber,
+                        accounts::Column::HealthFactor,
+                        accounts::Column::TotalCollateralValueInUsd,
+                        accounts::Column::TotalDebtValueInUsd,
+                        accounts::Column::EmodeCategory,
+                        accounts::Column::IsInIsolationMode,
+                        accounts::Column::HasSiloedBorrowing,
+                        accounts::Column::LeadingCollateralReserve,
+                        accounts::Column::LeadingDebtReserve,
+                        accounts::Column::LeadingCollateralReserveValue,
+                        accounts::Column::LeadingDebtReserveValue,
+                        accounts::Column::ProjectedHealthFactor,
+                        accounts::Column::Timestamp,
+                    ])
+                    .to_owned(),
+            )
+            .exec(db)
+            .await?;
+    }
+
+    account_changes_helper::notify_account_changes(db, tier_changes(&users, &location)?).await?;
+
+    Ok(())
+}
+
+/// Returns the tier changes of users about to be written to the given tier
+///
+/// # Arguments
+///
+/// * `users` - User details to be written, with the location they are stored in
+/// * `location` - Tier the users should be in after the write
+///
+/// # Returns
+///
+/// * `Result<Vec<AccountChange>>` - The changes of the new users and of the users changing tier
+fn tier_changes(
+    users: &[UserDetails],
+    location: &UserCurrentLocation,
+) -> Result<Vec<AccountChange>> {
+    let new_tier = location.tier()?;
+
+    Ok(users
+        .iter()
+        .filter(|user| user.current_location != *location)
+        .map(|user| AccountChange {
+            user_address: user.user_address.clone(),
+            old_tier: user.current_location.tier().ok(),
+            new_tier,
+            health_factor: user.health_factor,
+            block_number: user.last_updated_block_number,
+        })
+        .collect())
+}
+
+/// Stores the projected health factor of many users
+///
+/// Only the projection is written, the on-chain state of the users is left untouched.
+/// The projections are written with one statement per `MAX_ROWS_PER_STATEMENT` users.
+/// Pass a transaction to write all projections atomically.
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `projected_health_factors` - Vector of (user_address, projected_health_factor) tuples
+///
+/// # Returns
+///
+/// * `Result<()>` - Success or error result of the database operation
+pub async fn update_projected_health_factors(
+    db: &impl ConnectionTrait,
+    projected_health_factors: Vec<(String, Decimal)>,
+) -> Result<()> {
+    for projected_health_factors in projected_health_factors.chunks(MAX_ROWS_PER_STATEMENT) {
+        let mut values: Vec<Value> = Vec::with_capacity(projected_health_factors.len() * 2);
+        for (user_address, projected_health_factor) in projected_health_factors {
+            values.push(user_address.clone().into());
+            values.push((*projected_health_factor).into());
         }
+
+        let projections = (0..projected_health_factors.len())
+            .map(|index| format!("(${}::text, ${}::numeric)", index * 2 + 1, index * 2 + 2))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            format!(
+                "UPDATE accounts SET projected_health_factor = projections.health_factor \
+                 FROM (VALUES {}) AS projections(user_address, health_factor) \
+                 WHERE accounts.user_address = projections.user_address",
+                projections
+            ),
+            values,
+        ))
+        .await?;
     }
     Ok(())
 }
 
-/// Retrieves all user addresses from the liquidatable accounts table
+/// Retrieves a page of the users in the given tier
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
+/// * `location` - Tier to list the users of
+/// * `sort_column` - Column the users are sorted by
+/// * `descending` - Whether the users are sorted in descending order
+/// * `limit` - Maximum number of users returned
+/// * `offset` - Number of users skipped
+///
+/// # Returns
+///
+/// * `Result<(Vec<UserDetails>, u64)>` - The users of the page and the number of users in the tier
+pub async fn get_users_in_tier_page(
+    db: &impl ConnectionTrait,
+    location: UserCurrentLocation,
+    sort_column: AccountsSortColumn,
+    descending: bool,
+    limit: u64,
+    offset: u64,
+) -> Result<(Vec<UserDetails>, u64)> {
+    let tier = location.tier()?;
+    let column = match sort_column {
+        AccountsSortColumn::HealthFactor => accounts::Column::HealthFactor,
+        AccountsSortColumn::TotalDebt => accounts::Column::TotalDebtValueInUsd,
+    };
+    let order = if descending {
+        sea_orm::Order::Desc
+    } else {
+        sea_orm::Order::Asc
+    };
+
+    let total = accounts::Entity::find()
+        .filter(accounts::Column::Tier.eq(tier))
+        .count(db)
+        .await?;
+
+    // Sorting on the address too keeps pages stable between equal values
+    let accounts = accounts::Entity::find()
+        .filter(accounts::Column::Tier.eq(tier))
+        .order_by(column, order)
+        .order_by_asc(accounts::Column::UserAddress)
+        .limit(limit)
+        .offset(offset)
+        .all(db)
+        .await?;
+
+    Ok((
+        accounts.into_iter().map(account_to_user_details).collect(),
+        total,
+    ))
+}
+
+/// Retrieves all user addresses in the given tier
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+/// * `tier` - Tier to list the users of
+///
+/// # Returns
+///
+/// * `Result<Vec<String>>` - List of user addresses in the tier
+async fn get_all_users_in_tier(
+    db: &impl ConnectionTrait,
+    tier: AccountTier,
+) -> Result<Vec<String>> {
+    let users = accounts::Entity::find()
+        .select_only()
+        .column(accounts::Column::UserAddress)
+        .filter(accounts::Column::Tier.eq(tier))
+        .into_tuple::<String>()
+        .all(db)
+        .await?;
+    Ok(users)
+}
+
+/// Counts the users in each tier
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+///
+/// * `Result<Vec<(UserCurrentLocation, u64)>>` - Number of users of every tier, empty tiers included
+pub async fn count_users_per_tier(
+    db: &impl ConnectionTrait,
+) -> Result<Vec<(UserCurrentLocation, u64)>> {
+    let counts: Vec<(AccountTier, i64)> = accounts::Entity::find()
+        .select_only()
+        .column(accounts::Column::Tier)
+        .column_as(Expr::col(accounts::Column::Id).count(), "count")
+        .group_by(accounts::Column::Tier)
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    Ok([
+        AccountTier::Liquidatable,
+        AccountTier::AtRisk,
+        AccountTier::Healthy,
+    ]
+    .into_iter()
+    .map(|tier| {
+        let count = counts
+            .iter()
+            .find(|(counted_tier, _)| *counted_tier == tier)
+            .map(|(_, count)| *count as u64)
+            .unwrap_or_default();
+        (tier.into(), count)
+    })
+    .collect())
+}
+
+/// Retrieves all user addresses in the liquidatable tier
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
 ///
 /// # Returns
 ///
 /// * `Result<Vec<String>>` - List of user addresses in liquidatable state
-pub async fn get_all_liquidatable_users(db: &DatabaseConnection) -> Result<Vec<String>> {
-    let users = liquidatable_accounts::Entity::find().all(db).await?;
-    Ok(users.into_iter().map(|user| user.user_address).collect())
+pub async fn get_all_liquidatable_users(db: &impl ConnectionTrait) -> Result<Vec<String>> {
+    get_all_users_in_tier(db, AccountTier::Liquidatable).await
+}
+
+/// Retrieves the time of the least recent refresh of a liquidatable user
+///
+/// # Arguments
+///
+/// * `db` - Database connection or transaction
+///
+/// # Returns
+///
+/// * `Result<Option<DateTime<Utc>>>` - Time of the oldest refresh, `None` if no user is liquidatable
+pub async fn get_oldest_liquidatable_user_timestamp(
+    db: &impl ConnectionTrait,
+) -> Result<Option<DateTime<Utc>>> {
+    let timestamp: Option<Option<chrono::NaiveDateTime>> = accounts::Entity::find()
+        .select_only()
+        .column_as(Expr::col(accounts::Column::Timestamp).min(), "timestamp")
+        .filter(accounts::Column::Tier.eq(AccountTier::Liquidatable))
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    Ok(timestamp
+        .flatten()
+        .map(|timestamp| DateTime::from_naive_utc_and_offset(timestamp, Utc)))
 }
 
-/// Retrieves all user addresses from the at-risk accounts table
+/// Retrieves all user addresses in the at-risk tier
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 ///
 /// # Returns
 ///
 /// * `Result<Vec<String>>` - List of user addresses in at-risk state
-pub async fn get_all_at_risk_users(db: &DatabaseConnection) -> Result<Vec<String>> {
-    let users = at_risk_accounts::Entity::find().all(db).await?;
-    Ok(users.into_iter().map(|user| user.user_address).collect())
+pub async fn get_all_at_risk_users(db: &impl ConnectionTrait) -> Result<Vec<String>> {
+    get_all_users_in_tier(db, AccountTier::AtRisk).await
 }
 
-/// Retrieves all user addresses from the healthy accounts table
+/// Retrieves all user addresses in the healthy tier
 ///
 /// # Arguments
 ///
-/// * `db` - Database connection
+/// * `db` - Database connection or transaction
 ///
 /// # Returns
 ///
 /// * `Result<Vec<String>>` - List of user addresses in healthy state
-pub async fn get_all_healthy_users(db: &DatabaseConnection) -> Result<Vec<String>> {
-    let users = healthy_accounts::Entity::find().all(db).await?;
-    Ok(users.into_iter().map(|user| user.user_address).collect())
+pub async fn get_all_healthy_users(db: &impl ConnectionTrait) -> Result<Vec<String>> {
+    get_all_users_in_tier(db, AccountTier::Healthy).await
 }